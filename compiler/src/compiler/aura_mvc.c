@@ -25,12 +25,14 @@ typedef char* (*AuraControllerFunc)(void* instance, long long param);
 typedef struct {
     char path[64];
     AuraControllerFunc func;
+    void* env; // Captured variables of a function registered with aura_mvc_route
+    int has_env; // Otherwise func is a controller method and gets the served instance
 } Route;
 
 static Route registry[32];
 static int route_count = 0;
 
-void aura_mvc_register(const char* path, AuraControllerFunc func_ptr) {
+static void add_route(const char* path, AuraControllerFunc func_ptr, void* env, int has_env) {
     if (route_count < 32) {
         // Safe copy instead of strncpy
         size_t i = 0;
@@ -39,94 +41,19 @@ void aura_mvc_register(const char* path, AuraControllerFunc func_ptr) {
         }
         registry[route_count].path[i] = '\0';
         registry[route_count].func = func_ptr;
+        registry[route_count].env = env;
+        registry[route_count].has_env = has_env;
         route_count++;
     }
 }
 
-// --- AuraView Engine: Template Processor ---
-
-// Simple string replace (MVC version of the Aura Runtime)
-static char* mvc_str_replace(const char* orig, const char* rep, const char* with) {
-    if (!orig || !rep || !with) return (char*)orig;
-    char* result;
-    char* ins;
-    char* tmp;
-    int len_rep = strlen(rep);
-    int len_with = strlen(with);
-    int count;
-
-    ins = (char*)orig;
-    for (count = 0; (tmp = strstr(ins, rep)); ++count) {
-        ins = tmp + len_rep;
-    }
-
-    tmp = result = malloc(strlen(orig) + (len_with - len_rep) * count + 1);
-    if (!result) return NULL;
-
-    while (count--) {
-        ins = strstr(orig, rep);
-        int len_front = ins - orig;
-        tmp = strncpy(tmp, orig, len_front) + len_front;
-        tmp = strcpy(tmp, with) + len_with;
-        orig += len_front + len_rep;
-    }
-    strcpy(tmp, orig);
-    return result;
+void aura_mvc_register(const char* path, AuraControllerFunc func_ptr) {
+    add_route(path, func_ptr, NULL, 0);
 }
 
-// Renders a list (array of structs)
-// snippet: HTML part inside the foreach
-// data: Aura Array pointer (i64 array)
-// len: Array length
-// field_names: Class field names (comma separated)
-char* aura_mvc_render_list(const char* snippet, long long* data, int len, const char* field_names) {
-    char* final_res = malloc(1);
-    final_res[0] = '\0';
-    int total_len = 0;
-
-    char* fields[16];
-    int f_count = 0;
-    char* f_copy = _strdup(field_names);
-    char* token = strtok(f_copy, ",");
-    while (token && f_count < 16) {
-        fields[f_count++] = token;
-        token = strtok(NULL, ",");
-    }
-
-    for (int i = 0; i < len; i++) {
-        // Each item is essentially a struct pointer (or packed as i64)
-        long long item_ptr = data[i];
-        char* item_html = _strdup(snippet);
-
-        for (int f = 0; f < f_count; f++) {
-            char tag[64];
-            sprintf(tag, "{model.%s}", fields[f]);
-            
-            // In Aura 64-bit, every field is 8-byte (i64)
-            long long* field_ptr = (long long*)(item_ptr + (f * 8));
-            long long val = *field_ptr;
-
-            char val_str[64];
-            if (val > 0x10000) { // Probability of being a string pointer
-                char* replaced = mvc_str_replace(item_html, tag, (char*)val);
-                free(item_html);
-                item_html = replaced;
-            } else {
-                sprintf(val_str, "%lld", val);
-                char* replaced = mvc_str_replace(item_html, tag, val_str);
-                free(item_html);
-                item_html = replaced;
-            }
-        }
-
-        total_len += strlen(item_html);
-        final_res = realloc(final_res, total_len + 1);
-        strcat(final_res, item_html);
-        free(item_html);
-    }
-
-    free(f_copy);
-    return final_res;
+// A function value as a route: its code takes the closure environment where methods take the instance
+void aura_mvc_route(const char* path, AuraControllerFunc func_ptr, void* env) {
+    add_route(path, func_ptr, env, 1);
 }
 
 void aura_mvc_serve(long long sock_ll, void* instance) {
@@ -182,7 +109,8 @@ void aura_mvc_serve(long long sock_ll, void* instance) {
                             }
                         }
 
-                        char* response_body = registry[i].func(instance, param);
+                        void* context = registry[i].has_env ? registry[i].env : instance;
+                        char* response_body = registry[i].func(context, param);
                         
                         char header[256];
                         int h_len = sprintf(header, "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: %zu\r\n\r\n", strlen(response_body));
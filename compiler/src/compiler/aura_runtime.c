@@ -1,6 +1,8 @@
+#include <stdarg.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <errno.h>
 
 #ifdef _WIN32
     #define UNICODE
@@ -15,13 +17,19 @@
     #include <sys/socket.h>
     #include <netinet/in.h>
     #include <unistd.h>
-    #include <errno.h>
 #endif
 
 void aura_print_int(long long val) {
     printf("%lld\n", val);
 }
 
+// Runtime error (e.g. integer overflow in a checked build); never returns
+void aura_panic(const char* msg) {
+    fflush(stdout);
+    fprintf(stderr, "error: %s\n", msg);
+    abort();
+}
+
 void aura_print_str(const char* val) {
     printf("%s\n", val);
 }
@@ -47,6 +55,39 @@ char* aura_str_concat(const char* s1, const char* s2) {
     return res;
 }
 
+// Joins `count` strings (the parts of an interpolated string) in one allocation
+char* aura_str_join(long long count, ...) {
+    va_list ap;
+    size_t total = 0;
+    va_start(ap, count);
+    for (long long i = 0; i < count; i++) {
+        const char* s = va_arg(ap, const char*);
+        if (s) total += strlen(s);
+    }
+    va_end(ap);
+    char* res = malloc(total + 1);
+    char* p = res;
+    va_start(ap, count);
+    for (long long i = 0; i < count; i++) {
+        const char* s = va_arg(ap, const char*);
+        if (!s) continue;
+        size_t n = strlen(s);
+        memcpy(p, s, n);
+        p += n;
+    }
+    va_end(ap);
+    *p = 0;
+    return res;
+}
+
+// Compares two strings byte by byte: -1, 0 or 1 like strcmp's sign
+long long aura_str_compare(const char* s1, const char* s2) {
+    if (!s1) s1 = "";
+    if (!s2) s2 = "";
+    int c = strcmp(s1, s2);
+    return (c > 0) - (c < 0);
+}
+
 long long aura_net_setup(long long port) {
 #ifdef _WIN32
     WSADATA wsa;
@@ -99,7 +140,7 @@ char* aura_str_replace(const char* orig, const char* rep, const char* with) {
 
     if (!orig || !rep) return NULL;
     len_rep = strlen(rep);
-    if (len_rep == 0) return NULL; 
+    if (len_rep == 0) return aura_str_concat(orig, ""); // Nothing to replace: a copy
     if (!with) with = "";
     len_with = strlen(with);
 
@@ -129,15 +170,327 @@ char* aura_int_to_str(long long n) {
     return s;
 }
 
-char* aura_render_field(char* tpl, char* key, long long value) {
-    // Threshold heuristic to distinguish pointer vs small integer
-    // 0x10000 is a safe bet for modern OS minimum memory address
-    if (value > 0x10000 || value < -0x10000) {
-        return aura_str_replace(tpl, key, (char*)value);
+// Up to 15 significant digits; whole numbers keep a ".0" so they read as floats
+char* aura_float_to_str(double f) {
+    char* s = malloc(40);
+    snprintf(s, 40, "%.15g", f);
+    if (!strpbrk(s, ".eEn")) strcat(s, ".0");
+    return s;
+}
+
+void aura_print_float(double val) {
+    char* s = aura_float_to_str(val);
+    printf("%s\n", s);
+    free(s);
+}
+
+
+// Growable array behind Aura's [T] type. Elements are stored inline, elem_size
+// bytes each; compiled code reads and writes them through `data` directly.
+// Layout must match %aura.array in the generated IR.
+typedef struct {
+    long long len;
+    long long cap;
+    long long elem_size;
+    char* data;
+} AuraArray;
+
+// New array of `len` zeroed elements
+AuraArray* aura_array_new(long long elem_size, long long len) {
+    AuraArray* arr = malloc(sizeof(AuraArray));
+    arr->len = len;
+    arr->cap = len > 4 ? len : 4;
+    arr->elem_size = elem_size;
+    arr->data = calloc(arr->cap, elem_size);
+    return arr;
+}
+
+// Appends an element and returns the slot to store it in
+void* aura_array_push(AuraArray* arr) {
+    if (arr->len == arr->cap) {
+        arr->cap *= 2;
+        arr->data = realloc(arr->data, arr->cap * arr->elem_size);
+    }
+    return arr->data + arr->len++ * arr->elem_size;
+}
+
+// Removes the last element and returns the slot it is still stored in
+void* aura_array_pop(AuraArray* arr) {
+    if (arr->len == 0) aura_panic("pop() on an empty array");
+    return arr->data + --arr->len * arr->elem_size;
+}
+
+// Out of bounds array index; never returns
+void aura_bounds_fail(long long index, long long len, const char* location) {
+    char msg[256];
+    snprintf(msg, sizeof msg, "index %lld out of bounds for length %lld at %s", index, len, location);
+    aura_panic(msg);
+}
+
+
+// Hash map behind Aura's {K: V} type. Keys are ints or strings, passed as a
+// 64-bit value either way; string keys are copied and compared by content.
+// Values are stored inline, val_size bytes each, like array elements.
+// Entries stay in insertion order; removed ones are skipped until the next rebuild.
+typedef struct {
+    long long key_is_str;
+    long long val_size;
+    long long len;        // Live entries
+    long long count;      // Used entries, removed ones included
+    long long cap;        // Entry capacity
+    long long* keys;
+    unsigned long long* hashes;
+    char* live;
+    char* vals;
+    long long* index;     // 2 * cap slots holding an entry number, or -1 when empty
+} AuraMap;
+
+static unsigned long long aura_map_hash(AuraMap* map, long long key) {
+    unsigned long long h;
+    if (map->key_is_str) {
+        // FNV-1a
+        h = 14695981039346656037ULL;
+        for (const unsigned char* s = (const unsigned char*)(size_t)key; *s; s++) {
+            h = (h ^ *s) * 1099511628211ULL;
+        }
     } else {
-        char* str_val = aura_int_to_str((int)value);
-        char* res = aura_str_replace(tpl, key, str_val);
-        free(str_val);
-        return res;
+        // splitmix64 finalizer
+        h = (unsigned long long)key + 0x9E3779B97F4A7C15ULL;
+        h = (h ^ (h >> 30)) * 0xBF58476D1CE4E5B9ULL;
+        h = (h ^ (h >> 27)) * 0x94D049BB133111EBULL;
+        h ^= h >> 31;
+    }
+    return h;
+}
+
+static int aura_map_key_eq(AuraMap* map, long long a, long long b) {
+    if (map->key_is_str) return strcmp((const char*)(size_t)a, (const char*)(size_t)b) == 0;
+    return a == b;
+}
+
+// Entry number of `key`, or -1
+static long long aura_map_find(AuraMap* map, long long key, unsigned long long hash) {
+    unsigned long long mask = map->cap * 2 - 1;
+    for (unsigned long long i = hash & mask; map->index[i] != -1; i = (i + 1) & mask) {
+        long long e = map->index[i];
+        if (map->live[e] && map->hashes[e] == hash && aura_map_key_eq(map, map->keys[e], key)) return e;
+    }
+    return -1;
+}
+
+// Drops removed entries and resizes to `cap` entries (a power of two)
+static void aura_map_rebuild(AuraMap* map, long long cap) {
+    long long n = 0;
+    for (long long e = 0; e < map->count; e++) {
+        if (!map->live[e]) continue;
+        map->keys[n] = map->keys[e];
+        map->hashes[n] = map->hashes[e];
+        memmove(map->vals + n * map->val_size, map->vals + e * map->val_size, map->val_size);
+        map->live[n] = 1;
+        n++;
+    }
+    map->count = n;
+    map->cap = cap;
+    map->keys = realloc(map->keys, cap * sizeof(long long));
+    map->hashes = realloc(map->hashes, cap * sizeof(unsigned long long));
+    map->live = realloc(map->live, cap);
+    map->vals = realloc(map->vals, cap * map->val_size);
+    free(map->index);
+    map->index = malloc(cap * 2 * sizeof(long long));
+    memset(map->index, 0xFF, cap * 2 * sizeof(long long));
+    unsigned long long mask = cap * 2 - 1;
+    for (long long e = 0; e < n; e++) {
+        unsigned long long i = map->hashes[e] & mask;
+        while (map->index[i] != -1) i = (i + 1) & mask;
+        map->index[i] = e;
+    }
+}
+
+AuraMap* aura_map_new(long long key_is_str, long long val_size) {
+    AuraMap* map = calloc(1, sizeof(AuraMap));
+    map->key_is_str = key_is_str;
+    map->val_size = val_size;
+    aura_map_rebuild(map, 8);
+    return map;
+}
+
+// Slot of the value stored under `key`; stops the program if there is none
+void* aura_map_get(AuraMap* map, long long key, const char* location) {
+    long long e = aura_map_find(map, key, aura_map_hash(map, key));
+    if (e == -1) {
+        char msg[512];
+        if (map->key_is_str) snprintf(msg, sizeof msg, "key \"%.200s\" not found in map at %s", (const char*)(size_t)key, location);
+        else snprintf(msg, sizeof msg, "key %lld not found in map at %s", key, location);
+        aura_panic(msg);
+    }
+    return map->vals + e * map->val_size;
+}
+
+// Slot to store the value for `key` in, adding a zeroed entry if it is new
+void* aura_map_set(AuraMap* map, long long key) {
+    unsigned long long hash = aura_map_hash(map, key);
+    long long e = aura_map_find(map, key, hash);
+    if (e != -1) return map->vals + e * map->val_size;
+    if (map->count == map->cap) {
+        // Grow only when mostly live; otherwise reclaiming removed entries is enough
+        aura_map_rebuild(map, map->len * 2 >= map->cap ? map->cap * 2 : map->cap);
+    }
+    if (map->key_is_str) {
+        const char* s = (const char*)(size_t)key;
+        size_t n = strlen(s) + 1;
+        char* copy = malloc(n);
+        memcpy(copy, s, n);
+        key = (long long)(size_t)copy;
+    }
+    e = map->count++;
+    map->keys[e] = key;
+    map->hashes[e] = hash;
+    map->live[e] = 1;
+    memset(map->vals + e * map->val_size, 0, map->val_size);
+    unsigned long long mask = map->cap * 2 - 1;
+    unsigned long long i = hash & mask;
+    while (map->index[i] != -1) i = (i + 1) & mask;
+    map->index[i] = e;
+    map->len++;
+    return map->vals + e * map->val_size;
+}
+
+long long aura_map_has(AuraMap* map, long long key) {
+    return aura_map_find(map, key, aura_map_hash(map, key)) != -1;
+}
+
+// Removing a missing key does nothing
+void aura_map_remove(AuraMap* map, long long key) {
+    long long e = aura_map_find(map, key, aura_map_hash(map, key));
+    if (e == -1) return;
+    map->live[e] = 0;
+    map->len--;
+}
+
+long long aura_map_len(AuraMap* map) {
+    return map->len;
+}
+
+// The keys in insertion order, as a new [int] or [string] array
+AuraArray* aura_map_keys(AuraMap* map) {
+    AuraArray* arr = aura_array_new(sizeof(long long), map->len);
+    long long* out = (long long*)arr->data;
+    for (long long e = 0; e < map->count; e++) {
+        if (map->live[e]) *out++ = map->keys[e];
     }
+    return arr;
+}
+
+
+// --- system.str ---
+// Lengths and positions count bytes, like the rest of the runtime's C strings.
+
+long long aura_str_len(const char* s) {
+    return s ? (long long)strlen(s) : 0;
+}
+
+// Up to `count` bytes from `start`; both are clamped to the string
+char* aura_str_substr(const char* s, long long start, long long count) {
+    long long len = aura_str_len(s);
+    if (start < 0) start = 0;
+    if (start > len) start = len;
+    if (count < 0) count = 0;
+    if (count > len - start) count = len - start;
+    char* res = malloc(count + 1);
+    if (count > 0) memcpy(res, s + start, count);
+    res[count] = 0;
+    return res;
+}
+
+static char* aura_str_slice(const char* start, size_t len) {
+    char* res = malloc(len + 1);
+    memcpy(res, start, len);
+    res[len] = 0;
+    return res;
+}
+
+static size_t aura_utf8_len(unsigned char lead) {
+    if (lead >= 0xF0) return 4;
+    if (lead >= 0xE0) return 3;
+    if (lead >= 0xC0) return 2;
+    return 1;
+}
+
+// The parts between occurrences of `sep`, as a [string] array.
+// An empty separator splits the string into its characters.
+AuraArray* aura_str_split(const char* s, const char* sep) {
+    if (!s) s = "";
+    if (!sep) sep = "";
+    AuraArray* arr = aura_array_new(sizeof(char*), 0);
+    size_t sep_len = strlen(sep);
+    if (sep_len == 0) {
+        while (*s) {
+            size_t n = strnlen(s, aura_utf8_len((unsigned char)*s)); // Stop at a truncated sequence
+            *(char**)aura_array_push(arr) = aura_str_slice(s, n);
+            s += n;
+        }
+        return arr;
+    }
+    const char* hit;
+    while ((hit = strstr(s, sep)) != NULL) {
+        *(char**)aura_array_push(arr) = aura_str_slice(s, hit - s);
+        s = hit + sep_len;
+    }
+    *(char**)aura_array_push(arr) = aura_str_slice(s, strlen(s));
+    return arr;
+}
+
+static int aura_is_space(char c) {
+    return c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\v' || c == '\f';
+}
+
+// Copy without leading and trailing whitespace
+char* aura_str_trim(const char* s) {
+    if (!s) s = "";
+    const char* end = s + strlen(s);
+    while (aura_is_space(*s)) s++;
+    while (end > s && aura_is_space(end[-1])) end--;
+    return aura_str_slice(s, end - s);
+}
+
+// Position of the first occurrence of `pattern`, or -1
+long long aura_str_index(const char* s, const char* pattern) {
+    if (!s || !pattern) return -1;
+    const char* hit = strstr(s, pattern);
+    return hit ? hit - s : -1;
+}
+
+long long aura_str_starts_with(const char* s, const char* prefix) {
+    if (!s || !prefix) return 0;
+    return strncmp(s, prefix, strlen(prefix)) == 0;
+}
+
+// Decimal int with optional surrounding whitespace and sign; 0 if the string is not a number
+long long aura_str_to_int(const char* s) {
+    if (!s) return 0;
+    char* end;
+    errno = 0;
+    long long n = strtoll(s, &end, 10);
+    if (end == s || errno == ERANGE) return 0;
+    while (aura_is_space(*end)) end++;
+    return *end ? 0 : n;
+}
+
+// ASCII case conversion; other bytes are copied unchanged
+static char* aura_str_map_case(const char* s, int upper) {
+    if (!s) s = "";
+    char* res = aura_str_slice(s, strlen(s));
+    for (char* p = res; *p; p++) {
+        if (upper && *p >= 'a' && *p <= 'z') *p -= 'a' - 'A';
+        if (!upper && *p >= 'A' && *p <= 'Z') *p += 'a' - 'A';
+    }
+    return res;
+}
+
+char* aura_str_to_upper(const char* s) {
+    return aura_str_map_case(s, 1);
+}
+
+char* aura_str_to_lower(const char* s) {
+    return aura_str_map_case(s, 0);
 }
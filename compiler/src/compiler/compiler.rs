@@ -1,44 +1,89 @@
 use std::collections::{HashMap, HashSet};
+use crate::compiler::diagnostic::{CompileResult, Diagnostic};
 use crate::compiler::lexer::TokenType;
-use crate::compiler::parser::{Expr, Stmt};
+use crate::compiler::parser::{lambda_name, Expr, ExprKind, Lambda, Stmt, StmtKind, TypedName};
+use crate::compiler::sema::{expect_args, str_function, type_error, undefined_variable, unknown_method};
+use crate::compiler::source::Span;
+use crate::compiler::types::{FuncSig, TypeTable, VarType};
 
 #[derive(Clone, PartialEq, Debug)]
+#[allow(dead_code)] // Only the host's variant is constructed
 pub enum TargetOs {
     Windows,
     Linux,
     MacOS,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-enum VarType { 
-    Int, 
-    Str, 
-    Bool,
-    Array(Box<VarType>, usize),
-    Instance(String) 
+/// What a function receives before its parameters.
+enum Receiver<'a> {
+    None,
+    This(&'a str), // A method of this class: the object
+    Env(&'a [(String, VarType)]), // A lambda: its environment, pointers to these captured variables
+}
+
+/// The state of the function being compiled, set aside while another one is compiled.
+struct FunctionState {
+    in_function: bool,
+    var_types: HashMap<String, VarType>,
+    slots: HashMap<String, String>,
+    used_slots: HashSet<String>,
+    boxed: HashSet<String>,
+    escaping: HashSet<String>,
+    output: String,
+    allocas: String,
+    inits: Vec<String>,
+    terminated: bool,
+    scopes: Vec<Vec<String>>,
+    loops: Vec<(String, String, usize)>,
+    ret: VarType,
+}
+
+/// The compile-time value of a top-level declaration.
+#[derive(Clone)]
+enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
 }
 
 pub struct Compiler {
     output: String,     
     main_body: String,  
     current_output: String, // Buffer for functions
+    entry_allocas: String, // Allocas of the current function (or main), placed at the top of its entry block
+    global_inits: Vec<String>, // Globals computed at run time that the current function uses; it initializes them on entry
     
     reg_counter: i64,
     label_counter: i64,
     str_counter: i64,
     string_literals: Vec<(i64, String, usize)>,
+    global_defs: Vec<String>, // Definitions of the @g_ globals for top-level variables and constants
+    global_values: HashMap<String, Constant>, // Globals whose value is known at compile time
+    lazy_globals: HashSet<String>, // Other globals: @g_X.init computes them before their first use
     
     var_types: HashMap<String, VarType>, 
+    slots: HashMap<String, String>, // Variable name -> its alloca register (e.g. %x_ptr, %x.1_ptr)
+    used_slots: HashSet<String>, // Alloca registers taken in the current function
+    boxed: HashSet<String>, // Variables of the current function that closures capture; they live on the heap
+    escaping: HashSet<String>, // Variables of the current function whose objects its scopes don't free
+    function_values: HashSet<String>, // Functions whose closure constant has been emitted
+    lambdas: HashSet<String>, // Lambdas whose function has been emitted
     is_in_function: bool, 
     
-    classes: HashMap<String, Vec<String>>, // ClassName -> [FieldNames]
-    class_methods: HashMap<String, Vec<String>>, // ClassName -> [MethodNames]
     current_class: Option<String>,
+    current_ret: VarType, // Return type of the function being compiled
     pub target_os: TargetOs,
     scope_stack: Vec<Vec<String>>, // Stack of blocks, each containing variable names (Instances) to cleanup
     block_terminated: bool, // Tracking if 'ret' or 'br' was emitted in current block
+    loop_labels: Vec<(String, String, usize)>, // Enclosing loops: (continue label, break label, scope depth)
     system_modules: Vec<String>, // Tracks imported system library modules (e.g. "system.net")
     required_symbols: HashSet<String>, // LAZY DECLARATIONS
+    pub warnings: Vec<Diagnostic>, // Non-fatal diagnostics collected during compilation
+    types: TypeTable, // Function and class signatures inferred by sema
+    file_names: Vec<String>, // Indexed by FileId, for the locations in runtime error messages
+    overflow_checks: bool, // Int +, -, * trap on overflow (--checked)
+    bounds_checks: bool, // Array indexing traps when out of bounds (off with --unchecked)
 }
 
 impl Compiler {
@@ -58,20 +103,36 @@ impl Compiler {
             output: String::new(),
             main_body: String::new(),
             current_output: String::new(),
+            entry_allocas: String::new(),
+            global_inits: Vec::new(),
             reg_counter: 1, 
             label_counter: 0,
             str_counter: 0,
             string_literals: Vec::new(),
+            global_defs: Vec::new(),
+            global_values: HashMap::new(),
+            lazy_globals: HashSet::new(),
             var_types: HashMap::new(),
+            slots: HashMap::new(),
+            used_slots: HashSet::new(),
+            boxed: HashSet::new(),
+            escaping: HashSet::new(),
+            function_values: HashSet::new(),
+            lambdas: HashSet::new(),
             is_in_function: false,
-            classes: HashMap::new(),
-            class_methods: HashMap::new(),
             current_class: None,
+            current_ret: VarType::Void,
+            file_names: Vec::new(),
+            overflow_checks: false,
+            bounds_checks: true,
             target_os: target,
             scope_stack: Vec::new(),
             block_terminated: false,
+            loop_labels: Vec::new(),
             system_modules: Vec::new(),
             required_symbols: HashSet::new(),
+            warnings: Vec::new(),
+            types: TypeTable::default(),
         }
     }
 
@@ -107,6 +168,298 @@ impl Compiler {
         format!("@str.{}", id)
     }
 
+    /// Allocates the alloca register for a variable declaration. A name that is
+    /// declared again in another block of the same function (e.g. two loops using
+    /// `var i`) gets a numbered register so the allocas don't clash.
+    fn declare_slot(&mut self, name: &str) -> String {
+        let mut slot = format!("%{}_ptr", name);
+        let mut n = 0;
+        while self.used_slots.contains(&slot) {
+            n += 1;
+            slot = format!("%{}.{}_ptr", name, n);
+        }
+        self.used_slots.insert(slot.clone());
+        self.slots.insert(name.to_string(), slot.clone());
+        slot
+    }
+
+    /// Emits the storage of a variable: an alloca in the entry block, or a heap cell when
+    /// a closure captures the variable and may outlive the function.
+    fn emit_var_storage(&mut self, name: &str, slot: &str, ty: &str) {
+        if !self.boxed.contains(name) {
+            self.emit_alloca(slot, ty);
+            return;
+        }
+        // Every value fits in 8 bytes
+        let cell = self.get_reg();
+        self.emit(&format!("  {} = call i8* @malloc(i64 8)\n", cell));
+        self.emit(&format!("  {} = bitcast i8* {} to {}*\n", slot, cell, ty));
+    }
+
+    /// Emits an alloca at the top of the function's entry block, so that code run
+    /// repeatedly (e.g. in a loop) reuses the same stack slot instead of growing the stack.
+    fn emit_alloca(&mut self, reg: &str, ty: &str) {
+        self.entry_allocas.push_str(&format!("  {} = alloca {}\n", reg, ty));
+    }
+
+    /// Whether a declaration here defines a global: outside functions and blocks.
+    fn at_top_level(&self) -> bool {
+        !self.is_in_function && self.scope_stack.is_empty()
+    }
+
+    /// The alloca register (or @g_ global) a variable name currently refers to. Functions
+    /// initialize the globals computed at run time that they use on entry, as their
+    /// declarations may not have run yet.
+    fn slot(&mut self, name: &str) -> String {
+        let slot = self.slots.get(name).cloned().unwrap_or_else(|| format!("%{}_ptr", name));
+        if self.is_in_function && self.lazy_globals.contains(name) && slot == format!("@g_{}", name)
+            && !self.global_inits.contains(&slot) {
+            self.global_inits.push(slot.clone());
+        }
+        slot
+    }
+
+    /// Emits a call to a user function or method and returns the result register
+    /// ("0" for functions that don't return a value).
+    fn emit_user_call(&mut self, fn_name: &str, args_str: &str, ret_type: &VarType) -> String {
+        if *ret_type == VarType::Void {
+            self.emit(&format!("  call void @fn_{}({})\n", fn_name, args_str));
+            return "0".to_string();
+        }
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = call {} @fn_{}({})\n", reg, llvm_type(ret_type), fn_name, args_str));
+        reg
+    }
+
+    /// Loads the code pointer and the environment of a closure.
+    fn emit_closure_parts(&mut self, closure: &str) -> (String, String) {
+        let mut parts = Vec::new();
+        for field in 0..2 {
+            let ptr = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds %aura.closure, %aura.closure* {}, i32 0, i32 {}\n", ptr, closure, field));
+            let val = self.get_reg();
+            self.emit(&format!("  {} = load i8*, i8** {}\n", val, ptr));
+            parts.push(val);
+        }
+        let env = parts.pop().expect("two fields");
+        (parts.pop().expect("two fields"), env)
+    }
+
+    /// Calls a function value of type `func_type` with the given arguments.
+    fn emit_value_call(&mut self, closure: &str, func_type: &VarType, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        let VarType::Function(params, ret) = func_type else {
+            return Err(type_error("only functions can be called", func_type));
+        };
+        let arg_vals = self.compile_call_args(args, params)?;
+        Ok((self.emit_closure_call(closure, params, ret, arg_vals), (**ret).clone()))
+    }
+
+    /// Calls a closure with compiled arguments, passing its environment first. Returns the
+    /// result register ("0" for functions that don't return a value).
+    fn emit_closure_call(&mut self, closure: &str, params: &[VarType], ret: &VarType, args: Vec<String>) -> String {
+        let (code, env) = self.emit_closure_parts(closure);
+        let fn_ptr = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to {}\n", fn_ptr, code, closure_fn_type(params, ret)));
+        let mut arg_vals = vec![format!("i8* {}", env)];
+        arg_vals.extend(args);
+        if *ret == VarType::Void {
+            self.emit(&format!("  call void {}({})\n", fn_ptr, arg_vals.join(", ")));
+            return "0".to_string();
+        }
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = call {} {}({})\n", reg, llvm_type(ret), fn_ptr, arg_vals.join(", ")));
+        reg
+    }
+
+    /// The constant closure of a function that captures nothing: a lambda without captures,
+    /// or a named function used as a value (called through a thunk that skips the environment).
+    fn emit_static_closure(&mut self, name: &str, sig: &FuncSig, thunk: bool) -> String {
+        let global = format!("@fn_{}.closure", name);
+        if !self.function_values.insert(name.to_string()) { return global; }
+        let code = if thunk {
+            let params: Vec<String> = sig.params.iter().enumerate().map(|(i, p)| format!("{} %arg{}", llvm_type(p), i)).collect();
+            let ret = llvm_type(&sig.ret);
+            let call = format!("call {} @fn_{}({})", ret, name, params.join(", "));
+            let body = if sig.ret == VarType::Void { format!("  {}\n  ret void\n", call) } else { format!("  %ret = {}\n  ret {} %ret\n", call, ret) };
+            let args: Vec<String> = std::iter::once("i8* %env".to_string()).chain(params).collect();
+            self.output.push_str(&format!("\ndefine {} @fn_{}.thunk({}) {{\nentry:\n{}}}\n", ret, name, args.join(", "), body));
+            format!("{}.thunk", name)
+        } else { name.to_string() };
+        self.global_defs.push(format!("{} = constant %aura.closure {{ i8* bitcast ({} @fn_{} to i8*), i8* null }}", global, closure_fn_type(&sig.params, &sig.ret), code));
+        global
+    }
+
+    /// Compiles a lambda and creates its closure. The environment holds pointers to the
+    /// heap cells of the captured variables, which the code around keeps using too.
+    fn emit_lambda(&mut self, span: Span, lambda: &Lambda) -> CompileResult<(String, VarType)> {
+        let Lambda { params, body, captures, .. } = lambda;
+        let name = lambda_name(span);
+        let sig = self.types.functions[&name].clone();
+        let vtype = VarType::Function(sig.params.clone(), Box::new(sig.ret.clone()));
+        let mut env = Vec::new();
+        for capture in captures {
+            let ty = self.var_types.get(capture).cloned().ok_or_else(|| undefined_variable(capture))?;
+            env.push((capture.clone(), ty));
+        }
+        if self.lambdas.insert(name.clone()) {
+            self.compile_function(&name, Receiver::Env(&env), params, &sig, body)?;
+        }
+        if env.is_empty() {
+            return Ok((self.emit_static_closure(&name, &sig, false), vtype));
+        }
+
+        let env_raw = self.get_reg();
+        self.emit(&format!("  {} = call i8* @malloc(i64 {})\n", env_raw, 8 * env.len()));
+        let cells = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to i8**\n", cells, env_raw));
+        for (i, (capture, ty)) in env.iter().enumerate() {
+            let slot = self.slot(capture);
+            let cell = self.get_reg();
+            self.emit(&format!("  {} = bitcast {}* {} to i8*\n", cell, llvm_type(ty), slot));
+            let cell_ptr = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds i8*, i8** {}, i64 {}\n", cell_ptr, cells, i));
+            self.emit(&format!("  store i8* {}, i8** {}\n", cell, cell_ptr));
+        }
+
+        let raw = self.get_reg();
+        self.emit(&format!("  {} = call i8* @malloc(i64 16)\n", raw));
+        let closure = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to %aura.closure*\n", closure, raw));
+        let code = format!("bitcast ({} @fn_{} to i8*)", closure_fn_type(&sig.params, &sig.ret), name);
+        for (field, val) in [code, env_raw].iter().enumerate() {
+            let ptr = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds %aura.closure, %aura.closure* {}, i32 0, i32 {}\n", ptr, closure, field));
+            self.emit(&format!("  store i8* {}, i8** {}\n", val, ptr));
+        }
+        Ok((closure, vtype))
+    }
+
+    /// Compiles call arguments into a typed `i64 a, i8* b` argument list
+    /// matching the callee's parameter types.
+    fn compile_call_args(&mut self, args: &[Expr], params: &[VarType]) -> CompileResult<Vec<String>> {
+        let mut arg_vals = Vec::new();
+        for (arg, param) in args.iter().zip(params) {
+            let (val, vtype) = self.compile_expr(arg)?;
+            let val = self.coerce(val, &vtype, param);
+            arg_vals.push(format!("{} {}", llvm_type(param), val));
+        }
+        Ok(arg_vals)
+    }
+
+    /// Prepares a value for a slot of type `to` (variable, field, argument or
+    /// return value): string literals become pointers and ints widen to floats.
+    fn coerce(&mut self, val: String, from: &VarType, to: &VarType) -> String {
+        match (from, to) {
+            (VarType::Str, _) => self.ensure_string(val, VarType::Str),
+            (VarType::Int, VarType::Float) => {
+                let reg = self.get_reg();
+                self.emit(&format!("  {} = sitofp i64 {} to double\n", reg, val));
+                reg
+            }
+            (VarType::Instance(class_name), VarType::Instance(base)) => self.emit_upcast(&val, class_name, base),
+            _ => val,
+        }
+    }
+
+    /// Whether a method can serve an MVC route: the runtime calls routes with the
+    /// request's int parameter and sends back the string they return.
+    fn is_route(&self, class_name: &str, method: &str) -> bool {
+        let sig = self.types.method_sig(class_name, method).expect("routes come from the class's methods");
+        method != "init" && sig.params == [VarType::Int] && sig.ret == VarType::Str
+    }
+
+    /// Registers the route methods of a class (own and inherited) as MVC routes named after them.
+    /// For an interface, only its methods are routes, taken from the object's vtable.
+    fn emit_route_registration(&mut self, class_name: &str, obj_val: &str) {
+        if let Some(interface) = self.types.interfaces.get(class_name) {
+            let methods: Vec<String> = interface.methods.iter().map(|(m, _)| m.clone())
+                .filter(|m| self.is_route(class_name, m)).collect();
+            for m in methods {
+                let m_val = self.add_string(m.to_string());
+                let m_ptr = self.get_reg();
+                self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
+                let (slot, _) = self.types.resolve_method(class_name, &m).expect("interface methods have a slot");
+                let fn_raw = self.emit_vtable_load(class_name, obj_val, slot);
+                self.emit(&format!("  call void @aura_mvc_register(i8* {}, i8* {})\n", m_ptr, fn_raw));
+            }
+            return;
+        }
+        let vtable = self.types.classes[class_name].vtable.clone();
+        for (m, owner) in vtable {
+            if !self.is_route(&owner, &m) { continue; }
+            let m_val = self.add_string(m.to_string());
+            let m_ptr = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
+
+            // Get function pointer (fn_Class_Method) of the implementing class
+            let fn_name = format!("fn_{}_{}", owner, m);
+            let fn_type = self.method_fn_type(&owner, &m);
+            self.emit(&format!("  call void @aura_mvc_register(i8* {}, i8* bitcast ({} @{} to i8*))\n", m_ptr, fn_type, fn_name));
+        }
+    }
+
+    /// Calls a method on an object whose static type is `class_name`. `args` are the
+    /// compiled arguments after `this`. Classes that are extended dispatch through the
+    /// object's vtable, as do interfaces; otherwise the implementation is called directly.
+    fn emit_method_call(&mut self, class_name: &str, obj_val: &str, method: &str, args: Vec<String>) -> (String, VarType) {
+        let sig = self.types.method_sig(class_name, method).expect("sema checked the method exists").clone();
+        let (slot, owner) = self.types.resolve_method(class_name, method).expect("sema checked the method exists");
+        // Constructors differ between subclasses and always run the static type's init
+        let is_interface = self.types.interfaces.contains_key(class_name);
+        if !is_interface && (method == "init" || !self.types.has_subclasses(class_name)) {
+            let this = self.emit_upcast(obj_val, class_name, &owner);
+            let mut arg_vals = vec![format!("%struct.{}* {}", owner, this)];
+            arg_vals.extend(args);
+            let val = self.emit_user_call(&format!("{}_{}", owner, method), &arg_vals.join(", "), &sig.ret);
+            return (val, sig.ret);
+        }
+
+        let fn_raw = self.emit_vtable_load(class_name, obj_val, slot);
+        let fn_ptr = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to {}\n", fn_ptr, fn_raw, self.method_fn_type(class_name, method)));
+
+        let mut arg_vals = vec![format!("%struct.{}* {}", class_name, obj_val)];
+        arg_vals.extend(args);
+        if sig.ret == VarType::Void {
+            self.emit(&format!("  call void {}({})\n", fn_ptr, arg_vals.join(", ")));
+            return ("0".to_string(), sig.ret);
+        }
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = call {} {}({})\n", reg, llvm_type(&sig.ret), fn_ptr, arg_vals.join(", ")));
+        (reg, sig.ret)
+    }
+
+    /// Loads the function pointer in `slot` of an object's vtable, as an i8*.
+    fn emit_vtable_load(&mut self, class_name: &str, obj_val: &str, slot: usize) -> String {
+        let vtable_ptr = self.get_reg();
+        self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 0\n", vtable_ptr, class_name, class_name, obj_val));
+        let vtable = self.get_reg();
+        self.emit(&format!("  {} = load i8**, i8*** {}\n", vtable, vtable_ptr));
+        let fn_slot = self.get_reg();
+        self.emit(&format!("  {} = getelementptr inbounds i8*, i8** {}, i64 {}\n", fn_slot, vtable, slot));
+        let fn_raw = self.get_reg();
+        self.emit(&format!("  {} = load i8*, i8** {}\n", fn_raw, fn_slot));
+        fn_raw
+    }
+
+    /// Converts a pointer to a subclass object into a pointer to one of its base classes
+    /// or interfaces. Base class fields come first in the layout and every struct starts
+    /// with the vtable, so only the pointer type changes.
+    fn emit_upcast(&mut self, val: &str, class_name: &str, base: &str) -> String {
+        if class_name == base { return val.to_string(); }
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = bitcast %struct.{}* {} to %struct.{}*\n", reg, class_name, val, base));
+        reg
+    }
+
+    /// LLVM type of a class method, used when its address is registered as an MVC route.
+    fn method_fn_type(&self, class_name: &str, method: &str) -> String {
+        let sig = self.types.method_sig(class_name, method).expect("sema checked the method exists");
+        let mut params = vec![format!("%struct.{}*", class_name)];
+        params.extend(sig.params.iter().map(llvm_type));
+        format!("{} ({})*", llvm_type(&sig.ret), params.join(", "))
+    }
+
     /// Appends LLVM IR code to the current code buffer (function or main body).
     /// Automatically handles block termination rules (ret, br) and lazy symbol extraction.
     fn emit(&mut self, s: &str) {
@@ -130,88 +483,100 @@ impl Compiler {
         }
 
         // --- Header Guard / Lazy Tracking ---
-        if s.contains("call ") && s.contains("@") {
-             if let Some(start) = s.find('@') {
-                 let rest = &s[start + 1..];
-                 let func_end = rest.find('(').or_else(|| rest.find(' ')).unwrap_or(rest.len());
-                 let func_name = rest[..func_end].trim();
-                 self.required_symbols.insert(func_name.to_string());
-             }
+        if s.contains("call ") && let Some(start) = s.find('@') {
+             let rest = &s[start + 1..];
+             let func_end = rest.find('(').or_else(|| rest.find(' ')).unwrap_or(rest.len());
+             let func_name = rest[..func_end].trim();
+             self.required_symbols.insert(func_name.to_string());
         }
     }
 
-    /// Validates and handles system library calls (system.net, system.io, system.mvc).
+    /// Validates and handles system library calls (system.net, system.io, system.mvc, system.str).
     /// Ensures that the required modules are imported before allowing access.
-    fn resolve_stdlib_call(&mut self, parts: &[String], args: &[Expr]) -> (String, VarType) {
+    fn resolve_stdlib_call(&mut self, parts: &[String], args: &[Expr]) -> CompileResult<(String, VarType)> {
         match parts[0].as_str() {
             "system" => {
                 if !self.system_modules.contains(&"system".to_string()) {
-                    panic!("System Library modules (system.*) require 'import \"system\";'");
+                    return Err(Diagnostic::error("E0301", format!("'{}' requires the system library", parts.join(".")))
+                        .with_note("add 'import \"system\";' at the top of the file"));
+                }
+                if parts.len() < 3 {
+                    return Err(Diagnostic::error("E0302", format!("invalid system call '{}'", parts.join(".")))
+                        .with_note("expected a module and a function, e.g. system.net.api_listen"));
                 }
-                if parts.len() < 3 { panic!("Invalid system call. Expected e.g. system.net.api_listen"); }
                 match parts[1].as_str() {
                     "net" => self.emit_system_net_dispatch(&parts[2], args),
                     "io" => self.emit_system_io_dispatch(&parts[2], args),
                     "mvc" => self.emit_system_mvc_dispatch(&parts[2], args),
-                    _ => panic!("Unknown system module: {}", parts[1])
+                    "str" => self.emit_system_str_dispatch(&parts[2], args),
+                    _ => Err(Diagnostic::error("E0302", format!("unknown system module 'system.{}'", parts[1]))
+                        .with_note("available modules: system.net, system.io, system.mvc, system.str"))
                 }
             },
-            _ => panic!("Not a system library namespace: {}", parts[0])
+            _ => Err(Diagnostic::error("E0302", format!("'{}' is not a system library namespace", parts[0])))
         }
     }
 
     /// Generates LLVM IR for the modern Aura MVC networking system (system.net.api_listen).
     /// Handles socket setup, MVC route registration, and starting the server loop.
-    fn emit_system_net_dispatch(&mut self, method: &str, args: &[Expr]) -> (String, VarType) {
+    fn emit_system_net_dispatch(&mut self, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
         match method {
             "api_listen" => {
-                let (port_val, _) = self.compile_expr(&args[0]);
-                let (obj_val, obj_type) = self.compile_expr(&args[1]);
+                expect_args("system.net.api_listen", args, 2)?;
+                let (port_val, _) = self.compile_expr(&args[0])?;
+                let (obj_val, obj_type) = self.compile_expr(&args[1])?;
                 
                 if let VarType::Instance(class_name) = obj_type {
                     // 1. Socket Setup
                     let sock = self.get_reg();
                     self.emit(&format!("  {} = call i64 @aura_net_setup(i64 {})\n", sock, port_val));
 
-                    // 2. Register Routes (Class methods)
-                    if let Some(methods) = self.class_methods.get(&class_name).cloned() {
-                        for m in methods {
-                            let m_val = self.add_string(m.to_string());
-                            let m_ptr = self.get_reg();
-                            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
-                            
-                            // Get function pointer (fn_Class_Method)
-                            let fn_name = format!("fn_{}_{}", class_name, m);
-                            self.emit(&format!("  call void @aura_mvc_register(i8* {}, i8* bitcast (i8* (%struct.{}*, i64)* @{} to i8*))\n", m_ptr, class_name, fn_name));
-                        }
-                    }
+                    // 2. Register Routes (Class methods, including inherited ones)
+                    self.emit_route_registration(&class_name, &obj_val);
 
                     // 3. Start MVC Server (Infinite Loop in C)
                     let cast_reg = self.get_reg();
                     self.emit(&format!("  {} = bitcast %struct.{}* {} to i8*\n", cast_reg, class_name, obj_val));
                     self.emit(&format!("  call void @aura_mvc_serve(i64 {}, i8* {})\n", sock, cast_reg));
 
-                    ("0".to_string(), VarType::Int)
-                } else { panic!("api_listen requires a class instance."); }
+                    Ok(("0".to_string(), VarType::Void))
+                } else { Err(type_error("api_listen requires a class instance", &obj_type)) }
             },
-            _ => panic!("Unknown system.net method: {}", method)
+            "listen" => {
+                // Serves the routes registered with system.mvc.route, without a controller
+                expect_args("system.net.listen", args, 1)?;
+                let (port_val, _) = self.compile_expr(&args[0])?;
+                let sock = self.get_reg();
+                self.emit(&format!("  {} = call i64 @aura_net_setup(i64 {})\n", sock, port_val));
+                self.emit(&format!("  call void @aura_mvc_serve(i64 {}, i8* null)\n", sock));
+                Ok(("0".to_string(), VarType::Void))
+            },
+            _ => Err(unknown_method("system.net", method))
         }
     }
 
     /// Dispatches standard I/O calls to their corresponding LLVM IR generations (system.io).
-    fn emit_system_io_dispatch(&mut self, method: &str, args: &[Expr]) -> (String, VarType) {
+    fn emit_system_io_dispatch(&mut self, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
         match method {
             "print" | "println" => {
-                let (val, vtype) = self.compile_expr(&args[0]);
+                expect_args(method, args, 1)?;
+                let (val, vtype) = self.compile_expr(&args[0])?;
                 if vtype == VarType::Int {
                     self.emit(&format!("  call void @aura_print_int(i64 {})\n", val));
+                } else if vtype == VarType::Float {
+                    self.emit(&format!("  call void @aura_print_float(double {})\n", val));
                 } else if vtype == VarType::Str {
-                    self.emit(&format!("  call void @aura_print_str(i8* {})\n", val));
+                    let ptr = self.ensure_string(val, vtype);
+                    self.emit(&format!("  call void @aura_print_str(i8* {})\n", ptr));
+                } else {
+                    self.warnings.push(Diagnostic::warning("W0301", format!("{} of a {} value prints nothing", method, vtype)).with_span(args[0].span)
+                        .with_note("only int, float and string values can be printed"));
                 }
-                ("0".to_string(), VarType::Int)
+                Ok(("0".to_string(), VarType::Void))
             },
             "read_file" => {
-                let (path_val, _) = self.compile_expr(&args[0]);
+                expect_args("system.io.read_file", args, 1)?;
+                let (path_val, _) = self.compile_expr(&args[0])?;
                 let reg = self.get_reg();
                 let final_ptr = if path_val.starts_with("@str.") {
                      let str_len = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == path_val).unwrap().2;
@@ -222,7 +587,7 @@ impl Compiler {
                      path_val
                 };
                 self.emit(&format!("  {} = call i8* @aura_read_file(i8* {})\n", reg, final_ptr));
-                (reg, VarType::Str)
+                Ok((reg, VarType::Str))
             },
             "input" => {
                 let ptr_reg = self.get_reg();
@@ -230,102 +595,580 @@ impl Compiler {
                 self.emit(&format!("  call i64 (i8*, ...) @scanf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @fmt_input_num, i64 0, i64 0), i64* {})\n", ptr_reg));
                 let val_reg = self.get_reg();
                 self.emit(&format!("  {} = load i64, i64* {}\n", val_reg, ptr_reg));
-                (val_reg, VarType::Int)
+                Ok((val_reg, VarType::Int))
             },
             "input_str" => {
                 let malloc_reg = self.get_reg();
                 self.emit(&format!("  {} = call i8* @malloc(i64 256)\n", malloc_reg));
                 self.emit(&format!("  call i64 (i8*, ...) @scanf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @fmt_input_str, i64 0, i64 0), i8* {})\n", malloc_reg));
-                (malloc_reg, VarType::Str)
+                Ok((malloc_reg, VarType::Str))
             },
-            _ => panic!("Unknown system.io method: {}", method)
+            _ => Err(unknown_method("system.io", method))
+        }
+    }
+
+    /// Dispatches string functions (system.str) to their runtime implementations.
+    fn emit_system_str_dispatch(&mut self, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        let sig = str_function(method).ok_or_else(|| unknown_method("system.str", method))?;
+        let func = match method {
+            "find" => "aura_str_index",
+            "from_int" => "aura_int_to_str",
+            _ => &format!("aura_str_{}", method),
+        };
+        expect_args(&format!("system.str.{}", method), args, sig.params.len())?;
+        let mut arg_vals = Vec::new();
+        for (arg, param) in args.iter().zip(&sig.params) {
+            let (val, vtype) = self.compile_expr(arg)?;
+            let val = self.coerce(val, &vtype, param);
+            arg_vals.push(format!("{} {}", llvm_type(param), val));
         }
+        // The runtime returns bools as 0/1 ints
+        let ret = if sig.ret == VarType::Bool { "i64".to_string() } else { llvm_type(&sig.ret) };
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = call {} @{}({})\n", reg, ret, func, arg_vals.join(", ")));
+        if sig.ret != VarType::Bool { return Ok((reg, sig.ret)); }
+        let flag = self.get_reg();
+        self.emit(&format!("  {} = icmp ne i64 {}, 0\n", flag, reg));
+        Ok((flag, VarType::Bool))
     }
 
     /// Dispatches MVC related calls (system.mvc).
-    fn emit_system_mvc_dispatch(&mut self, method: &str, args: &[Expr]) -> (String, VarType) {
+    fn emit_system_mvc_dispatch(&mut self, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
         match method {
             "render" => {
                 if args.len() == 2 {
-                    let (tpl_val, _) = self.compile_expr(&args[0]);
-                    let (obj_val, obj_type) = self.compile_expr(&args[1]);
-                    
-                    let mut current_tpl = if tpl_val.starts_with("@str.") {
-                         let str_len = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == tpl_val).unwrap().2;
-                         let p_reg = self.get_reg();
-                         self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", p_reg, str_len, str_len, tpl_val));
-                         p_reg
-                    } else { tpl_val };
+                    let (tpl_val, tpl_type) = self.compile_expr(&args[0])?;
+                    let (obj_val, obj_type) = self.compile_expr(&args[1])?;
+                    let current_tpl = self.ensure_string(tpl_val, tpl_type);
 
                     if let VarType::Instance(class_name) = obj_type {
-                        let fields = self.classes.get(&class_name).unwrap().clone();
-                        for (i, field_name) in fields.iter().enumerate() {
-                            let placeholder = format!("{{model.{}}}", field_name);
-                            let p_id = self.add_string(placeholder.clone());
-                            let p_ptr = self.get_reg();
-                            let p_len = placeholder.len() + 1;
-                            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", p_ptr, p_len, p_len, p_id));
-
-                            let ptr_reg = self.get_reg();
-                            self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", ptr_reg, class_name, class_name, obj_val, i));
-                            let val_reg = self.get_reg();
-                            self.emit(&format!("  {} = load i64, i64* {}\n", val_reg, ptr_reg));
-
-                            let next_tpl = self.get_reg();
-                            self.emit(&format!("  {} = call i8* @aura_render_field(i8* {}, i8* {}, i64 {})\n", next_tpl, current_tpl, p_ptr, val_reg));
-                            current_tpl = next_tpl;
-                        }
-                        (current_tpl, VarType::Str)
-                    } else { panic!("render expects a class instance."); }
+                        let rendered = self.emit_render_fields(current_tpl, &obj_val, &class_name);
+                        Ok((rendered, VarType::Str))
+                    } else { Err(type_error("render expects a class instance", &obj_type)) }
                 } else if args.len() == 3 {
-                    let (tpl_val, _) = self.compile_expr(&args[0]);
-                    let (key_val, _) = self.compile_expr(&args[1]);
-                    let (val_val, val_type) = self.compile_expr(&args[2]);
-                    
-                    let final_val = if val_type == VarType::Int {
-                        let s_reg = self.get_reg();
-                        self.emit(&format!("  {} = call i8* @aura_int_to_str(i64 {})\n", s_reg, val_val));
-                        s_reg
-                    } else { val_val };
+                    let (tpl_val, tpl_type) = self.compile_expr(&args[0])?;
+                    let (key_val, key_type) = self.compile_expr(&args[1])?;
+                    let (val_val, val_type) = self.compile_expr(&args[2])?;
+                    let tpl_val = self.ensure_string(tpl_val, tpl_type);
+                    let key_val = self.ensure_string(key_val, key_type);
+                    let final_val = self.ensure_string(val_val, val_type);
 
                     let res_reg = self.get_reg();
                     self.emit(&format!("  {} = call i8* @aura_str_replace(i8* {}, i8* {}, i8* {})\n", res_reg, tpl_val, key_val, final_val));
-                    (res_reg, VarType::Str)
-                } else { panic!("render() takes 2 or 3 arguments."); }
+                    Ok((res_reg, VarType::Str))
+                } else {
+                    Err(Diagnostic::error("E0303", format!("system.mvc.render takes 2 or 3 arguments but {} were supplied", args.len())))
+                }
             },
             "render_list" => {
-                let (tpl_val, _) = self.compile_expr(&args[0]);
-                let (tag_val, _) = self.compile_expr(&args[1]);
-                let (_arr_val, arr_type) = self.compile_expr(&args[2]);
-                let (item_tpl_val, _) = self.compile_expr(&args[3]);
-
-                if let VarType::Array(elem_type, len) = arr_type {
-                    if let VarType::Instance(class_name) = *elem_type {
-                        let field_names = self.classes.get(&class_name).unwrap().join(",");
-                        let f_id = self.add_string(field_names.clone());
-                        let f_ptr = self.get_reg();
-                        let f_len = field_names.len() + 1;
-                        self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", f_ptr, f_len, f_len, f_id));
-
-                        let arr_ptr = self.get_reg();
-                        self.emit(&format!("  {} = bitcast [{} x i64]* %{}_ptr to i64*\n", arr_ptr, len, match &args[2] { 
-                            Expr::Variable(n) => n,
-                            _ => panic!("render_list expects an array variable.")
-                        }));
-
+                expect_args("system.mvc.render_list", args, 4)?;
+                let (tpl_val, tpl_type) = self.compile_expr(&args[0])?;
+                let (tag_val, tag_type) = self.compile_expr(&args[1])?;
+                let (arr_val, arr_type) = self.compile_expr(&args[2])?;
+                let (item_tpl_val, item_tpl_type) = self.compile_expr(&args[3])?;
+                let tpl_val = self.ensure_string(tpl_val, tpl_type);
+                let tag_val = self.ensure_string(tag_val, tag_type);
+                let item_tpl_val = self.ensure_string(item_tpl_val, item_tpl_type);
+
+                if let VarType::Array(elem_type) = &arr_type {
+                    if let VarType::Instance(class_name) = &**elem_type {
+                        let item_llvm = llvm_type(elem_type);
+
+                        // Render the item template once per element and join the results
+                        let empty = self.add_string(String::new());
+                        let empty_ptr = self.ensure_string(empty, VarType::Str);
+                        let acc_ptr = self.get_reg();
+                        self.emit_alloca(&acc_ptr, "i8*");
+                        self.emit(&format!("  store i8* {}, i8** {}\n", empty_ptr, acc_ptr));
+                        let idx_ptr = self.get_reg();
+                        self.emit_alloca(&idx_ptr, "i64");
+                        self.emit(&format!("  store i64 0, i64* {}\n", idx_ptr));
+
+                        let label_cond = self.get_label();
+                        let label_body = self.get_label();
+                        let label_end = self.get_label();
+                        self.emit(&format!("  br label %{}\n", label_cond));
+                        self.emit(&format!("{}:\n", label_cond));
+                        let idx = self.get_reg();
+                        self.emit(&format!("  {} = load i64, i64* {}\n", idx, idx_ptr));
+                        let len = self.emit_array_len(&arr_val);
+                        let in_range = self.get_reg();
+                        self.emit(&format!("  {} = icmp slt i64 {}, {}\n", in_range, idx, len));
+                        self.emit(&format!("  br i1 {}, label %{}, label %{}\n", in_range, label_body, label_end));
+
+                        self.emit(&format!("{}:\n", label_body));
+                        let item_ptr = self.emit_array_slot(&arr_val, elem_type, &idx);
+                        let item = self.get_reg();
+                        self.emit(&format!("  {} = load {}, {}* {}\n", item, item_llvm, item_llvm, item_ptr));
+                        let item_html = self.emit_render_fields(item_tpl_val, &item, class_name);
+                        let acc = self.get_reg();
+                        self.emit(&format!("  {} = load i8*, i8** {}\n", acc, acc_ptr));
+                        let joined = self.get_reg();
+                        self.emit(&format!("  {} = call i8* @aura_str_concat(i8* {}, i8* {})\n", joined, acc, item_html));
+                        self.emit(&format!("  store i8* {}, i8** {}\n", joined, acc_ptr));
+                        let next_idx = self.get_reg();
+                        self.emit(&format!("  {} = add i64 {}, 1\n", next_idx, idx));
+                        self.emit(&format!("  store i64 {}, i64* {}\n", next_idx, idx_ptr));
+                        self.emit(&format!("  br label %{}\n", label_cond));
+
+                        self.emit(&format!("{}:\n", label_end));
                         let list_html = self.get_reg();
-                        self.emit(&format!("  {} = call i8* @aura_mvc_render_list(i8* {}, i64* {}, i32 {}, i8* {})\n", 
-                            list_html, item_tpl_val, arr_ptr, len, f_ptr));
-
+                        self.emit(&format!("  {} = load i8*, i8** {}\n", list_html, acc_ptr));
                         let res_reg = self.get_reg();
                         self.emit(&format!("  {} = call i8* @aura_str_replace(i8* {}, i8* {}, i8* {})\n", 
                             res_reg, tpl_val, tag_val, list_html));
-                        (res_reg, VarType::Str)
-                    } else { panic!("render_list requires an array of class instances."); }
-                } else { panic!("render_list requires an array variable."); }
+                        Ok((res_reg, VarType::Str))
+                    } else { Err(type_error("render_list requires an array of class instances", &arr_type)) }
+                } else { Err(type_error("render_list requires an array", &arr_type)) }
+            },
+            "route" => {
+                expect_args("system.mvc.route", args, 2)?;
+                let (path_val, path_type) = self.compile_expr(&args[0])?;
+                let path_val = self.ensure_string(path_val, path_type);
+                let (handler, _) = self.compile_expr(&args[1])?;
+                let (code, env) = self.emit_closure_parts(&handler);
+                self.emit(&format!("  call void @aura_mvc_route(i8* {}, i8* {}, i8* {})\n", path_val, code, env));
+                Ok(("0".to_string(), VarType::Void))
             },
-            _ => panic!("Unknown system.mvc method: {}", method)
+            _ => Err(unknown_method("system.mvc", method))
+        }
+    }
+
+    /// Replaces each `{model.<field>}` placeholder in `tpl` with the value of that
+    /// field of `obj_val`, formatted according to the field's type. Object fields
+    /// have no text form and are left untouched. Returns the rendered template.
+    fn emit_render_fields(&mut self, tpl: String, obj_val: &str, class_name: &str) -> String {
+        // Interfaces have no fields
+        let Some(info) = self.types.classes.get(class_name) else { return tpl };
+        let fields = info.fields.clone();
+        let mut current_tpl = tpl;
+        for (i, (field_name, field_type)) in fields.iter().enumerate() {
+            if !matches!(field_type, VarType::Int | VarType::Float | VarType::Str | VarType::Bool) { continue; }
+            let placeholder = self.add_string(format!("{{model.{}}}", field_name));
+            let p_ptr = self.ensure_string(placeholder, VarType::Str);
+
+            let ty = llvm_type(field_type);
+            let ptr_reg = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", ptr_reg, class_name, class_name, obj_val, i + 1));
+            let val_reg = self.get_reg();
+            self.emit(&format!("  {} = load {}, {}* {}\n", val_reg, ty, ty, ptr_reg));
+            let text = self.ensure_string(val_reg, field_type.clone());
+
+            let next_tpl = self.get_reg();
+            self.emit(&format!("  {} = call i8* @aura_str_replace(i8* {}, i8* {}, i8* {})\n", next_tpl, current_tpl, p_ptr, text));
+            current_tpl = next_tpl;
         }
+        current_tpl
+    }
+
+    /// Emits a pointer to `array[index]` or `map[key]` and returns it with the element type.
+    fn element_ptr(&mut self, array_expr: &Expr, index_expr: &Expr) -> CompileResult<(String, VarType)> {
+        let (arr, vtype) = self.compile_expr(array_expr)?;
+        if let VarType::Map(key_type, value_type) = &vtype {
+            let key = self.compile_map_key(index_expr, key_type)?;
+            let slot = self.emit_map_slot(&arr, value_type, &key, Some(index_expr.span));
+            return Ok((slot, (**value_type).clone()));
+        }
+        let VarType::Array(elem_type) = &vtype else {
+            return Err(type_error("only arrays can be indexed", &vtype));
+        };
+        let (idx_val, _) = self.compile_expr(index_expr)?;
+        if self.bounds_checks {
+            let len = self.emit_array_len(&arr);
+            self.emit_bounds_check(&idx_val, &len, index_expr.span);
+        }
+        let ptr_reg = self.emit_array_slot(&arr, elem_type, &idx_val);
+        Ok((ptr_reg, (**elem_type).clone()))
+    }
+
+    /// Emits an arithmetic, comparison or string operator on two compiled operands.
+    fn emit_binary(&mut self, op: &TokenType, l_val: String, l_vtype: VarType, r_val: String, r_vtype: VarType, span: Span) -> CompileResult<(String, VarType)> {
+        if *op == TokenType::Plus && (l_vtype == VarType::Str || r_vtype == VarType::Str) {
+            let reg = self.get_reg();
+            let l_ptr = self.ensure_string(l_val, l_vtype);
+            let r_ptr = self.ensure_string(r_val, r_vtype);
+            self.emit(&format!("  {} = call i8* @aura_str_concat(i8* {}, i8* {})\n", reg, l_ptr, r_ptr));
+            return Ok((reg, VarType::Str));
+        }
+
+        // Strings compare by content: the runtime's -1/0/1 result is compared against 0
+        let (l_val, l_vtype, r_val) = if l_vtype == VarType::Str && r_vtype == VarType::Str {
+            let l_ptr = self.ensure_string(l_val, l_vtype);
+            let r_ptr = self.ensure_string(r_val, r_vtype.clone());
+            let reg = self.get_reg();
+            self.emit(&format!("  {} = call i64 @aura_str_compare(i8* {}, i8* {})\n", reg, l_ptr, r_ptr));
+            (reg, VarType::Int, "0".to_string())
+        } else {
+            (l_val, l_vtype, r_val)
+        };
+
+        if l_vtype == VarType::Float || r_vtype == VarType::Float {
+            // Mixed int/float operands: the int side is widened
+            let l_val = self.coerce(l_val, &l_vtype, &VarType::Float);
+            let r_val = self.coerce(r_val, &r_vtype, &VarType::Float);
+            let reg = self.get_reg();
+            let (instr, vtype) = match op {
+                TokenType::Plus => ("fadd", VarType::Float), TokenType::Minus => ("fsub", VarType::Float),
+                TokenType::Mul => ("fmul", VarType::Float), TokenType::Div => ("fdiv", VarType::Float),
+                TokenType::Mod => ("frem", VarType::Float),
+                TokenType::Eq => ("fcmp oeq", VarType::Bool), TokenType::Neq => ("fcmp une", VarType::Bool),
+                TokenType::Lt => ("fcmp olt", VarType::Bool), TokenType::Gt => ("fcmp ogt", VarType::Bool),
+                TokenType::Lte => ("fcmp ole", VarType::Bool), TokenType::Gte => ("fcmp oge", VarType::Bool),
+                _ => return Err(Diagnostic::error("E0307", format!("unsupported float operator {:?}", op))),
+            };
+            self.emit(&format!("  {} = {} double {}, {}\n", reg, instr, l_val, r_val));
+            return Ok((reg, vtype));
+        }
+
+        if self.overflow_checks && matches!(op, TokenType::Plus|TokenType::Minus|TokenType::Mul) {
+            let reg = self.emit_checked_arith(op, &l_val, &r_val, span);
+            return Ok((reg, VarType::Int));
+        }
+
+        if matches!(op, TokenType::Plus|TokenType::Minus|TokenType::Mul|TokenType::Div|TokenType::Mod
+            |TokenType::BitAnd|TokenType::BitOr|TokenType::BitXor|TokenType::Shl|TokenType::Shr) {
+            let reg = self.get_reg();
+            let op_str = match op {
+                TokenType::Plus => "add", TokenType::Minus => "sub",
+                TokenType::Mul => "mul", TokenType::Div => "sdiv", TokenType::Mod => "srem",
+                TokenType::BitAnd => "and", TokenType::BitOr => "or", TokenType::BitXor => "xor",
+                TokenType::Shl => "shl", TokenType::Shr => "ashr",
+                _ => unreachable!()
+            };
+            self.emit(&format!("  {} = {} i64 {}, {}\n", reg, op_str, l_val, r_val));
+            Ok((reg, VarType::Int))
+        } else {
+            let reg = self.get_reg();
+            let op_str = match op {
+                TokenType::Eq => "eq", TokenType::Neq => "ne",
+                TokenType::Lt => "slt", TokenType::Gt => "sgt",
+                TokenType::Lte => "sle", TokenType::Gte => "sge",
+                _ => unreachable!()
+            };
+            self.emit(&format!("  {} = icmp {} {} {}, {}\n", reg, op_str, llvm_type(&l_vtype), l_val, r_val));
+            Ok((reg, VarType::Bool))
+        }
+    }
+
+    /// Emits the size in bytes of a value of the given type, via the null-GEP idiom.
+    fn emit_sizeof(&mut self, vtype: &VarType) -> String {
+        let ty = llvm_type(vtype);
+        let size_ptr = self.get_reg();
+        self.emit(&format!("  {} = getelementptr {}, {}* null, i32 1\n", size_ptr, ty, ty));
+        let size = self.get_reg();
+        self.emit(&format!("  {} = ptrtoint {}* {} to i64\n", size, ty, size_ptr));
+        size
+    }
+
+    /// Emits a new array of `len` zeroed elements.
+    fn emit_array_new(&mut self, elem_type: &VarType, len: &str) -> String {
+        let size = self.emit_sizeof(elem_type);
+        let arr = self.get_reg();
+        self.emit(&format!("  {} = call %aura.array* @aura_array_new(i64 {}, i64 {})\n", arr, size, len));
+        arr
+    }
+
+    /// Loads the current length of an array.
+    fn emit_array_len(&mut self, arr: &str) -> String {
+        let len_ptr = self.get_reg();
+        self.emit(&format!("  {} = getelementptr inbounds %aura.array, %aura.array* {}, i32 0, i32 0\n", len_ptr, arr));
+        let len = self.get_reg();
+        self.emit(&format!("  {} = load i64, i64* {}\n", len, len_ptr));
+        len
+    }
+
+    /// Emits a typed pointer to element `index` of an array.
+    fn emit_array_slot(&mut self, arr: &str, elem_type: &VarType, index: &str) -> String {
+        let elem = llvm_type(elem_type);
+        let data_ptr = self.get_reg();
+        self.emit(&format!("  {} = getelementptr inbounds %aura.array, %aura.array* {}, i32 0, i32 3\n", data_ptr, arr));
+        let data = self.get_reg();
+        self.emit(&format!("  {} = load i8*, i8** {}\n", data, data_ptr));
+        let typed_data = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to {}*\n", typed_data, data, elem));
+        let slot = self.get_reg();
+        self.emit(&format!("  {} = getelementptr inbounds {}, {}* {}, i64 {}\n", slot, elem, elem, typed_data, index));
+        slot
+    }
+
+    /// Appends a value to an array.
+    fn emit_array_push(&mut self, arr: &str, elem_type: &VarType, val: &str) {
+        let elem = llvm_type(elem_type);
+        let slot = self.get_reg();
+        self.emit(&format!("  {} = call i8* @aura_array_push(%aura.array* {})\n", slot, arr));
+        let typed_slot = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to {}*\n", typed_slot, slot, elem));
+        self.emit(&format!("  store {} {}, {}* {}\n", elem, val, elem, typed_slot));
+    }
+
+    /// Emits a loop over the elements of an array; `body` compiles one iteration
+    /// given the element's register.
+    fn emit_array_loop(&mut self, arr: &str, elem_type: &VarType, mut body: impl FnMut(&mut Self, String)) {
+        let elem = llvm_type(elem_type);
+        let idx_ptr = self.get_reg();
+        self.emit_alloca(&idx_ptr, "i64");
+        self.emit(&format!("  store i64 0, i64* {}\n", idx_ptr));
+        let label_cond = self.get_label();
+        let label_body = self.get_label();
+        let label_end = self.get_label();
+        self.emit(&format!("  br label %{}\n", label_cond));
+        self.emit(&format!("{}:\n", label_cond));
+        let idx = self.get_reg();
+        self.emit(&format!("  {} = load i64, i64* {}\n", idx, idx_ptr));
+        let len = self.emit_array_len(arr);
+        let in_range = self.get_reg();
+        self.emit(&format!("  {} = icmp slt i64 {}, {}\n", in_range, idx, len));
+        self.emit(&format!("  br i1 {}, label %{}, label %{}\n", in_range, label_body, label_end));
+
+        self.emit(&format!("{}:\n", label_body));
+        let item_ptr = self.emit_array_slot(arr, elem_type, &idx);
+        let item = self.get_reg();
+        self.emit(&format!("  {} = load {}, {}* {}\n", item, elem, elem, item_ptr));
+        body(self, item);
+        let next_idx = self.get_reg();
+        self.emit(&format!("  {} = add i64 {}, 1\n", next_idx, idx));
+        self.emit(&format!("  store i64 {}, i64* {}\n", next_idx, idx_ptr));
+        self.emit(&format!("  br label %{}\n", label_cond));
+        self.emit(&format!("{}:\n", label_end));
+    }
+
+    /// Compiles `map(f)`, `filter(f)` and `reduce(f, initial)` on an array as loops calling `f`.
+    fn emit_array_higher_order(&mut self, arr: &str, elem_type: &VarType, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        expect_args(method, args, if method == "reduce" { 2 } else { 1 })?;
+        let (func, func_type) = self.compile_expr(&args[0])?;
+        let VarType::Function(params, ret) = func_type else {
+            return Err(type_error(&format!("'{}' expects a function", method), &func_type));
+        };
+        let elem = llvm_type(elem_type);
+        match method {
+            "map" => {
+                let result = self.emit_array_new(&ret, "0");
+                self.emit_array_loop(arr, elem_type, |this, item| {
+                    let val = this.emit_closure_call(&func, &params, &ret, vec![format!("{} {}", elem, item)]);
+                    this.emit_array_push(&result, &ret, &val);
+                });
+                Ok((result, VarType::Array(ret)))
+            }
+            "filter" => {
+                let result = self.emit_array_new(elem_type, "0");
+                self.emit_array_loop(arr, elem_type, |this, item| {
+                    let keep = this.emit_closure_call(&func, &params, &ret, vec![format!("{} {}", elem, item)]);
+                    let label_keep = this.get_label();
+                    let label_next = this.get_label();
+                    this.emit(&format!("  br i1 {}, label %{}, label %{}\n", keep, label_keep, label_next));
+                    this.emit(&format!("{}:\n", label_keep));
+                    this.emit_array_push(&result, elem_type, &item);
+                    this.emit(&format!("  br label %{}\n", label_next));
+                    this.emit(&format!("{}:\n", label_next));
+                });
+                Ok((result, VarType::Array(Box::new(elem_type.clone()))))
+            }
+            _ => {
+                // The accumulator has the type of the function's first parameter
+                let acc_type = params[0].clone();
+                let acc = llvm_type(&acc_type);
+                let (init, init_type) = self.compile_expr(&args[1])?;
+                let init = self.coerce(init, &init_type, &acc_type);
+                let acc_ptr = self.get_reg();
+                self.emit_alloca(&acc_ptr, &acc);
+                self.emit(&format!("  store {} {}, {}* {}\n", acc, init, acc, acc_ptr));
+                self.emit_array_loop(arr, elem_type, |this, item| {
+                    let current = this.get_reg();
+                    this.emit(&format!("  {} = load {}, {}* {}\n", current, acc, acc, acc_ptr));
+                    let args = vec![format!("{} {}", acc, current), format!("{} {}", elem, item)];
+                    let val = this.emit_closure_call(&func, &params, &ret, args);
+                    let val = this.coerce(val, &ret, &acc_type);
+                    this.emit(&format!("  store {} {}, {}* {}\n", acc, val, acc, acc_ptr));
+                });
+                let result = self.get_reg();
+                self.emit(&format!("  {} = load {}, {}* {}\n", result, acc, acc, acc_ptr));
+                Ok((result, acc_type))
+            }
+        }
+    }
+
+    /// Compiles the methods of an array: `push`, `pop`, `len`, `map`, `filter` and `reduce`.
+    fn emit_array_method(&mut self, arr: &str, elem_type: &VarType, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        let elem = llvm_type(elem_type);
+        match method {
+            "push" => {
+                expect_args("push", args, 1)?;
+                let (val, vtype) = self.compile_expr(&args[0])?;
+                let val = self.coerce(val, &vtype, elem_type);
+                self.emit_array_push(arr, elem_type, &val);
+                Ok(("0".to_string(), VarType::Void))
+            }
+            "map" | "filter" | "reduce" => self.emit_array_higher_order(arr, elem_type, method, args),
+            "pop" => {
+                expect_args("pop", args, 0)?;
+                let slot = self.get_reg();
+                self.emit(&format!("  {} = call i8* @aura_array_pop(%aura.array* {})\n", slot, arr));
+                let typed_slot = self.get_reg();
+                self.emit(&format!("  {} = bitcast i8* {} to {}*\n", typed_slot, slot, elem));
+                let val = self.get_reg();
+                self.emit(&format!("  {} = load {}, {}* {}\n", val, elem, elem, typed_slot));
+                Ok((val, elem_type.clone()))
+            }
+            "len" => {
+                expect_args("len", args, 0)?;
+                Ok((self.emit_array_len(arr), VarType::Int))
+            }
+            _ => Err(Diagnostic::error("E0315", format!("arrays have no method '{}'", method))),
+        }
+    }
+
+    /// Emits a new empty map.
+    fn emit_map_new(&mut self, key_type: &VarType, value_type: &VarType) -> String {
+        let size = self.emit_sizeof(value_type);
+        let key_is_str = i64::from(*key_type == VarType::Str);
+        let map = self.get_reg();
+        self.emit(&format!("  {} = call %aura.map* @aura_map_new(i64 {}, i64 {})\n", map, key_is_str, size));
+        map
+    }
+
+    /// Compiles a map key into the i64 the runtime takes (strings are passed by address).
+    fn compile_map_key(&mut self, key_expr: &Expr, key_type: &VarType) -> CompileResult<String> {
+        let (key, vtype) = self.compile_expr(key_expr)?;
+        let key = self.coerce(key, &vtype, key_type);
+        if *key_type != VarType::Str { return Ok(key); }
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", reg, key));
+        Ok(reg)
+    }
+
+    /// Emits a typed pointer to the value stored under `key`. With `get_at` a missing
+    /// key stops the program, reporting that location; without it the entry is added.
+    fn emit_map_slot(&mut self, map: &str, value_type: &VarType, key: &str, get_at: Option<Span>) -> String {
+        let slot = self.get_reg();
+        if let Some(span) = get_at {
+            let loc = self.add_string(self.location(span));
+            let loc_ptr = self.ensure_string(loc, VarType::Str);
+            self.emit(&format!("  {} = call i8* @aura_map_get(%aura.map* {}, i64 {}, i8* {})\n", slot, map, key, loc_ptr));
+        } else {
+            self.emit(&format!("  {} = call i8* @aura_map_set(%aura.map* {}, i64 {})\n", slot, map, key));
+        }
+        let value = llvm_type(value_type);
+        let typed_slot = self.get_reg();
+        self.emit(&format!("  {} = bitcast i8* {} to {}*\n", typed_slot, slot, value));
+        typed_slot
+    }
+
+    /// Emits an array of a map's keys, in insertion order.
+    fn emit_map_keys(&mut self, map: &str) -> String {
+        let keys = self.get_reg();
+        self.emit(&format!("  {} = call %aura.array* @aura_map_keys(%aura.map* {})\n", keys, map));
+        keys
+    }
+
+    /// Compiles `has`, `remove`, `keys` and `len` on a map.
+    fn emit_map_method(&mut self, map: &str, key_type: &VarType, method: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        match method {
+            "has" | "remove" => {
+                expect_args(method, args, 1)?;
+                let key = self.compile_map_key(&args[0], key_type)?;
+                if method == "remove" {
+                    self.emit(&format!("  call void @aura_map_remove(%aura.map* {}, i64 {})\n", map, key));
+                    return Ok(("0".to_string(), VarType::Void));
+                }
+                let found = self.get_reg();
+                self.emit(&format!("  {} = call i64 @aura_map_has(%aura.map* {}, i64 {})\n", found, map, key));
+                let reg = self.get_reg();
+                self.emit(&format!("  {} = icmp ne i64 {}, 0\n", reg, found));
+                Ok((reg, VarType::Bool))
+            }
+            "keys" => {
+                expect_args("keys", args, 0)?;
+                Ok((self.emit_map_keys(map), VarType::Array(Box::new(key_type.clone()))))
+            }
+            "len" => {
+                expect_args("len", args, 0)?;
+                let len = self.get_reg();
+                self.emit(&format!("  {} = call i64 @aura_map_len(%aura.map* {})\n", len, map));
+                Ok((len, VarType::Int))
+            }
+            _ => Err(Diagnostic::error("E0315", format!("maps have no method '{}'", method))),
+        }
+    }
+
+    /// Emits an int `+`, `-` or `*` through an LLVM overflow intrinsic. If the
+    /// result doesn't fit in an i64 the program stops with the source location.
+    fn emit_checked_arith(&mut self, op: &TokenType, l_val: &str, r_val: &str, span: Span) -> String {
+        let (intrinsic, symbol) = match op {
+            TokenType::Plus => ("sadd", "+"),
+            TokenType::Minus => ("ssub", "-"),
+            _ => ("smul", "*"),
+        };
+        let pair = self.get_reg();
+        self.emit(&format!("  {} = call {{i64, i1}} @llvm.{}.with.overflow.i64(i64 {}, i64 {})\n", pair, intrinsic, l_val, r_val));
+        let val = self.get_reg();
+        self.emit(&format!("  {} = extractvalue {{i64, i1}} {}, 0\n", val, pair));
+        let overflow = self.get_reg();
+        self.emit(&format!("  {} = extractvalue {{i64, i1}} {}, 1\n", overflow, pair));
+
+        let label_trap = self.get_label();
+        let label_ok = self.get_label();
+        self.emit(&format!("  br i1 {}, label %{}, label %{}\n", overflow, label_trap, label_ok));
+        self.emit(&format!("{}:\n", label_trap));
+        let msg = self.add_string(format!("integer overflow in '{}' at {}", symbol, self.location(span)));
+        let msg_ptr = self.ensure_string(msg, VarType::Str);
+        self.emit(&format!("  call void @aura_panic(i8* {})\n", msg_ptr));
+        self.emit("  unreachable\n");
+        self.emit(&format!("{}:\n", label_ok));
+        val
+    }
+
+    /// Emits a check that `index` is within `0..len`; out of bounds the program
+    /// stops with the index, the length and the source location.
+    fn emit_bounds_check(&mut self, index: &str, len: &str, span: Span) {
+        // Unsigned compare: negative indices wrap around to huge ones
+        let in_bounds = self.get_reg();
+        self.emit(&format!("  {} = icmp ult i64 {}, {}\n", in_bounds, index, len));
+        let label_ok = self.get_label();
+        let label_trap = self.get_label();
+        self.emit(&format!("  br i1 {}, label %{}, label %{}\n", in_bounds, label_ok, label_trap));
+        self.emit(&format!("{}:\n", label_trap));
+        let loc = self.add_string(self.location(span));
+        let loc_ptr = self.ensure_string(loc, VarType::Str);
+        self.emit(&format!("  call void @aura_bounds_fail(i64 {}, i64 {}, i8* {})\n", index, len, loc_ptr));
+        self.emit("  unreachable\n");
+        self.emit(&format!("{}:\n", label_ok));
+    }
+
+    /// `file:line:column` of a span, for runtime error messages.
+    fn location(&self, span: Span) -> String {
+        let file = self.file_names.get(span.file as usize).cloned().unwrap_or_default();
+        format!("{}:{}:{}", file, span.line, span.column)
+    }
+
+    /// File names indexed by FileId, used in runtime error messages.
+    pub fn set_file_names(&mut self, file_names: Vec<String>) {
+        self.file_names = file_names;
+    }
+
+    /// Makes int `+`, `-` and `*` trap on overflow instead of wrapping around.
+    pub fn enable_overflow_checks(&mut self) {
+        self.overflow_checks = true;
+    }
+
+    /// Leaves out the bounds checks on array indexing.
+    pub fn disable_bounds_checks(&mut self) {
+        self.bounds_checks = false;
+    }
+
+    /// Explicit numeric conversions: `int(x)` truncates toward zero, `float(x)` widens.
+    fn emit_conversion(&mut self, name: &str, args: &[Expr]) -> CompileResult<(String, VarType)> {
+        expect_args(name, args, 1)?;
+        let (val, vtype) = self.compile_expr(&args[0])?;
+        let (instr, target) = match (name, &vtype) {
+            ("int", VarType::Float) => ("fptosi double", VarType::Int),
+            ("int", VarType::Bool) => ("zext i1", VarType::Int),
+            ("float", VarType::Int) => ("sitofp i64", VarType::Float),
+            ("int", VarType::Int) | ("float", VarType::Float) => return Ok((val, vtype)),
+            _ => return Err(type_error(&format!("cannot convert to '{}'", name), &vtype)),
+        };
+        let reg = self.get_reg();
+        self.emit(&format!("  {} = {} {} to {}\n", reg, instr, val, llvm_type(&target)));
+        Ok((reg, target))
     }
 
     /// Casts an Aura variable (Int, Str, Bool) to a standard LLVM i1 boolean.
@@ -348,7 +1191,7 @@ impl Compiler {
     }
 
     /// Helper to ensure a value is a string (i8*) for concatenation or other operations.
-    /// Handles static strings, dynamic strings, and auto-casts integers to strings.
+    /// Handles static strings, dynamic strings, and auto-casts numbers and bools to strings.
     fn ensure_string(&mut self, val: String, vtype: VarType) -> String {
         if vtype == VarType::Str {
             if val.starts_with("@str.") {
@@ -363,6 +1206,18 @@ impl Compiler {
             let s_reg = self.get_reg();
             self.emit(&format!("  {} = call i8* @aura_int_to_str(i64 {})\n", s_reg, val));
             s_reg
+        } else if vtype == VarType::Float {
+            let s_reg = self.get_reg();
+            self.emit(&format!("  {} = call i8* @aura_float_to_str(double {})\n", s_reg, val));
+            s_reg
+        } else if vtype == VarType::Bool {
+            let true_str = self.add_string("true".to_string());
+            let true_ptr = self.ensure_string(true_str, VarType::Str);
+            let false_str = self.add_string("false".to_string());
+            let false_ptr = self.ensure_string(false_str, VarType::Str);
+            let s_reg = self.get_reg();
+            self.emit(&format!("  {} = select i1 {}, i8* {}, i8* {}\n", s_reg, val, true_ptr, false_ptr));
+            s_reg
         } else {
              val
         }
@@ -370,6 +1225,7 @@ impl Compiler {
 
     /// Legacy API Server implementation (for backwards compatibility).
     /// Sets up a native TCP listener and handles basic HTTP request routing.
+    #[allow(dead_code)]
     fn emit_api_runtime(&mut self, port: String, obj_val: String, class_name: String) -> (String, VarType) {
         // --- Initialize Socket ---
         if self.target_os == TargetOs::Windows {
@@ -430,75 +1286,74 @@ impl Compiler {
         self.emit(&format!("  call i64 @send(i64 {}, i8* {}, i64 54, i64 0)\n", client_sock, h1_ptr));
 
         // --- Dynamic Method Routing ---
-        if let Some(methods) = self.class_methods.get(&class_name).cloned() {
-            for m in methods {
-                let m_val = self.add_string(m.to_string());
-                let m_ptr = self.get_reg();
-                self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
-                
-                let str_match = self.get_reg();
-                self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* {})\n", str_match, buf_ptr, m_ptr));
-                
-                let is_match = self.get_reg();
-                self.emit(&format!("  {} = icmp ne i8* {}, null\n", is_match, str_match));
-                
-                let l_then = self.get_label();
-                let l_next = self.get_label();
-                self.emit(&format!("  br i1 {}, label %{}, label %{}\n", is_match, l_then, l_next));
-                
-                self.emit(&format!("{}:\n", l_then));
-                // --- Parameter Parsing Logic ---
-                // Search for '?' in buffer
-                let q_mark = self.add_string("?".to_string());
-                let q_ptr = self.get_reg();
-                self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* {}, i64 0, i64 0))\n", q_ptr, buf_ptr, q_mark));
-                
-                let param_val_final = self.get_reg();
-                self.emit(&format!("  {} = alloca i64\n", param_val_final));
-                self.emit(&format!("  store i64 0, i64* {}\n", param_val_final));
-
-                let has_q = self.get_reg();
-                self.emit(&format!("  {} = icmp ne i8* {}, null\n", has_q, q_ptr));
-                let l_q_then = self.get_label();
-                let l_q_next = self.get_label();
-                self.emit(&format!("  br i1 {}, label %{}, label %{}\n", has_q, l_q_then, l_q_next));
-                
-                self.emit(&format!("{}:\n", l_q_then));
-                // Search for '='
-                let eq_mark = self.add_string("=".to_string());
-                let eq_ptr = self.get_reg();
-                self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* {}, i64 0, i64 0))\n", eq_ptr, q_ptr, eq_mark));
-                
-                let has_eq = self.get_reg();
-                self.emit(&format!("  {} = icmp ne i8* {}, null\n", has_eq, eq_ptr));
-                let l_eq_then = self.get_label();
-                self.emit(&format!("  br i1 {}, label %{}, label %{}\n", has_eq, l_eq_then, l_q_next));
-                
-                self.emit(&format!("{}:\n", l_eq_then));
-                let val_start = self.get_reg();
-                self.emit(&format!("  {} = getelementptr inbounds i8, i8* {}, i64 1\n", val_start, eq_ptr));
-                let parsed_int = self.get_reg();
-                self.emit(&format!("  {} = call i64 @atoi(i8* {})\n", parsed_int, val_start));
-                self.emit(&format!("  store i64 {}, i64* {}\n", parsed_int, param_val_final));
-                self.emit(&format!("  br label %{}\n", l_q_next));
-
-                self.emit(&format!("{}:\n", l_q_next));
-                let final_arg = self.get_reg();
-                self.emit(&format!("  {} = load i64, i64* {}\n", final_arg, param_val_final));
-
-                // Call method: Class_Method(this, param)
-                let res_ptr = self.get_reg();
-                self.emit(&format!("  {} = call i8* @{}_{}(%struct.{}* {}, i64 {})\n", res_ptr, class_name, m, class_name, obj_val, final_arg));
-                
-                // Measure length (strlen)
-                let res_len = self.get_reg();
-                self.emit(&format!("  {} = call i64 @strlen(i8* {})\n", res_len, res_ptr));
-                
-                self.emit(&format!("  call i64 @send(i64 {}, i8* {}, i64 {}, i64 0)\n", client_sock, res_ptr, res_len));
-                self.emit(&format!("  br label %{}\n", l_next));
-                
-                self.emit(&format!("{}:\n", l_next));
-            }
+        let methods: Vec<String> = self.types.classes[&class_name].vtable.iter().map(|(m, _)| m.clone()).collect();
+        for m in methods {
+            let m_val = self.add_string(m.to_string());
+            let m_ptr = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
+            
+            let str_match = self.get_reg();
+            self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* {})\n", str_match, buf_ptr, m_ptr));
+            
+            let is_match = self.get_reg();
+            self.emit(&format!("  {} = icmp ne i8* {}, null\n", is_match, str_match));
+            
+            let l_then = self.get_label();
+            let l_next = self.get_label();
+            self.emit(&format!("  br i1 {}, label %{}, label %{}\n", is_match, l_then, l_next));
+            
+            self.emit(&format!("{}:\n", l_then));
+            // --- Parameter Parsing Logic ---
+            // Search for '?' in buffer
+            let q_mark = self.add_string("?".to_string());
+            let q_ptr = self.get_reg();
+            self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* {}, i64 0, i64 0))\n", q_ptr, buf_ptr, q_mark));
+            
+            let param_val_final = self.get_reg();
+            self.emit(&format!("  {} = alloca i64\n", param_val_final));
+            self.emit(&format!("  store i64 0, i64* {}\n", param_val_final));
+
+            let has_q = self.get_reg();
+            self.emit(&format!("  {} = icmp ne i8* {}, null\n", has_q, q_ptr));
+            let l_q_then = self.get_label();
+            let l_q_next = self.get_label();
+            self.emit(&format!("  br i1 {}, label %{}, label %{}\n", has_q, l_q_then, l_q_next));
+            
+            self.emit(&format!("{}:\n", l_q_then));
+            // Search for '='
+            let eq_mark = self.add_string("=".to_string());
+            let eq_ptr = self.get_reg();
+            self.emit(&format!("  {} = call i8* @strstr(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* {}, i64 0, i64 0))\n", eq_ptr, q_ptr, eq_mark));
+            
+            let has_eq = self.get_reg();
+            self.emit(&format!("  {} = icmp ne i8* {}, null\n", has_eq, eq_ptr));
+            let l_eq_then = self.get_label();
+            self.emit(&format!("  br i1 {}, label %{}, label %{}\n", has_eq, l_eq_then, l_q_next));
+            
+            self.emit(&format!("{}:\n", l_eq_then));
+            let val_start = self.get_reg();
+            self.emit(&format!("  {} = getelementptr inbounds i8, i8* {}, i64 1\n", val_start, eq_ptr));
+            let parsed_int = self.get_reg();
+            self.emit(&format!("  {} = call i64 @atoi(i8* {})\n", parsed_int, val_start));
+            self.emit(&format!("  store i64 {}, i64* {}\n", parsed_int, param_val_final));
+            self.emit(&format!("  br label %{}\n", l_q_next));
+
+            self.emit(&format!("{}:\n", l_q_next));
+            let final_arg = self.get_reg();
+            self.emit(&format!("  {} = load i64, i64* {}\n", final_arg, param_val_final));
+
+            // Call method: Class_Method(this, param)
+            let res_ptr = self.get_reg();
+            self.emit(&format!("  {} = call i8* @{}_{}(%struct.{}* {}, i64 {})\n", res_ptr, class_name, m, class_name, obj_val, final_arg));
+            
+            // Measure length (strlen)
+            let res_len = self.get_reg();
+            self.emit(&format!("  {} = call i64 @strlen(i8* {})\n", res_len, res_ptr));
+            
+            self.emit(&format!("  call i64 @send(i64 {}, i8* {}, i64 {}, i64 0)\n", client_sock, res_ptr, res_len));
+            self.emit(&format!("  br label %{}\n", l_next));
+            
+            self.emit(&format!("{}:\n", l_next));
         }
 
         if self.target_os == TargetOs::Windows {
@@ -511,13 +1366,23 @@ impl Compiler {
         ("0".to_string(), VarType::Int)
     }
 
+    /// Looks up the struct index and type of a field, reporting unknown fields as diagnostics.
+    /// Index 0 of every struct holds the vtable pointer.
+    fn field_index(&self, class_name: &str, field_name: &str) -> CompileResult<(usize, VarType)> {
+        let fields = &self.types.classes.get(class_name)
+            .ok_or_else(|| Diagnostic::error("E0308", format!("unknown class '{}'", class_name)))?.fields;
+        fields.iter().position(|(name, _)| name == field_name).map(|i| (i + 1, fields[i].1.clone())).ok_or_else(|| {
+            let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            Diagnostic::error("E0309", format!("class '{}' has no field '{}'", class_name, field_name))
+                .with_note(format!("available fields: {}", names.join(", ")))
+        })
+    }
+
     fn resolve_full_name(&self, expr: &Expr) -> Option<String> {
-        match expr {
-            Expr::Variable(n) => Some(n.clone()),
-            Expr::Get(inner, field) => {
-                if let Some(parent) = self.resolve_full_name(inner) {
-                    Some(format!("{}.{}", parent, field))
-                } else { None }
+        match &expr.kind {
+            ExprKind::Variable(n) => Some(n.clone()),
+            ExprKind::Get(inner, field) => {
+                self.resolve_full_name(inner).map(|parent| format!("{}.{}", parent, field))
             },
             _ => None
         }
@@ -526,193 +1391,272 @@ impl Compiler {
     /// The core recursive expression compiler.
     /// Translates Aura expressions (AST) into specific LLVM IR instructions.
     /// Returns a tuple containing the LLVM value (register or literal) and its Aura VarType.
-    fn compile_expr(&mut self, expr: &Expr) -> (String, VarType) {
-        match expr {
-            Expr::Number(n) => (format!("{}", n), VarType::Int),
-            Expr::String(s) => {
+    fn compile_expr(&mut self, expr: &Expr) -> CompileResult<(String, VarType)> {
+        // Errors raised without a more precise location point at the whole expression
+        self.compile_expr_kind(expr).map_err(|d| d.or_span(expr.span))
+    }
+
+    fn compile_expr_kind(&mut self, expr: &Expr) -> CompileResult<(String, VarType)> {
+        match &expr.kind {
+            ExprKind::Number(n) => Ok((format!("{}", n), VarType::Int)),
+            ExprKind::Float(f) => Ok((float_const(*f), VarType::Float)),
+            ExprKind::String(s) => {
                 let str_id = self.add_string(s.clone());
-                (str_id, VarType::Str)
+                Ok((str_id, VarType::Str))
             }
-            Expr::Bool(b) => {
+            ExprKind::Bool(b) => {
                 let val = if *b { "1" } else { "0" };
-                (val.to_string(), VarType::Bool)
+                Ok((val.to_string(), VarType::Bool))
             }
-            Expr::Unary(op, inner) => {
-                let (val, vtype) = self.compile_expr(inner);
+            ExprKind::Unary(op, inner) => {
+                let (val, vtype) = self.compile_expr(inner)?;
                 if op == &TokenType::Not {
                     if vtype == VarType::Bool {
                         let reg = self.get_reg();
                         self.emit(&format!("  {} = xor i1 {}, 1\n", reg, val));
-                        (reg, VarType::Bool)
+                        Ok((reg, VarType::Bool))
                     } else {
-                        panic!("Not operator (!) only supports boolean values");
+                        Err(type_error("the '!' operator only supports bool values", &vtype))
                     }
+                } else if op == &TokenType::Minus && vtype == VarType::Float {
+                    let reg = self.get_reg();
+                    self.emit(&format!("  {} = fneg double {}\n", reg, val));
+                    Ok((reg, VarType::Float))
+                } else if op == &TokenType::Minus && vtype == VarType::Int {
+                    // -x is 0 - x, so checked builds also catch negating the smallest int
+                    if self.overflow_checks {
+                        return Ok((self.emit_checked_arith(op, "0", &val, expr.span), VarType::Int));
+                    }
+                    let reg = self.get_reg();
+                    self.emit(&format!("  {} = sub i64 0, {}\n", reg, val));
+                    Ok((reg, VarType::Int))
                 } else {
-                    panic!("Unsupported unary operator: {:?}", op);
+                    Err(Diagnostic::error("E0305", format!("unsupported unary operator {:?}", op)))
                 }
             }
-            Expr::ArrayLiteral(_) => {
-                panic!("Array Literal can only be used in variable declaration!");
+            ExprKind::ArrayLiteral(elements) => {
+                let vtype = typed(expr);
+                let VarType::Array(elem_type) = &vtype else {
+                    return Err(type_error("invalid array literal", &vtype));
+                };
+                let mut vals = Vec::new();
+                for el in elements {
+                    let (val, el_type) = self.compile_expr(el)?;
+                    vals.push(self.coerce(val, &el_type, elem_type));
+                }
+                let arr = self.emit_array_new(elem_type, &vals.len().to_string());
+                let elem = llvm_type(elem_type);
+                for (i, val) in vals.into_iter().enumerate() {
+                    let slot = self.emit_array_slot(&arr, elem_type, &i.to_string());
+                    self.emit(&format!("  store {} {}, {}* {}\n", elem, val, elem, slot));
+                }
+                Ok((arr, vtype))
             }
-            Expr::Variable(name) => {
-                let vtype_opt = self.var_types.get(name).cloned();
-                if let Some(vtype) = vtype_opt {
-                    let reg = self.get_reg();
-                    let result = match &vtype { 
-                        VarType::Int => { self.emit(&format!("  {} = load i64, i64* %{}_ptr\n", reg, name)); (reg, vtype.clone()) }, 
-                        VarType::Str => { self.emit(&format!("  {} = load i8*, i8** %{}_ptr\n", reg, name)); (reg, vtype.clone()) },
-                        VarType::Bool => { self.emit(&format!("  {} = load i1, i1* %{}_ptr\n", reg, name)); (reg, vtype.clone()) },
-                        VarType::Instance(cls) => { self.emit(&format!("  {} = load %struct.{}*, %struct.{}** %{}_ptr\n", reg, cls, cls, name)); (reg, vtype.clone()) },
-                        VarType::Array(elem, len) => {
-                            let p_reg = self.get_reg();
-                            self.emit(&format!("  {} = bitcast [{} x i64]* %{}_ptr to i64*\n", p_reg, len, name));
-                            (p_reg, VarType::Array(elem.clone(), *len))
-                        }
-                    };
-                    result
-                } else {
-                    panic!("Undefined variable: {}", name);
+            ExprKind::MapLiteral(entries) => {
+                let vtype = typed(expr);
+                let VarType::Map(key_type, value_type) = &vtype else {
+                    return Err(type_error("invalid map literal", &vtype));
+                };
+                let map = self.emit_map_new(key_type, value_type);
+                let value = llvm_type(value_type);
+                for (key_expr, value_expr) in entries {
+                    let key = self.compile_map_key(key_expr, key_type)?;
+                    let (val, val_type) = self.compile_expr(value_expr)?;
+                    let val = self.coerce(val, &val_type, value_type);
+                    let slot = self.emit_map_slot(&map, value_type, &key, None);
+                    self.emit(&format!("  store {} {}, {}* {}\n", value, val, value, slot));
                 }
+                Ok((map, vtype))
             }
-            Expr::New(class_name) => {
-                if let Some(fields) = self.classes.get(class_name) {
-                    let field_count = fields.len();
-                    // Assuming all fields are i64 (8 bytes). Struct size = 8 * count.
-                    let size = field_count * 8; 
-                    
+            ExprKind::Interpolated(parts) => {
+                // Every part becomes a string, then one runtime call joins them all
+                let mut args = Vec::new();
+                for part in parts {
+                    let (val, vtype) = self.compile_expr(part)?;
+                    args.push(format!("i8* {}", self.ensure_string(val, vtype)));
+                }
+                let reg = self.get_reg();
+                self.emit(&format!("  {} = call i8* (i64, ...) @aura_str_join(i64 {}, {})\n", reg, args.len(), args.join(", ")));
+                Ok((reg, VarType::Str))
+            }
+            // A function name that no variable shadows is a function value
+            ExprKind::Variable(name) if !self.var_types.contains_key(name) && self.types.functions.contains_key(name) => {
+                let sig = self.types.functions[name].clone();
+                let vtype = VarType::Function(sig.params.clone(), Box::new(sig.ret.clone()));
+                Ok((self.emit_static_closure(name, &sig, true), vtype))
+            }
+            ExprKind::Lambda(lambda) => self.emit_lambda(expr.span, lambda),
+            ExprKind::CallValue(callee, args) => {
+                let (closure, vtype) = self.compile_expr(callee)?;
+                self.emit_value_call(&closure, &vtype, args)
+            }
+            ExprKind::Variable(name) => {
+                let vtype = self.var_types.get(name).cloned().ok_or_else(|| undefined_variable(name))?;
+                let slot = self.slot(name);
+                let reg = self.get_reg();
+                let ty = llvm_type(&vtype);
+                self.emit(&format!("  {} = load {}, {}* {}\n", reg, ty, ty, slot));
+                Ok((reg, vtype))
+            }
+            ExprKind::New(class_name, args) => {
+                if self.types.classes.contains_key(class_name) {
+                    // sizeof(%struct.X) via the null-GEP idiom; calloc zeroes every field (0, false, null)
+                    let size_ptr = self.get_reg();
+                    self.emit(&format!("  {} = getelementptr %struct.{}, %struct.{}* null, i32 1\n", size_ptr, class_name, class_name));
+                    let size = self.get_reg();
+                    self.emit(&format!("  {} = ptrtoint %struct.{}* {} to i64\n", size, class_name, size_ptr));
+
                     let malloc_reg = self.get_reg();
-                    self.emit(&format!("  {} = call i8* @malloc(i64 {})\n", malloc_reg, size));
+                    self.emit(&format!("  {} = call i8* @calloc(i64 1, i64 {})\n", malloc_reg, size));
                     
                     let cast_reg = self.get_reg();
                     self.emit(&format!("  {} = bitcast i8* {} to %struct.{}*\n", cast_reg, malloc_reg, class_name));
+
+                    // The object's class decides which implementation a virtual call runs
+                    let vtable_len = self.types.interface_slots().len() + self.types.classes[class_name].vtable.len();
+                    let vtable_ptr = self.get_reg();
+                    self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 0\n", vtable_ptr, class_name, class_name, cast_reg));
+                    self.emit(&format!("  store i8** getelementptr inbounds ([{} x i8*], [{} x i8*]* @vtable.{}, i64 0, i64 0), i8*** {}\n", vtable_len, vtable_len, class_name, vtable_ptr));
+
+                    // Array and map fields start out empty rather than null, so they can be filled right away
+                    let fields = self.types.classes[class_name].fields.clone();
+                    for (i, (_, field_type)) in fields.iter().enumerate() {
+                        let empty = match field_type {
+                            VarType::Array(elem_type) => self.emit_array_new(elem_type, "0"),
+                            VarType::Map(key_type, value_type) => self.emit_map_new(key_type, value_type),
+                            _ => continue,
+                        };
+                        let ty = llvm_type(field_type);
+                        let field_ptr = self.get_reg();
+                        self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", field_ptr, class_name, class_name, cast_reg, i + 1));
+                        self.emit(&format!("  store {} {}, {}* {}\n", ty, empty, ty, field_ptr));
+                    }
+
+                    // Run the constructor (own or inherited) on the zeroed object
+                    if let Some(init) = self.types.classes[class_name].methods.get("init").cloned() {
+                        let arg_vals = self.compile_call_args(args, &init.params)?;
+                        self.emit_method_call(class_name, &cast_reg, "init", arg_vals);
+                    }
                     
-                    (cast_reg, VarType::Instance(class_name.clone()))
+                    Ok((cast_reg, VarType::Instance(class_name.clone())))
                 } else {
-                    panic!("Unknown class: {}", class_name);
+                    Err(Diagnostic::error("E0308", format!("unknown class '{}'", class_name)))
                 }
             }
-            Expr::Get(obj_expr, field_name) => {
-                let (obj_reg, vtype) = self.compile_expr(obj_expr);
+            ExprKind::Get(obj_expr, field_name) => {
+                let (obj_reg, vtype) = self.compile_expr(obj_expr)?;
                 if let VarType::Instance(class_name) = vtype {
-                     let fields = self.classes.get(&class_name).unwrap();
-                     let index = fields.iter().position(|r| r == field_name)
-                        .expect(&format!("Field '{}' not found in class '{}'", field_name, class_name));
+                     let (index, field_type) = self.field_index(&class_name, field_name)?;
                      
                      let gep_reg = self.get_reg();
                      self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", 
                          gep_reg, class_name, class_name, obj_reg, index));
                      
+                     let ty = llvm_type(&field_type);
                      let val_reg = self.get_reg();
-                     self.emit(&format!("  {} = load i64, i64* {}\n", val_reg, gep_reg));
-                     
-                     (val_reg, VarType::Int) 
-                } else { panic!("Property access on non-object"); }
+                     self.emit(&format!("  {} = load {}, {}* {}\n", val_reg, ty, ty, gep_reg));
+                     Ok((val_reg, field_type))
+                } else { Err(type_error(&format!("cannot read field '{}' of a non-object", field_name), &vtype)) }
             }
-            Expr::Set(obj_expr, field_name, val_expr) => {
-                let (obj_reg, vtype) = self.compile_expr(obj_expr);
+            ExprKind::Set(obj_expr, field_name, val_expr) => {
+                let (obj_reg, vtype) = self.compile_expr(obj_expr)?;
                 if let VarType::Instance(class_name) = vtype {
-                     let fields = self.classes.get(&class_name).unwrap();
-                     let index = fields.iter().position(|r| r == field_name)
-                        .expect(&format!("Field '{}' not found in class '{}'", field_name, class_name));
+                     let (index, field_type) = self.field_index(&class_name, field_name)?;
                      
-                     let (val_val, val_type) = self.compile_expr(val_expr);
-                     let final_val = if val_type == VarType::Int {
-                         val_val.clone()
-                     } else if val_type == VarType::Str {
-                         let ptr_reg = self.get_reg();
-                         self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", ptr_reg, val_val));
-                         ptr_reg
-                     } else { val_val.clone() };
+                     let (val_val, val_type) = self.compile_expr(val_expr)?;
+                     let val_val = self.coerce(val_val, &val_type, &field_type);
+                     let ty = llvm_type(&field_type);
 
                      let gep_reg = self.get_reg();
                      self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", 
                          gep_reg, class_name, class_name, obj_reg, index));
                      
-                     self.emit(&format!("  store i64 {}, i64* {}\n", final_val, gep_reg));
-                     (val_val.clone(), VarType::Int)
-                } else { panic!("Property set on non-object"); }
+                     self.emit(&format!("  store {} {}, {}* {}\n", ty, val_val, ty, gep_reg));
+                     Ok((val_val, field_type))
+                } else { Err(type_error(&format!("cannot set field '{}' of a non-object", field_name), &vtype)) }
             }
-            Expr::IndexAccess(name, index_expr) => {
-                 let vtype = self.var_types.get(name).expect(&format!("Undefined variable: {}", name)).clone();
-                 if let VarType::Array(elem_type, len) = vtype {
-                     let (idx_val, _) = self.compile_expr(index_expr);
-                     let ptr_reg = self.get_reg();
-                     let llvm_type = match *elem_type {
-                         VarType::Int => "i64",
-                         VarType::Str => "i8*",
-                         _ => "i64"
-                     };
-                     self.emit(&format!("  {} = getelementptr inbounds [{} x {}], [{} x {}]* %{}_ptr, i64 0, i64 {}\n", 
-                         ptr_reg, len, llvm_type, len, llvm_type, name, idx_val));
-                     
-                     let val_reg = self.get_reg();
-                     self.emit(&format!("  {} = load {}, {}* {}\n", val_reg, llvm_type, llvm_type, ptr_reg));
-                     (val_reg, *elem_type)
-                 } else { panic!("'{}' is not an array!", name); }
+            ExprKind::IndexAccess(array_expr, index_expr) => {
+                 let (ptr_reg, elem_type) = self.element_ptr(array_expr, index_expr)?;
+                 let elem_llvm = llvm_type(&elem_type);
+                 let val_reg = self.get_reg();
+                 self.emit(&format!("  {} = load {}, {}* {}\n", val_reg, elem_llvm, elem_llvm, ptr_reg));
+                 Ok((val_reg, elem_type))
             }
-            Expr::MethodCall(obj_expr, method_name, args) => {
+            ExprKind::MethodCall(obj_expr, method_name, args) => {
                 // Check if it's a namespaced system library call: system.net.api_listen()
                 if let Some(full_name) = self.resolve_full_name(obj_expr) {
                     let full_call = format!("{}.{}", full_name, method_name);
                     if full_call == "system.net.api_listen" || (full_call == "api_listen" && !self.system_modules.is_empty()) {
-                         let (port_val, _) = self.compile_expr(&args[0]);
-                         let (obj_val, obj_type) = self.compile_expr(&args[1]);
+                         expect_args("api_listen", args, 2)?;
+                         let (port_val, _) = self.compile_expr(&args[0])?;
+                         let (obj_val, obj_type) = self.compile_expr(&args[1])?;
                          if let VarType::Instance(class_name) = obj_type {
                              let sock = self.get_reg();
                              self.emit(&format!("  {} = call i64 @aura_net_setup(i64 {})\n", sock, port_val));
-                             if let Some(methods) = self.class_methods.get(&class_name).cloned() {
-                                 for m in methods {
-                                     let m_val = self.add_string(m.to_string());
-                                     let m_ptr = self.get_reg();
-                                     self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", m_ptr, m.len()+1, m.len()+1, m_val));
-                                     let fn_name = format!("fn_{}_{}", class_name, m);
-                                     self.emit(&format!("  call void @aura_mvc_register(i8* {}, i8* bitcast (i8* (%struct.{}*, i64)* @{} to i8*))\n", m_ptr, class_name, fn_name));
-                                 }
-                             }
+                             self.emit_route_registration(&class_name, &obj_val);
                              let cast_reg = self.get_reg();
                              self.emit(&format!("  {} = bitcast %struct.{}* {} to i8*\n", cast_reg, class_name, obj_val));
                              self.emit(&format!("  call void @aura_mvc_serve(i64 {}, i8* {})\n", sock, cast_reg));
-                             return ("0".to_string(), VarType::Int);
-                         } else { panic!("api_listen requires a class instance."); }
+                             return Ok(("0".to_string(), VarType::Void));
+                         } else { return Err(type_error("api_listen requires a class instance", &obj_type)); }
                     }
                 }
 
-                let (obj_val, obj_type) = self.compile_expr(obj_expr);
+                let (obj_val, obj_type) = self.compile_expr(obj_expr)?;
+                if let VarType::Array(elem_type) = &obj_type {
+                    return self.emit_array_method(&obj_val, elem_type, method_name, args);
+                }
+                if let VarType::Map(key_type, _) = &obj_type {
+                    return self.emit_map_method(&obj_val, key_type, method_name, args);
+                }
                 if let VarType::Instance(class_name) = obj_type {
-                    // Mangled name: Class_Method
-                    let func_name = format!("{}_{}", class_name, method_name);
-                    
-                    let mut arg_vals = Vec::new();
-                    // Pass 'this' as first argument
-                    arg_vals.push(format!("%struct.{}* {}", class_name, obj_val));
-
-                    for arg in args {
-                        let (val, _) = self.compile_expr(arg);
-                        arg_vals.push(format!("i64 {}", val)); // Simplify: assume i64 args
+                    if self.types.method_sig(&class_name, method_name).is_none() {
+                        // A field holding a function value
+                        let (index, field_type) = self.field_index(&class_name, method_name)?;
+                        let gep_reg = self.get_reg();
+                        self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n", gep_reg, class_name, class_name, obj_val, index));
+                        let closure = self.get_reg();
+                        self.emit(&format!("  {} = load %aura.closure*, %aura.closure** {}\n", closure, gep_reg));
+                        return self.emit_value_call(&closure, &field_type, args);
                     }
-                    
-                    let args_str = arg_vals.join(", ");
-                    let reg = self.get_reg();
-                    // User methods also use 'fn_' prefix mangling
-                    self.emit(&format!("  {} = call i8* @fn_{}({})\n", reg, func_name, args_str));
-                    let int_reg = self.get_reg();
-                    self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", int_reg, reg));
-                    (int_reg, VarType::Int)
+                    let params = self.types.method_sig(&class_name, method_name).expect("sema checked the method exists").params.clone();
+                    let arg_vals = self.compile_call_args(args, &params)?;
+                    Ok(self.emit_method_call(&class_name, &obj_val, method_name, arg_vals))
                 } else {
-                    panic!("Method calls only supported on class instances.");
+                    Err(type_error(&format!("cannot call method '{}' on a non-object", method_name), &obj_type))
                 }
             },
-            Expr::NamespacedCall(parts, args) => {
+            ExprKind::SuperCall(method_name, args) => {
+                // The base class's implementation, called directly rather than through the vtable
+                let class_name = self.current_class.clone()
+                    .ok_or_else(|| Diagnostic::error("E0311", "'super' can only be used inside a class method"))?;
+                let parent = self.types.classes[&class_name].parent.clone()
+                    .ok_or_else(|| Diagnostic::error("E0311", format!("class '{}' doesn't extend another class", class_name)))?;
+                let (_, owner) = self.types.resolve_method(&parent, method_name)
+                    .ok_or_else(|| Diagnostic::error("E0315", format!("class '{}' has no method '{}'", parent, method_name)))?;
+                let sig = self.types.classes[&owner].methods[method_name].clone();
+
+                let this_reg = self.get_reg();
+                let this_slot = self.slot("this");
+                self.emit(&format!("  {} = load %struct.{}*, %struct.{}** {}\n", this_reg, class_name, class_name, this_slot));
+                let this = self.emit_upcast(&this_reg, &class_name, &owner);
+                let mut arg_vals = vec![format!("%struct.{}* {}", owner, this)];
+                arg_vals.extend(self.compile_call_args(args, &sig.params)?);
+                let val = self.emit_user_call(&format!("{}_{}", owner, method_name), &arg_vals.join(", "), &sig.ret);
+                Ok((val, sig.ret))
+            },
+            ExprKind::NamespacedCall(parts, args) => {
                 self.resolve_stdlib_call(parts, args)
             },
-            Expr::Call(name, args) => {
+            ExprKind::Call(name, args) => {
                 // Core built-ins handled by system.io dispatch
                 if name == "print" || name == "println" {
                     return self.emit_system_io_dispatch(name, args);
                 }
 
                 if name == "print_str" {
-                     let (val, vtype) = self.compile_expr(&args[0]);
+                     expect_args("print_str", args, 1)?;
+                     let (val, vtype) = self.compile_expr(&args[0])?;
                      if val.starts_with("@str.") {
                          let str_id_val = val.to_string();
                          let str_info = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == str_id_val).unwrap();
@@ -730,65 +1674,55 @@ impl Compiler {
                          };
                          self.emit(&format!("  call i64 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @fmt_str, i64 0, i64 0), i8* {})\n", final_ptr));
                      }
-                     return ("0".to_string(), VarType::Int); 
+                     return Ok(("0".to_string(), VarType::Void)); 
                 }
                 
+                if name == "int" || name == "float" {
+                    return self.emit_conversion(name, args);
+                }
+
                 if name == "free" {
-                    let (obj_reg, obj_type) = self.compile_expr(&args[0]);
+                    expect_args("free", args, 1)?;
+                    let (obj_reg, obj_type) = self.compile_expr(&args[0])?;
                     if let VarType::Instance(class_name) = obj_type {
                         let cast_reg = self.get_reg();
                         self.emit(&format!("  {} = bitcast %struct.{}* {} to i8*\n", cast_reg, class_name, obj_reg));
                         self.emit(&format!("  call void @free(i8* {})\n", cast_reg));
-                        return ("0".to_string(), VarType::Int);
+                        return Ok(("0".to_string(), VarType::Void));
                     } else {
-                        panic!("free() only supports class instances.");
+                        return Err(type_error("free() only supports class instances", &obj_type));
                     }
                 }
 
-                // Global function dispatch (User functions)
-                let mut arg_vals = Vec::new();
-                for arg in args {
-                    let (val, vtype) = self.compile_expr(arg);
-                    if let VarType::Str = vtype {
-                        if val.starts_with("@str.") {
-                            let str_id_val = val.to_string();
-                            let str_info = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == str_id_val).unwrap();
-                            let str_len = str_info.2;
-                            let ptr_reg = self.get_reg();
-                            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", ptr_reg, str_len, str_len, str_id_val));
-                            let int_reg = self.get_reg();
-                            self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", int_reg, ptr_reg));
-                            arg_vals.push(format!("i64 {}", int_reg));
-                        } else {
-                            let int_reg = self.get_reg();
-                            self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", int_reg, val));
-                            arg_vals.push(format!("i64 {}", int_reg));
-                        }
-                    } else {
-                        arg_vals.push(format!("i64 {}", val)); 
-                    }
+                // A variable holding a function value
+                if let Some(vtype @ VarType::Function(..)) = self.var_types.get(name).cloned() {
+                    let slot = self.slot(name);
+                    let closure = self.get_reg();
+                    self.emit(&format!("  {} = load %aura.closure*, %aura.closure** {}\n", closure, slot));
+                    return self.emit_value_call(&closure, &vtype, args);
                 }
-                let args_str = arg_vals.join(", ");
-                let reg = self.get_reg();
-                self.emit(&format!("  {} = call i8* @fn_{}({})\n", reg, name, args_str));
-                let int_reg = self.get_reg();
-                self.emit(&format!("  {} = ptrtoint i8* {} to i64\n", int_reg, reg));
-                (int_reg, VarType::Int)
+
+                // Global function dispatch (User functions)
+                let params = self.types.functions[name].params.clone();
+                let args_str = self.compile_call_args(args, &params)?.join(", ");
+                let ret_type = self.types.functions[name].ret.clone();
+                let val = self.emit_user_call(name, &args_str, &ret_type);
+                Ok((val, ret_type))
             }
-            Expr::Binary(left, op, right) => {
+            ExprKind::Binary(left, op, right) => {
                 if *op == TokenType::And {
                     let l_label = self.get_label();
                     let end_label = self.get_label();
                     let res_ptr = self.get_reg();
                     self.emit(&format!("  {} = alloca i1\n", res_ptr));
                     
-                    let (l_val, l_type) = self.compile_expr(left);
+                    let (l_val, l_type) = self.compile_expr(left)?;
                     let l_i1 = self.cast_to_i1(l_val, l_type);
                     self.emit(&format!("  store i1 0, i1* {}\n", res_ptr)); // Short-circuit: false
                     self.emit(&format!("  br i1 {}, label %{}, label %{}\n", l_i1, l_label, end_label));
                     
                     self.emit(&format!("{}:\n", l_label));
-                    let (r_val, r_type) = self.compile_expr(right);
+                    let (r_val, r_type) = self.compile_expr(right)?;
                     let r_i1 = self.cast_to_i1(r_val, r_type);
                     self.emit(&format!("  store i1 {}, i1* {}\n", r_i1, res_ptr));
                     self.emit(&format!("  br label %{}\n", end_label));
@@ -796,7 +1730,7 @@ impl Compiler {
                     self.emit(&format!("{}:\n", end_label));
                     let res_val = self.get_reg();
                     self.emit(&format!("  {} = load i1, i1* {}\n", res_val, res_ptr));
-                    return (res_val, VarType::Bool);
+                    return Ok((res_val, VarType::Bool));
                 }
                 if *op == TokenType::Or {
                     let r_label = self.get_label();
@@ -804,13 +1738,13 @@ impl Compiler {
                     let res_ptr = self.get_reg();
                     self.emit(&format!("  {} = alloca i1\n", res_ptr));
                     
-                    let (l_val, l_type) = self.compile_expr(left);
+                    let (l_val, l_type) = self.compile_expr(left)?;
                     let l_i1 = self.cast_to_i1(l_val, l_type);
                     self.emit(&format!("  store i1 1, i1* {}\n", res_ptr)); // Short-circuit: true
                     self.emit(&format!("  br i1 {}, label %{}, label %{}\n", l_i1, end_label, r_label));
                     
                     self.emit(&format!("{}:\n", r_label));
-                    let (r_val, r_type) = self.compile_expr(right);
+                    let (r_val, r_type) = self.compile_expr(right)?;
                     let r_i1 = self.cast_to_i1(r_val, r_type);
                     self.emit(&format!("  store i1 {}, i1* {}\n", r_i1, res_ptr));
                     self.emit(&format!("  br label %{}\n", end_label));
@@ -818,40 +1752,13 @@ impl Compiler {
                     self.emit(&format!("{}:\n", end_label));
                     let res_val = self.get_reg();
                     self.emit(&format!("  {} = load i1, i1* {}\n", res_val, res_ptr));
-                    return (res_val, VarType::Bool);
+                    return Ok((res_val, VarType::Bool));
                 }
 
-                let (l_val, l_vtype) = self.compile_expr(left);
-                let (r_val, r_vtype) = self.compile_expr(right);
+                let (l_val, l_vtype) = self.compile_expr(left)?;
+                let (r_val, r_vtype) = self.compile_expr(right)?;
 
-                if *op == TokenType::Plus && (l_vtype == VarType::Str || r_vtype == VarType::Str) {
-                    let reg = self.get_reg();
-                    let l_ptr = self.ensure_string(l_val, l_vtype);
-                    let r_ptr = self.ensure_string(r_val, r_vtype);
-                    self.emit(&format!("  {} = call i8* @aura_str_concat(i8* {}, i8* {})\n", reg, l_ptr, r_ptr));
-                    return (reg, VarType::Str);
-                }
-
-                if matches!(op, TokenType::Plus|TokenType::Minus|TokenType::Mul|TokenType::Div) {
-                    let reg = self.get_reg();
-                    let op_str = match op {
-                        TokenType::Plus => "add", TokenType::Minus => "sub",
-                        TokenType::Mul => "mul", TokenType::Div => "sdiv",
-                        _ => unreachable!()
-                    };
-                    self.emit(&format!("  {} = {} i64 {}, {}\n", reg, op_str, l_val, r_val));
-                    (reg, VarType::Int)
-                } else {
-                    let reg = self.get_reg();
-                    let op_str = match op {
-                        TokenType::Eq => "eq", TokenType::Neq => "ne",
-                        TokenType::Lt => "slt", TokenType::Gt => "sgt",
-                        TokenType::Lte => "sle", TokenType::Gte => "sge",
-                        _ => unreachable!()
-                    };
-                    self.emit(&format!("  {} = icmp {} i64 {}, {}\n", reg, op_str, l_val, r_val));
-                    (reg, VarType::Bool) 
-                }
+                self.emit_binary(op, l_val, l_vtype, r_val, r_vtype, expr.span)
             }
         }
     }
@@ -860,133 +1767,310 @@ impl Compiler {
     /// Scans the current scope stack and emits Class_drop and free() calls for all instances.
     fn emit_block_cleanup(&mut self, skip_var: Option<&str>) {
         if let Some(scope) = self.scope_stack.last().cloned() {
-            for var_name in scope.iter().rev() {
-                if let Some(skip) = skip_var { if var_name == skip { continue; } }
-                
-                // 1. Get Instance type
-                if let Some(VarType::Instance(cls_name)) = self.var_types.get(var_name).cloned() {
-                    // 2. Destructor Call (ClassName_drop)
-                    if let Some(methods) = self.class_methods.get(&cls_name) {
-                        if methods.contains(&"drop".to_string()) {
-                            let ptr_reg = self.get_reg();
-                            self.emit(&format!("  {} = load %struct.{}*, %struct.{}** %{}_ptr\n", ptr_reg, cls_name, cls_name, var_name));
-                            // drop(this) - currently returns i8* for all aura funcs
-                            self.emit(&format!("  call i8* @{}_drop(%struct.{}* {})\n", cls_name, cls_name, ptr_reg));
-                        }
-                    }
+            self.emit_scope_cleanup(&scope, skip_var);
+        }
+    }
 
-                    // 3. free(i8*)
+    /// Emits the drop and free() calls for the objects owned by one scope.
+    fn emit_scope_cleanup(&mut self, scope: &[String], skip_var: Option<&str>) {
+        for var_name in scope.iter().rev() {
+            if skip_var == Some(var_name.as_str()) { continue; }
+            
+            // 1. Get Instance type
+            if let Some(VarType::Instance(cls_name)) = self.var_types.get(var_name).cloned() {
+                let slot = self.slot(var_name);
+                // 2. Destructor Call (ClassName_drop, possibly overridden by a subclass)
+                if self.types.method_sig(&cls_name, "drop").is_some() {
                     let ptr_reg = self.get_reg();
-                    self.emit(&format!("  {} = load %struct.{}*, %struct.{}** %{}_ptr\n", ptr_reg, cls_name, cls_name, var_name));
-                    let cast_reg = self.get_reg();
-                    self.emit(&format!("  {} = bitcast %struct.{}* {} to i8*\n", cast_reg, cls_name, ptr_reg));
-                    self.emit(&format!("  call void @free(i8* {})\n", cast_reg));
+                    self.emit(&format!("  {} = load %struct.{}*, %struct.{}** {}\n", ptr_reg, cls_name, cls_name, slot));
+                    self.emit_method_call(&cls_name, &ptr_reg, "drop", Vec::new());
+                }
+
+                // 3. free(i8*)
+                let ptr_reg = self.get_reg();
+                self.emit(&format!("  {} = load %struct.{}*, %struct.{}** {}\n", ptr_reg, cls_name, cls_name, slot));
+                let cast_reg = self.get_reg();
+                self.emit(&format!("  {} = bitcast %struct.{}* {} to i8*\n", cast_reg, cls_name, ptr_reg));
+                self.emit(&format!("  call void @free(i8* {})\n", cast_reg));
+            }
+        }
+    }
+
+    /// Defines a top-level variable or constant as an LLVM global, so functions can use
+    /// it too. A value known at compile time becomes the global's initializer; any other
+    /// value is computed by `@g_X.init` when the declaration runs or a function using the
+    /// global is called, whichever comes first.
+    fn compile_global(&mut self, name: &str, expr: &Expr, constant: bool) -> CompileResult<()> {
+        let var_type = self.types.globals[name].clone();
+        let ty = llvm_type(&var_type);
+        let global = format!("@g_{}", name);
+        match self.global_values.get(name).cloned() {
+            Some(value) => {
+                let init = self.constant_llvm(&value);
+                let kind = if constant { "constant" } else { "global" };
+                self.global_defs.push(format!("{} = {} {} {}", global, kind, ty, init));
+            }
+            None => {
+                self.emit_global_init(name, expr, &var_type)?;
+                self.global_defs.push(format!("{} = global {} zeroinitializer", global, ty));
+                self.global_defs.push(format!("{}.ready = global i1 0", global));
+                self.emit(&format!("  call void {}.init()\n", global));
+            }
+        }
+        self.slots.insert(name.to_string(), global);
+        self.var_types.insert(name.to_string(), var_type);
+        Ok(())
+    }
+
+    /// Defines `@g_X.init`, which stores the value of a global the first time it is called.
+    fn emit_global_init(&mut self, name: &str, expr: &Expr, var_type: &VarType) -> CompileResult<()> {
+        let global = format!("@g_{}", name);
+        let outer = self.enter_function(&format!("g_{}.init", name), VarType::Void);
+        let label_init = self.get_label();
+        let label_done = self.get_label();
+        let ready = self.get_reg();
+        self.emit(&format!("  {} = load i1, i1* {}.ready\n", ready, global));
+        self.emit(&format!("  br i1 {}, label %{}, label %{}\n", ready, label_done, label_init));
+        self.emit(&format!("{}:\n", label_init));
+        // Set first: an initializer that uses the global again sees its zero value
+        self.emit(&format!("  store i1 1, i1* {}.ready\n", global));
+        let (val, vtype) = self.compile_expr(expr)?;
+        if vtype == VarType::Void {
+            return Err(type_error(&format!("cannot declare variable '{}' with this value", name), &vtype));
+        }
+        let val = self.coerce(val, &vtype, var_type);
+        let ty = llvm_type(var_type);
+        self.emit(&format!("  store {} {}, {}* {}\n", ty, val, ty, global));
+        self.emit(&format!("  br label %{}\n", label_done));
+        self.emit(&format!("{}:\n", label_done));
+        self.emit("  ret void\n");
+        self.leave_function(&format!("\ndefine void {}.init() {{\nentry:\n", global), outer);
+        Ok(())
+    }
+
+    /// Evaluates the top-level declarations that can be evaluated at compile time; the
+    /// others are computed by their `@g_X.init`. Constants can use earlier constants.
+    fn collect_globals(&mut self, stmts: &[Stmt], constants: &mut HashMap<String, Constant>) {
+        for stmt in stmts {
+            let (name, expr, constant) = match &stmt.kind {
+                StmtKind::Module(stmts) => { self.collect_globals(stmts, constants); continue; }
+                StmtKind::VarDecl(name, _, expr) => (name, expr, false),
+                StmtKind::ConstDecl(name, _, expr) => (name, expr, true),
+                _ => continue,
+            };
+            let Some(var_type) = self.types.globals.get(name) else { continue };
+            match fold_constant(expr, constants).and_then(|value| value.into_type(var_type)) {
+                Some(value) => {
+                    if constant { constants.insert(name.clone(), value.clone()); }
+                    self.global_values.insert(name.clone(), value);
                 }
+                None => { self.lazy_globals.insert(name.clone()); }
+            }
+        }
+    }
+
+    /// The LLVM constant for a compile-time value.
+    fn constant_llvm(&mut self, value: &Constant) -> String {
+        match value {
+            Constant::Int(n) => n.to_string(),
+            Constant::Float(f) => float_const(*f),
+            Constant::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+            Constant::Str(s) => {
+                let str_id = self.add_string(s.clone());
+                let len = s.len() + 1;
+                format!("getelementptr inbounds ([{} x i8], [{} x i8]* {}, i64 0, i64 0)", len, len, str_id)
             }
         }
     }
 
     /// Compiles a sequence of statements within a new scope block.
     /// Handles scope push/pop and ensures resources are cleaned up at the end of the block.
-    fn compile_block(&mut self, stmts: &[Stmt]) {
+    fn compile_block(&mut self, stmts: &[Stmt]) -> CompileResult<()> {
         let old_term = self.block_terminated;
+        let old_vars = self.var_types.clone();
+        let old_slots = self.slots.clone();
         self.block_terminated = false;
         self.scope_stack.push(Vec::new());
-        for stmt in stmts { self.compile_stmt(stmt); }
+        for stmt in stmts { self.compile_stmt(stmt)?; }
         self.emit_block_cleanup(None);
         self.scope_stack.pop();
         self.block_terminated = old_term; // Restore status (e.g. if the whole block returned)
+        // Variables declared in the block go out of scope
+        self.var_types = old_vars;
+        self.slots = old_slots;
+        Ok(())
+    }
+
+    /// Compiles a loop body with `continue` and `break` jumping to the given labels.
+    fn compile_loop_body(&mut self, stmts: &[Stmt], label_continue: &str, label_break: &str) -> CompileResult<()> {
+        self.loop_labels.push((label_continue.to_string(), label_break.to_string(), self.scope_stack.len()));
+        let result = self.compile_block(stmts);
+        self.loop_labels.pop();
+        result
+    }
+
+    /// Emits `define <ret> @fn_<name>(...)` for a function, for a method when the receiver
+    /// is `This` ('this' is then passed as the first argument) or for a lambda when it is
+    /// `Env` (the environment comes first). Parameters and the return value use the types
+    /// from the function's signature. Lambdas are compiled in the middle of the code around
+    /// them, so the state of that code is set aside meanwhile.
+    fn compile_function(&mut self, name: &str, receiver: Receiver, params: &[TypedName], sig: &FuncSig, body: &[Stmt]) -> CompileResult<()> {
+        let outer = self.enter_function(name, sig.ret.clone());
+
+        let first_arg = usize::from(!matches!(receiver, Receiver::None));
+        let mut arg_defs = Vec::new();
+        match receiver {
+            Receiver::This(cls_name) => arg_defs.push(format!("%struct.{}* %arg0", cls_name)),
+            Receiver::Env(_) => arg_defs.push("i8* %arg0".to_string()),
+            Receiver::None => {}
+        }
+        for (i, vtype) in sig.params.iter().enumerate() {
+            arg_defs.push(format!("{} %arg{}", llvm_type(vtype), first_arg + i));
+        }
+        let params_str = arg_defs.join(", ");
+        let ret_type = llvm_type(&sig.ret);
+        let define = format!("\ndefine {} @fn_{}({}) {{\nentry:\n", ret_type, name, params_str);
+        
+        self.scope_stack.push(Vec::new()); // Function Top-Level Scope
+        match receiver {
+            Receiver::This(cls_name) => {
+                let slot = self.declare_slot("this");
+                self.emit_var_storage("this", &slot, &format!("%struct.{}*", cls_name));
+                self.emit(&format!("  store %struct.{}* %arg0, %struct.{}** {}\n", cls_name, cls_name, slot));
+                self.var_types.insert("this".to_string(), VarType::Instance(cls_name.to_string()));
+            }
+            Receiver::Env(captures) => {
+                // The captured variables are used through the pointers in the environment
+                let cells = self.get_reg();
+                self.emit(&format!("  {} = bitcast i8* %arg0 to i8**\n", cells));
+                for (i, (capture, vtype)) in captures.iter().enumerate() {
+                    let cell_ptr = self.get_reg();
+                    self.emit(&format!("  {} = getelementptr inbounds i8*, i8** {}, i64 {}\n", cell_ptr, cells, i));
+                    let cell = self.get_reg();
+                    self.emit(&format!("  {} = load i8*, i8** {}\n", cell, cell_ptr));
+                    let slot = self.get_reg();
+                    self.emit(&format!("  {} = bitcast i8* {} to {}*\n", slot, cell, llvm_type(vtype)));
+                    self.slots.insert(capture.clone(), slot);
+                    self.var_types.insert(capture.clone(), vtype.clone());
+                }
+            }
+            Receiver::None => {}
+        }
+        for (i, ((param, _), vtype)) in params.iter().zip(&sig.params).enumerate() {
+            let slot = self.declare_slot(param);
+            let ty = llvm_type(vtype);
+            self.emit_var_storage(param, &slot, &ty);
+            self.emit(&format!("  store {} %arg{}, {}* {}\n", ty, first_arg + i, ty, slot));
+            self.var_types.insert(param.clone(), vtype.clone());
+        }
+        
+        self.compile_block(body)?;
+        
+        // Falling off the end of the function returns a zero value (0, false, null)
+        let last_line = self.current_output.lines().last().unwrap_or("").trim_start();
+        if !last_line.starts_with("ret ") && !last_line.starts_with("br ") {
+            if sig.ret == VarType::Void {
+                self.current_output.push_str("  ret void\n");
+            } else {
+                self.current_output.push_str(&format!("  ret {} zeroinitializer\n", ret_type));
+            }
+        }
+        self.leave_function(&define, outer);
+        Ok(())
+    }
+
+    /// Starts a new function, setting aside the state of the code being compiled.
+    /// Globals are visible in it unless a parameter or local variable shadows them.
+    fn enter_function(&mut self, name: &str, ret: VarType) -> FunctionState {
+        let boxed = self.types.captured.get(name).cloned().unwrap_or_default();
+        let escaping = self.types.escaping.get(name).cloned().unwrap_or_default();
+        let outer = FunctionState {
+            in_function: self.is_in_function,
+            var_types: std::mem::take(&mut self.var_types),
+            slots: std::mem::take(&mut self.slots),
+            used_slots: std::mem::take(&mut self.used_slots),
+            boxed: std::mem::replace(&mut self.boxed, boxed),
+            escaping: std::mem::replace(&mut self.escaping, escaping),
+            output: std::mem::take(&mut self.current_output),
+            allocas: std::mem::take(&mut self.entry_allocas),
+            inits: std::mem::take(&mut self.global_inits),
+            terminated: self.block_terminated,
+            scopes: std::mem::take(&mut self.scope_stack),
+            loops: std::mem::take(&mut self.loop_labels),
+            ret: std::mem::replace(&mut self.current_ret, ret),
+        };
+        for (global, ty) in &self.types.globals {
+            self.var_types.insert(global.clone(), ty.clone());
+            self.slots.insert(global.clone(), format!("@g_{}", global));
+        }
+        self.is_in_function = true;
+        self.block_terminated = false;
+        outer
+    }
+
+    /// Appends the function compiled since `enter_function` under its `define` line,
+    /// and returns to the code around it.
+    fn leave_function(&mut self, define: &str, outer: FunctionState) {
+        self.output.push_str(define);
+        self.output.push_str(&self.entry_allocas);
+        for global in &self.global_inits {
+            self.output.push_str(&format!("  call void {}.init()\n", global));
+        }
+        self.output.push_str(&self.current_output);
+        self.output.push_str("}\n");
+        self.is_in_function = outer.in_function;
+        self.var_types = outer.var_types;
+        self.slots = outer.slots;
+        self.used_slots = outer.used_slots;
+        self.boxed = outer.boxed;
+        self.escaping = outer.escaping;
+        self.current_output = outer.output;
+        self.entry_allocas = outer.allocas;
+        self.global_inits = outer.inits;
+        self.block_terminated = outer.terminated;
+        self.scope_stack = outer.scopes;
+        self.loop_labels = outer.loops;
+        self.current_ret = outer.ret;
     }
 
     /// The core recursive statement compiler.
     /// Dispatches Aura statements (Vars, Funcs, Classes, Loops, Ifs) to their LLVM IR generators.
-    fn compile_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::ClassDecl(name, fields, methods) => {
-                // Register class properties & methods
-                self.classes.insert(name.clone(), fields.clone());
+    fn compile_stmt(&mut self, stmt: &Stmt) -> CompileResult<()> {
+        self.compile_stmt_kind(stmt).map_err(|d| d.or_span(stmt.span))
+    }
+
+    fn compile_stmt_kind(&mut self, stmt: &Stmt) -> CompileResult<()> {
+        match &stmt.kind {
+            StmtKind::ClassDecl(name, _, _, _, methods) => {
+                // Fields, their types and the vtable come from sema
                 self.current_class = Some(name.clone());
-                
-                let mut method_names = Vec::new();
 
                 // Compile methods
                 for method in methods {
-                    let method_clone = method.clone();
-                    if let Stmt::FuncDecl(method_name, mut args, body) = method_clone {
-                        method_names.push(method_name.clone());
-                        // 1. Mangle Name: Class_Method
+                    if let StmtKind::FuncDecl(method_name, args, _, body) = &method.kind {
+                        let sig = self.types.classes[name].methods[method_name].clone();
+                        // Mangle Name: Class_Method, with 'this' injected as the first argument
                         let mangled_name = format!("{}_{}", name, method_name);
-                        
-                        // 2. Inject 'this' argument
-                        args.insert(0, "this".to_string());
-                        
-                        // 3. Compile as standard function
-                        self.compile_stmt(&Stmt::FuncDecl(mangled_name, args.clone(), body.to_vec()));
+                        self.compile_function(&mangled_name, Receiver::This(name), args, &sig, body)
+                            .map_err(|d| d.or_span(method.span))?;
                     }
                 }
-                
-                self.class_methods.insert(name.clone(), method_names);
 
                 // Clear context
                 self.current_class = None;
             },
-            Stmt::FuncDecl(name, args, body) => {
-                let old_in_func = self.is_in_function;
-                let old_vars = self.var_types.clone(); 
-                self.is_in_function = true;
-                self.current_output = String::new(); 
-                self.var_types.clear(); 
-                self.block_terminated = false;
-
-                let mut arg_defs = Vec::new();
-                for (i, arg_name) in args.iter().enumerate() {
-                    if arg_name == "this" {
-                        if let Some(cls_name) = self.current_class.clone() {
-                             arg_defs.push(format!("%struct.{}* %arg{}", cls_name, i));
-                        } else {
-                             // Should not happen if 'this' is only injected by us
-                             panic!("'this' argument found outside of class context");
-                        }
-                    } else {
-                        arg_defs.push(format!("i64 %arg{}", i)); 
-                    }
-                }
-                let params_str = arg_defs.join(", ");
-                self.output.push_str(&format!("\ndefine i8* @fn_{}({}) {{\nentry:\n", name, params_str));
-                
-                self.scope_stack.push(Vec::new()); // Function Top-Level Scope
-                self.block_terminated = false;
-                for (i, arg_name) in args.iter().enumerate() {
-                    if arg_name == "this" {
-                         if let Some(cls_name) = self.current_class.clone() {
-                             self.current_output.push_str(&format!("  %{}_ptr = alloca %struct.{}*\n", arg_name, cls_name));
-                             self.current_output.push_str(&format!("  store %struct.{}* %arg{}, %struct.{}** %{}_ptr\n", cls_name, i, cls_name, arg_name));
-                             self.var_types.insert(arg_name.clone(), VarType::Instance(cls_name));
-                         }
-                    } else {
-                        self.current_output.push_str(&format!("  %{}_ptr = alloca i64\n", arg_name));
-                        self.current_output.push_str(&format!("  store i64 %arg{}, i64* %{}_ptr\n", i, arg_name));
-                        self.var_types.insert(arg_name.clone(), VarType::Int);
-                    }
-                }
-                
-                self.compile_block(body);
-                
-                if !self.current_output.contains("ret i8*") {
-                    self.current_output.push_str("  ret i8* null\n");
-                }
-                self.output.push_str(&self.current_output);
-                self.output.push_str("}\n");
-                self.scope_stack.pop(); // Pop Function Scope
-                self.is_in_function = old_in_func;
-                self.var_types = old_vars;
+            // Interfaces only declare methods; classes provide the code
+            StmtKind::InterfaceDecl(..) => {}
+            StmtKind::FuncDecl(name, args, _, body) => {
+                let sig = self.types.functions[name].clone();
+                self.compile_function(name, Receiver::None, args, &sig, body)?;
             }
-            Stmt::ReturnStmt(expr_opt) => {
+            StmtKind::ReturnStmt(expr_opt) => {
                 if let Some(expr) = expr_opt {
-                    let (val, vtype) = self.compile_expr(expr);
+                    let (val, vtype) = self.compile_expr(expr)?;
                     
                     // Cleanup current block (skipping return value if from variable)
-                    let skip_v = if let Expr::Variable(n) = expr { Some(n.as_str()) } else { None };
+                    let skip_v = if let ExprKind::Variable(n) = &expr.kind { Some(n.as_str()) } else { None };
                     
                     // We need to cleanup ALL parent scopes up to the function entry? 
                     // Aura currently doesn't have deep nested blocks that survive return.
@@ -994,132 +2078,128 @@ impl Compiler {
                     self.emit_block_cleanup(skip_v);
 
                     if self.is_in_function {
-                        if vtype == VarType::Int {
-                            let ptr = self.get_reg();
-                            self.emit(&format!("  {} = inttoptr i64 {} to i8*\n", ptr, val));
-                            self.emit(&format!("  ret i8* {}\n", ptr));
-                        } else if vtype == VarType::Bool {
-                             let zext_reg = self.get_reg();
-                             self.emit(&format!("  {} = zext i1 {} to i64\n", zext_reg, val));
-                             let ptr = self.get_reg();
-                             self.emit(&format!("  {} = inttoptr i64 {} to i8*\n", ptr, zext_reg));
-                             self.emit(&format!("  ret i8* {}\n", ptr));
-                        } else if val.starts_with("@str.") {
-                             let str_len = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == val).unwrap().2;
-                             let ptr_reg = self.get_reg();
-                             self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", ptr_reg, str_len, str_len, val));
-                             self.emit(&format!("  ret i8* {}\n", ptr_reg));
-                        } else {
-                            self.emit(&format!("  ret i8* {}\n", val));
-                        }
+                        let ret_type = self.current_ret.clone();
+                        let val = self.coerce(val, &vtype, &ret_type);
+                        self.emit(&format!("  ret {} {}\n", llvm_type(&ret_type), val));
+                    } else if vtype == VarType::Bool {
+                        // In main: the exit code is an i64
+                        let code = self.get_reg();
+                        self.emit(&format!("  {} = zext i1 {} to i64\n", code, val));
+                        self.emit(&format!("  ret i64 {}\n", code));
                     } else {
-                        // In main: use i64
-                        if vtype == VarType::Bool {
-                             let zext_reg = self.get_reg();
-                             self.emit(&format!("  {} = zext i1 {} to i64\n", zext_reg, val));
-                             self.emit(&format!("  ret i64 {}\n", zext_reg));
-                        } else {
-                             self.emit(&format!("  ret i64 {}\n", val));
-                        }
+                        self.emit(&format!("  ret i64 {}\n", val));
                     }
                 } else {
-                    if self.is_in_function { self.emit("  ret i8* null\n"); }
+                    if self.is_in_function { self.emit("  ret void\n"); }
                     else { self.emit("  ret i64 0\n"); }
                 }
             }
-            Stmt::VarDecl(name, expr) => {
-                if let Expr::ArrayLiteral(elements) = expr {
-                    let len = elements.len();
-                    // Determine element type from the first element
-                    let (first_val, elem_vtype) = if len > 0 { 
-                        self.compile_expr(&elements[0]) 
-                    } else { ("0".to_string(), VarType::Int) };
-                    
-                    let llvm_type = match elem_vtype {
-                        VarType::Int => "i64",
-                        VarType::Str => "i8*",
-                        VarType::Bool => "i1",
-                        _ => "i64"
-                    };
-
-                    self.emit(&format!("  %{}_ptr = alloca [{} x {}]\n", name, len, llvm_type));
-                    self.var_types.insert(name.clone(), VarType::Array(Box::new(elem_vtype.clone()), len));
-                    
-                    for (i, el) in elements.iter().enumerate() {
-                        let (val, _) = if i == 0 { (first_val.clone(), elem_vtype.clone()) } else { self.compile_expr(el) };
-                        let ptr_reg = self.get_reg();
-                        self.emit(&format!("  {} = getelementptr inbounds [{} x {}], [{} x {}]* %{}_ptr, i64 0, i64 {}\n", 
-                            ptr_reg, len, llvm_type, len, llvm_type, name, i));
-                        
-                        let store_val = if elem_vtype == VarType::Str && val.starts_with("@str.") {
-                            let str_len = self.string_literals.iter().find(|(id, _, _)| format!("@str.{}", id) == val).unwrap().2;
-                            let reg = self.get_reg();
-                            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* {}, i64 0, i64 0\n", reg, str_len, str_len, val));
-                            reg
-                        } else { val };
-
-                        let final_store_val = if let VarType::Instance(cls) = &elem_vtype {
-                            let int_reg = self.get_reg();
-                            self.emit(&format!("  {} = ptrtoint %struct.{}* {} to i64\n", int_reg, cls, store_val));
-                            int_reg
-                        } else {
-                            store_val
-                        };
-
-                        self.emit(&format!("  store {} {}, {}* {}\n", llvm_type, final_store_val, llvm_type, ptr_reg));
-                    }
-
-                } else {
-                    let (val, vtype) = self.compile_expr(expr);
-                    match &vtype {
-                        VarType::Instance(cls) => {
-                             self.emit(&format!("  %{}_ptr = alloca %struct.{}*\n", name, cls));
-                             self.emit(&format!("  store %struct.{}* {}, %struct.{}** %{}_ptr\n", cls, val, cls, name));
-                             if let Some(scope) = self.scope_stack.last_mut() {
-                                 scope.push(name.clone());
-                             }
-                        },
-                        VarType::Int => {
-                             self.emit(&format!("  %{}_ptr = alloca i64\n", name));
-                             self.emit(&format!("  store i64 {}, i64* %{}_ptr\n", val, name));
-                        },
-                        VarType::Str => {
-                             self.emit(&format!("  %{}_ptr = alloca i8*\n", name));
-                             self.emit(&format!("  store i8* {}, i8** %{}_ptr\n", val, name));
-                        },
-                        VarType::Bool => {
-                             self.emit(&format!("  %{}_ptr = alloca i1\n", name));
-                             self.emit(&format!("  store i1 {}, i1* %{}_ptr\n", val, name));
-                        },
-                        _ => panic!("Unsupported var type decl")
-                    }
-                    self.var_types.insert(name.clone(), vtype);
+            StmtKind::VarDecl(name, _, expr) | StmtKind::ConstDecl(name, _, expr) if self.at_top_level() => {
+                self.compile_global(name, expr, matches!(stmt.kind, StmtKind::ConstDecl(..)))?;
+            }
+            StmtKind::VarDecl(name, declared, expr) => {
+                let (val, vtype) = self.compile_expr(expr)?;
+                if vtype == VarType::Void {
+                    return Err(type_error(&format!("cannot declare variable '{}' with this value", name), &vtype));
                 }
+                let var_type = declared.clone().unwrap_or_else(|| vtype.clone());
+                let val = self.coerce(val, &vtype, &var_type);
+                let ty = llvm_type(&var_type);
+                let slot = self.declare_slot(name);
+                self.emit_var_storage(name, &slot, &ty);
+                self.emit(&format!("  store {} {}, {}* {}\n", ty, val, ty, slot));
+                // The scope owns (and frees) objects created by this declaration,
+                // not aliases of objects owned elsewhere. An object that is stored,
+                // passed, returned or captured by a closure may be used after the
+                // scope ends, so it is kept.
+                if let (VarType::Instance(_), ExprKind::New(..)) = (&vtype, &expr.kind)
+                    && !self.boxed.contains(name)
+                    && !self.escaping.contains(name)
+                    && let Some(scope) = self.scope_stack.last_mut() {
+                    scope.push(name.clone());
+                }
+                self.var_types.insert(name.clone(), var_type);
             }
-            Stmt::Assignment(name, expr) => {
-                 let (val, vtype) = self.compile_expr(expr);
-                 // Assuming var already exists and type matches
-                 match vtype {
-                     VarType::Int => { self.emit(&format!("  store i64 {}, i64* %{}_ptr\n", val, name)); }
-                     VarType::Str => { self.emit(&format!("  store i8* {}, i8** %{}_ptr\n", val, name)); }
-                     VarType::Bool => { self.emit(&format!("  store i1 {}, i1* %{}_ptr\n", val, name)); }
-                     VarType::Instance(cls) => {
-                          self.emit(&format!("  store %struct.{}* {}, %struct.{}** %{}_ptr\n", cls, val, cls, name));
-                     }
-                     _ => panic!("Assign error")
+            StmtKind::Assignment(name, expr) => {
+                 let (val, vtype) = self.compile_expr(expr)?;
+                 if vtype == VarType::Void {
+                     return Err(type_error(&format!("cannot assign this value to '{}'", name), &vtype));
+                 }
+                 let var_type = self.var_types.get(name).cloned().ok_or_else(|| undefined_variable(name))?;
+                 let val = self.coerce(val, &vtype, &var_type);
+                 let ty = llvm_type(&var_type);
+                 let slot = self.slot(name);
+                 self.emit(&format!("  store {} {}, {}* {}\n", ty, val, ty, slot));
+            }
+            StmtKind::IndexAssignment(array_expr, index_expr, expr) => {
+                 if let VarType::Map(key_type, value_type) = typed(array_expr) {
+                     // The slot is looked up after the value is compiled: adding entries moves the map's storage
+                     let (map, _) = self.compile_expr(array_expr)?;
+                     let key = self.compile_map_key(index_expr, &key_type)?;
+                     let (val, vtype) = self.compile_expr(expr)?;
+                     let val = self.coerce(val, &vtype, &value_type);
+                     let slot = self.emit_map_slot(&map, &value_type, &key, None);
+                     let ty = llvm_type(&value_type);
+                     self.emit(&format!("  store {} {}, {}* {}\n", ty, val, ty, slot));
+                     return Ok(());
                  }
+                 let (ptr_reg, elem_type) = self.element_ptr(array_expr, index_expr)?;
+                 let (val, vtype) = self.compile_expr(expr)?;
+                 let val = self.coerce(val, &vtype, &elem_type);
+                 let ty = llvm_type(&elem_type);
+                 self.emit(&format!("  store {} {}, {}* {}\n", ty, val, ty, ptr_reg));
+            }
+            StmtKind::CompoundAssignment(target, op, expr) => {
+                 // The object and index are evaluated once; the old value is read from the slot that is written
+                 let (slot, slot_type, map_entry) = match &target.kind {
+                     ExprKind::Get(obj_expr, field_name) => {
+                         let (obj_reg, vtype) = self.compile_expr(obj_expr)?;
+                         let VarType::Instance(class_name) = vtype else {
+                             return Err(type_error(&format!("cannot set field '{}' of a non-object", field_name), &vtype));
+                         };
+                         let (index, field_type) = self.field_index(&class_name, field_name)?;
+                         let gep_reg = self.get_reg();
+                         self.emit(&format!("  {} = getelementptr inbounds %struct.{}, %struct.{}* {}, i32 0, i32 {}\n",
+                             gep_reg, class_name, class_name, obj_reg, index));
+                         (gep_reg, field_type, None)
+                     }
+                     ExprKind::IndexAccess(array_expr, index_expr) => {
+                         if let VarType::Map(key_type, value_type) = typed(array_expr) {
+                             let (map, _) = self.compile_expr(array_expr)?;
+                             let key = self.compile_map_key(index_expr, &key_type)?;
+                             let slot = self.emit_map_slot(&map, &value_type, &key, Some(index_expr.span));
+                             (slot, *value_type, Some((map, key)))
+                         } else {
+                             let (ptr_reg, elem_type) = self.element_ptr(array_expr, index_expr)?;
+                             (ptr_reg, elem_type, None)
+                         }
+                     }
+                     _ => return Err(Diagnostic::error("E0203", "invalid assignment target")),
+                 };
+                 let ty = llvm_type(&slot_type);
+                 let old = self.get_reg();
+                 self.emit(&format!("  {} = load {}, {}* {}\n", old, ty, ty, slot));
+                 let (val, vtype) = self.compile_expr(expr)?;
+                 let (result, result_type) = self.emit_binary(op, old, slot_type.clone(), val, vtype, stmt.span)?;
+                 let result = self.coerce(result, &result_type, &slot_type);
+                 // Adding entries while computing the value moves the map's storage
+                 let slot = match map_entry {
+                     Some((map, key)) => self.emit_map_slot(&map, &slot_type, &key, None),
+                     None => slot,
+                 };
+                 self.emit(&format!("  store {} {}, {}* {}\n", ty, result, ty, slot));
             }
-            Stmt::ExprStmt(expr) => {
-                self.compile_expr(expr);
+            StmtKind::ExprStmt(expr) => {
+                self.compile_expr(expr)?;
             }
-            Stmt::ImportStmt(module) => {
+            StmtKind::ImportStmt(module) => {
                 self.system_modules.push(module.clone());
             }
-            Stmt::Print(expr) => {
-                self.emit_system_io_dispatch("print", &[expr.clone()]);
+            StmtKind::Print(expr) => {
+                self.emit_system_io_dispatch("print", std::slice::from_ref(expr))?;
             }
-            Stmt::IfStmt(cond, then_block, else_block_opt) => {
-                let (val, vtype) = self.compile_expr(cond);
+            StmtKind::IfStmt(cond, then_block, else_block_opt) => {
+                let (val, vtype) = self.compile_expr(cond)?;
                 let cond_reg = self.cast_to_i1(val, vtype);
                 let label_then = self.get_label();
                 let label_else = self.get_label();
@@ -1128,38 +2208,119 @@ impl Compiler {
 
                 self.emit(&format!("  br i1 {}, label %{}, label %{}\n", cond_reg, label_then, jump_false));
                 self.emit(&format!("{}:\n", label_then));
-                self.compile_block(then_block);
+                self.compile_block(then_block)?;
                 self.emit(&format!("  br label %{}\n", label_merge)); 
                 if let Some(else_block) = else_block_opt {
                     self.emit(&format!("{}:\n", label_else));
-                    self.compile_block(else_block);
+                    self.compile_block(else_block)?;
                     self.emit(&format!("  br label %{}\n", label_merge));
                 }
                 self.emit(&format!("{}:\n", label_merge));
             }
-            Stmt::WhileStmt(cond, block) => {
+            StmtKind::WhileStmt(cond, block, step) => {
                 let label_cond = self.get_label();
                 let label_body = self.get_label();
                 let label_end = self.get_label();
+                // `continue` in a for loop still has to run the step
+                let label_step = if step.is_empty() { label_cond.clone() } else { self.get_label() };
                 self.emit(&format!("  br label %{}\n", label_cond));
                 self.emit(&format!("{}:\n", label_cond));
-                let (val, vtype) = self.compile_expr(cond);
+                let (val, vtype) = self.compile_expr(cond)?;
                 let cond_reg = self.cast_to_i1(val, vtype);
                 self.emit(&format!("  br i1 {}, label %{}, label %{}\n", cond_reg, label_body, label_end));
                 self.emit(&format!("{}:\n", label_body));
-                self.compile_block(block);
+                self.compile_loop_body(block, &label_step, &label_end)?;
+                if !step.is_empty() {
+                    self.emit(&format!("  br label %{}\n", label_step));
+                    self.emit(&format!("{}:\n", label_step));
+                    self.compile_block(step)?;
+                }
+                self.emit(&format!("  br label %{}\n", label_cond));
+                self.emit(&format!("{}:\n", label_end));
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let (label_continue, label_break, depth) = self.loop_labels.last().cloned()
+                    .ok_or_else(|| Diagnostic::error("E0316", "'break' or 'continue' outside of a loop"))?;
+                // Objects owned by the blocks being left are freed first
+                for scope in self.scope_stack[depth..].to_vec().iter().rev() {
+                    self.emit_scope_cleanup(scope, None);
+                }
+                let target = if matches!(stmt.kind, StmtKind::Break) { label_break } else { label_continue };
+                self.emit(&format!("  br label %{}\n", target));
+            }
+            StmtKind::Foreach(name, collection, block) => {
+                let (collection_val, collection_vtype) = self.compile_expr(collection)?;
+                let (arr, elem_vtype) = match collection_vtype {
+                    VarType::Array(elem) => (collection_val, *elem),
+                    // Maps are iterated over a snapshot of their keys, so the body may remove entries
+                    VarType::Map(key, _) => (self.emit_map_keys(&collection_val), *key),
+                    other => return Err(type_error("foreach can only iterate over arrays and maps", &other)),
+                };
+                let elem_type = llvm_type(&elem_vtype);
+                let old_vars = self.var_types.clone();
+                let old_slots = self.slots.clone();
+
+                // Hidden index counter plus the loop variable, both allocated in the entry block.
+                // A captured loop variable gets a new heap cell every iteration instead.
+                let idx_slot = self.get_reg();
+                self.emit_alloca(&idx_slot, "i64");
+                self.emit(&format!("  store i64 0, i64* {}\n", idx_slot));
+                let var_slot = self.declare_slot(name);
+                let boxed = self.boxed.contains(name);
+                if !boxed {
+                    self.emit_var_storage(name, &var_slot, &elem_type);
+                }
+                self.var_types.insert(name.clone(), elem_vtype.clone());
+
+                let label_cond = self.get_label();
+                let label_body = self.get_label();
+                let label_step = self.get_label();
+                let label_end = self.get_label();
+                self.emit(&format!("  br label %{}\n", label_cond));
+                self.emit(&format!("{}:\n", label_cond));
+                let idx = self.get_reg();
+                self.emit(&format!("  {} = load i64, i64* {}\n", idx, idx_slot));
+                // The length is re-read every time: the body may push or pop
+                let len = self.emit_array_len(&arr);
+                let cond_reg = self.get_reg();
+                self.emit(&format!("  {} = icmp slt i64 {}, {}\n", cond_reg, idx, len));
+                self.emit(&format!("  br i1 {}, label %{}, label %{}\n", cond_reg, label_body, label_end));
+
+                self.emit(&format!("{}:\n", label_body));
+                let elem_ptr = self.emit_array_slot(&arr, &elem_vtype, &idx);
+                let elem = self.get_reg();
+                self.emit(&format!("  {} = load {}, {}* {}\n", elem, elem_type, elem_type, elem_ptr));
+                if boxed {
+                    self.emit_var_storage(name, &var_slot, &elem_type);
+                }
+                self.emit(&format!("  store {} {}, {}* {}\n", elem_type, elem, elem_type, var_slot));
+                self.compile_loop_body(block, &label_step, &label_end)?;
+                self.emit(&format!("  br label %{}\n", label_step));
+                self.emit(&format!("{}:\n", label_step));
+                let next = self.get_reg();
+                self.emit(&format!("  {} = add i64 {}, 1\n", next, idx));
+                self.emit(&format!("  store i64 {}, i64* {}\n", next, idx_slot));
                 self.emit(&format!("  br label %{}\n", label_cond));
                 self.emit(&format!("{}:\n", label_end));
+
+                self.var_types = old_vars;
+                self.slots = old_slots;
             }
-            Stmt::BlockStmt(stmts) => {
+            // Sema only accepts constants at the top level
+            StmtKind::ConstDecl(..) => unreachable!("constant outside the top level"),
+            StmtKind::Module(stmts) => {
+                for stmt in stmts { self.compile_stmt(stmt)?; }
+            }
+            StmtKind::BlockStmt(stmts) => {
                 if let Some(_first) = stmts.first() {
                     // Check if it's an import simulation
                     // In a real standard library, we might have specialized logic here.
                     // For now, if we see BlockStmt(empty), it doesn't do much.
                 }
-                self.compile_block(stmts);
+                self.compile_block(stmts)?;
             }
         }
+        Ok(())
     }
 
     /// The main entry point for the Aura Compiler.
@@ -1167,29 +2328,60 @@ impl Compiler {
     /// 1. Registers all class structures.
     /// 2. Compiles global statements.
     /// 3. Generates the final LLVM module with headers, function bodies, and the main entry point.
-    pub fn compile(&mut self, stmts: &[Stmt]) -> String {
+    pub fn compile(&mut self, stmts: &[Stmt], types: TypeTable) -> CompileResult<String> {
+        self.types = types;
+        self.boxed = self.types.captured.get("").cloned().unwrap_or_default();
+        self.escaping = self.types.escaping.get("").cloned().unwrap_or_default();
         self.output = String::new();
         self.main_body = String::new();
+        self.entry_allocas = String::new();
         self.required_symbols.insert("system".to_string()); // Used by boilerplate
+        self.collect_globals(stmts, &mut HashMap::new());
         
-        // 1. Scan for Class Declarations first to register them (and generate struct defs later)
-        // Note: recursion/nested blocks might hide class decls if not top level.
-        // For now, only Top Level classes supported.
-        for stmt in stmts {
-            if let Stmt::ClassDecl(name, fields, _) = stmt {
-                self.classes.insert(name.clone(), fields.clone());
-            }
-        }
-        
-        // 2. Compile Statements
-        for stmt in stmts { self.compile_stmt(stmt); }
+        // Compile Statements
+        for stmt in stmts { self.compile_stmt(stmt)?; }
         
         let mut header = String::from("; Module: aura_lang\n");
+        // Runtime array header (AuraArray in aura_runtime.c): len, cap, elem_size, data
+        header.push_str("%aura.array = type { i64, i64, i64, i8* }\n");
+        // Maps (AuraMap) are only handled through runtime calls
+        header.push_str("%aura.map = type opaque\n");
+        // Function values: code pointer (taking the environment first) and captured variables
+        header.push_str("%aura.closure = type { i8*, i8* }\n");
         // Generate Struct Definitions
-         for (name, fields) in &self.classes {
-              let types_str = fields.iter().map(|_| "i64").collect::<Vec<_>>().join(", ");
-              header.push_str(&format!("%struct.{} = type {{ {} }}\n", name, types_str));
-         }
+        let mut class_names: Vec<&String> = self.types.classes.keys().collect();
+        class_names.sort();
+        for name in &class_names {
+            // Field 0 points to the class's vtable
+            let types_str = std::iter::once("i8**".to_string())
+                .chain(self.types.classes[*name].fields.iter().map(|(_, ty)| llvm_type(ty)))
+                .collect::<Vec<_>>().join(", ");
+            header.push_str(&format!("%struct.{} = type {{ {} }}\n", name, types_str));
+        }
+        // An interface value is any implementing object, seen only through its vtable
+        let mut interface_names: Vec<&String> = self.types.interfaces.keys().collect();
+        interface_names.sort();
+        for name in interface_names {
+            header.push_str(&format!("%struct.{} = type {{ i8** }}\n", name));
+        }
+        // Vtables: the interface slots, then one function pointer per method, inherited slots first
+        let interface_slots = self.types.interface_slots();
+        for name in &class_names {
+            let info = &self.types.classes[*name];
+            let implementation = |method: &str| info.vtable.iter().find(|(m, _)| m == method).map(|(_, owner)| owner);
+            let entries: Vec<String> = interface_slots.iter()
+                .map(|(interface, method)| match implementation(method) {
+                    Some(owner) if info.interfaces.contains(interface) => Some((method, owner)),
+                    _ => None,
+                })
+                .chain(info.vtable.iter().map(|(method, owner)| Some((method, owner))))
+                .map(|entry| match entry {
+                    Some((method, owner)) => format!("i8* bitcast ({} @fn_{}_{} to i8*)", self.method_fn_type(owner, method), owner, method),
+                    None => "i8* null".to_string(),
+                })
+                .collect();
+            header.push_str(&format!("@vtable.{} = constant [{} x i8*] [{}]\n", name, entries.len(), entries.join(", ")));
+        }
 
         // --- LAZY IR EMISSION (Required Symbols ONLY) ---
         let mut decls = HashSet::new();
@@ -1198,6 +2390,7 @@ impl Compiler {
                 "printf" => decls.insert("declare i64 @printf(i8*, ...)"),
                 "scanf" => decls.insert("declare i64 @scanf(i8*, ...)"),
                 "malloc" => decls.insert("declare i8* @malloc(i64)"),
+                "calloc" => decls.insert("declare i8* @calloc(i64, i64)"),
                 "free" => decls.insert("declare void @free(i8*)"),
                 "atoi" => decls.insert("declare i64 @atoi(i8*)"),
                 "strlen" => decls.insert("declare i64 @strlen(i8*)"),
@@ -1213,17 +2406,44 @@ impl Compiler {
                 "aura_net_setup" => decls.insert("declare i64 @aura_net_setup(i64)"),
                 "aura_close_socket" => decls.insert("declare void @aura_close_socket(i64)"),
                 "aura_print_int" => decls.insert("declare void @aura_print_int(i64)"),
+                "aura_panic" => decls.insert("declare void @aura_panic(i8*)"),
+                "aura_bounds_fail" => decls.insert("declare void @aura_bounds_fail(i64, i64, i8*)"),
+                "llvm.sadd.with.overflow.i64" => decls.insert("declare {i64, i1} @llvm.sadd.with.overflow.i64(i64, i64)"),
+                "llvm.ssub.with.overflow.i64" => decls.insert("declare {i64, i1} @llvm.ssub.with.overflow.i64(i64, i64)"),
+                "llvm.smul.with.overflow.i64" => decls.insert("declare {i64, i1} @llvm.smul.with.overflow.i64(i64, i64)"),
+                "aura_print_float" => decls.insert("declare void @aura_print_float(double)"),
                 "aura_print_str" => decls.insert("declare void @aura_print_str(i8*)"),
                 "aura_str_contains" => decls.insert("declare i64 @aura_str_contains(i8*, i8*)"),
                 "aura_str_find" => decls.insert("declare i8* @aura_str_find(i8*, i8*)"),
                 "aura_mvc_register" => decls.insert("declare void @aura_mvc_register(i8*, i8*)"),
+                "aura_mvc_route" => decls.insert("declare void @aura_mvc_route(i8*, i8*, i8*)"),
                 "aura_mvc_serve" => decls.insert("declare void @aura_mvc_serve(i64, i8*)"),
                 "aura_read_file" => decls.insert("declare i8* @aura_read_file(i8*)"),
                 "aura_str_replace" => decls.insert("declare i8* @aura_str_replace(i8*, i8*, i8*)"),
                 "aura_int_to_str" => decls.insert("declare i8* @aura_int_to_str(i64)"),
+                "aura_float_to_str" => decls.insert("declare i8* @aura_float_to_str(double)"),
                 "aura_str_concat" => decls.insert("declare i8* @aura_str_concat(i8*, i8*)"),
-                "aura_render_field" => decls.insert("declare i8* @aura_render_field(i8*, i8*, i64)"),
-                "aura_mvc_render_list" => decls.insert("declare i8* @aura_mvc_render_list(i8*, i64*, i32, i8*)"),
+                "aura_str_compare" => decls.insert("declare i64 @aura_str_compare(i8*, i8*)"),
+                "aura_str_join" => decls.insert("declare i8* @aura_str_join(i64, ...)"),
+                "aura_str_len" => decls.insert("declare i64 @aura_str_len(i8*)"),
+                "aura_str_substr" => decls.insert("declare i8* @aura_str_substr(i8*, i64, i64)"),
+                "aura_str_split" => decls.insert("declare %aura.array* @aura_str_split(i8*, i8*)"),
+                "aura_str_trim" => decls.insert("declare i8* @aura_str_trim(i8*)"),
+                "aura_str_index" => decls.insert("declare i64 @aura_str_index(i8*, i8*)"),
+                "aura_str_starts_with" => decls.insert("declare i64 @aura_str_starts_with(i8*, i8*)"),
+                "aura_str_to_int" => decls.insert("declare i64 @aura_str_to_int(i8*)"),
+                "aura_str_to_upper" => decls.insert("declare i8* @aura_str_to_upper(i8*)"),
+                "aura_str_to_lower" => decls.insert("declare i8* @aura_str_to_lower(i8*)"),
+                "aura_array_new" => decls.insert("declare %aura.array* @aura_array_new(i64, i64)"),
+                "aura_array_push" => decls.insert("declare i8* @aura_array_push(%aura.array*)"),
+                "aura_array_pop" => decls.insert("declare i8* @aura_array_pop(%aura.array*)"),
+                "aura_map_new" => decls.insert("declare %aura.map* @aura_map_new(i64, i64)"),
+                "aura_map_get" => decls.insert("declare i8* @aura_map_get(%aura.map*, i64, i8*)"),
+                "aura_map_set" => decls.insert("declare i8* @aura_map_set(%aura.map*, i64)"),
+                "aura_map_has" => decls.insert("declare i64 @aura_map_has(%aura.map*, i64)"),
+                "aura_map_remove" => decls.insert("declare void @aura_map_remove(%aura.map*, i64)"),
+                "aura_map_len" => decls.insert("declare i64 @aura_map_len(%aura.map*)"),
+                "aura_map_keys" => decls.insert("declare %aura.array* @aura_map_keys(%aura.map*)"),
                 _ => false, // User function or unknown
             };
         }
@@ -1244,7 +2464,7 @@ impl Compiler {
         for (id, content, len) in &self.string_literals {
              let mut llvm_str = String::new();
              for byte in content.bytes() {
-                 if byte >= 32 && byte <= 126 && byte != 34 && byte != 92 {
+                 if (32..=126).contains(&byte) && byte != 34 && byte != 92 {
                      llvm_str.push(byte as char);
                  } else {
                      llvm_str.push_str(&format!("\\{:02X}", byte));
@@ -1252,8 +2472,11 @@ impl Compiler {
              }
              header.push_str(&format!("@str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n", id, len, llvm_str));
         }
+        for def in &self.global_defs {
+            header.push_str(&format!("{}\n", def));
+        }
         
-        header.push_str("\n");
+        header.push('\n');
         header.push_str(&self.output); // Functions
         
         header.push_str("\ndefine i64 @main() {\nentry:\n");
@@ -1261,10 +2484,112 @@ impl Compiler {
             header.push_str("  call i64 @system(i8* getelementptr inbounds ([17 x i8], [17 x i8]* @cmd_chcp, i64 0, i64 0))\n");
         }
         
+        header.push_str(&self.entry_allocas);
         header.push_str(&self.main_body);
         header.push_str("  ret i64 0\n}\n");
         
-        header
+        Ok(header)
     }
 }
 
+/// Type sema inferred for an expression.
+fn typed(expr: &Expr) -> VarType {
+    expr.ty.clone().expect("sema assigns a type to every expression it accepts")
+}
+
+/// Evaluates an initializer made of literals, earlier constants, arithmetic and string
+/// concatenation. Anything else, or an operation that would overflow or divide by zero,
+/// is left to run time.
+fn fold_constant(expr: &Expr, constants: &HashMap<String, Constant>) -> Option<Constant> {
+    match &expr.kind {
+        ExprKind::Number(n) => Some(Constant::Int(*n)),
+        ExprKind::Float(f) => Some(Constant::Float(*f)),
+        ExprKind::Bool(b) => Some(Constant::Bool(*b)),
+        ExprKind::String(s) => Some(Constant::Str(s.clone())),
+        ExprKind::Variable(name) => constants.get(name).cloned(),
+        ExprKind::Unary(op, inner) => match (op, fold_constant(inner, constants)?) {
+            (TokenType::Minus, Constant::Int(n)) => n.checked_neg().map(Constant::Int),
+            (TokenType::Minus, Constant::Float(f)) => Some(Constant::Float(-f)),
+            (TokenType::Not, Constant::Bool(b)) => Some(Constant::Bool(!b)),
+            _ => None,
+        },
+        ExprKind::Binary(left, op, right) => {
+            match (fold_constant(left, constants)?, fold_constant(right, constants)?) {
+                (Constant::Int(l), Constant::Int(r)) => {
+                    let shift = u32::try_from(r).ok();
+                    let value = match op {
+                        TokenType::Plus => l.checked_add(r), TokenType::Minus => l.checked_sub(r),
+                        TokenType::Mul => l.checked_mul(r), TokenType::Div => l.checked_div(r),
+                        TokenType::Mod => l.checked_rem(r),
+                        TokenType::BitAnd => Some(l & r), TokenType::BitOr => Some(l | r), TokenType::BitXor => Some(l ^ r),
+                        TokenType::Shl => shift.and_then(|r| l.checked_shl(r)),
+                        TokenType::Shr => shift.and_then(|r| l.checked_shr(r)),
+                        _ => None,
+                    };
+                    value.map(Constant::Int)
+                }
+                (Constant::Str(l), Constant::Str(r)) if *op == TokenType::Plus => Some(Constant::Str(l + &r)),
+                (l, r) => {
+                    // Mixed int/float operands: the int side is widened
+                    let (Some(l), Some(r)) = (l.as_float(), r.as_float()) else { return None };
+                    let value = match op {
+                        TokenType::Plus => l + r, TokenType::Minus => l - r,
+                        TokenType::Mul => l * r, TokenType::Div => l / r, TokenType::Mod => l % r,
+                        _ => return None,
+                    };
+                    Some(Constant::Float(value))
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Constant {
+    fn as_float(&self) -> Option<f64> {
+        match self {
+            Constant::Int(n) => Some(*n as f64),
+            Constant::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// The value for a global of the given type (an int initializes a float).
+    fn into_type(self, var_type: &VarType) -> Option<Constant> {
+        match (self, var_type) {
+            (Constant::Int(n), VarType::Float) => Some(Constant::Float(n as f64)),
+            (value @ Constant::Int(_), VarType::Int)
+            | (value @ Constant::Float(_), VarType::Float)
+            | (value @ Constant::Bool(_), VarType::Bool)
+            | (value @ Constant::Str(_), VarType::Str) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// LLVM constant for a float literal. The hex form is exact; decimal double
+/// constants must be representable or llvm-as rejects them.
+fn float_const(f: f64) -> String {
+    format!("0x{:016X}", f.to_bits())
+}
+
+/// LLVM type of a register or variable holding a value of the given type.
+fn llvm_type(vtype: &VarType) -> String {
+    match vtype {
+        VarType::Int => "i64".to_string(),
+        VarType::Float => "double".to_string(),
+        VarType::Str => "i8*".to_string(),
+        VarType::Bool => "i1".to_string(),
+        VarType::Array(_) => "%aura.array*".to_string(),
+        VarType::Map(..) => "%aura.map*".to_string(),
+        VarType::Instance(cls) => format!("%struct.{}*", cls),
+        VarType::Function(..) => "%aura.closure*".to_string(),
+        VarType::Void => "void".to_string(),
+    }
+}
+
+/// LLVM type of the code pointer of a closure, which takes the environment before the parameters.
+fn closure_fn_type(params: &[VarType], ret: &VarType) -> String {
+    let args: Vec<String> = std::iter::once("i8*".to_string()).chain(params.iter().map(llvm_type)).collect();
+    format!("{} ({})*", llvm_type(ret), args.join(", "))
+}
@@ -0,0 +1,83 @@
+use crate::compiler::parser::{Stmt, StmtKind, TypedName};
+use crate::compiler::types::VarType;
+
+/// Renders the functions, classes, interfaces and constants of a program, with their `///` comments,
+/// as a Markdown reference (`aura doc`).
+pub fn render_markdown(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    render_stmts(stmts, &mut out);
+    out
+}
+
+fn render_stmts(stmts: &[Stmt], out: &mut String) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::FuncDecl(name, params, ret, _) => {
+                out.push_str(&format!("## `{}`\n\n", signature(name, params, ret)));
+                push_doc(out, &stmt.doc);
+            }
+            StmtKind::ClassDecl(name, parent, interfaces, fields, methods) => {
+                let mut header = format!("## class `{}`", name);
+                if let Some(parent) = parent { header.push_str(&format!(" extends `{}`", parent)); }
+                if !interfaces.is_empty() {
+                    let names: Vec<String> = interfaces.iter().map(|i| format!("`{}`", i)).collect();
+                    header.push_str(&format!(" implements {}", names.join(", ")));
+                }
+                out.push_str(&header);
+                out.push_str("\n\n");
+                push_doc(out, &stmt.doc);
+                for field in fields {
+                    out.push_str(&format!("- `{}`", typed_name(&field.name, &field.ty)));
+                    if let Some(doc) = &field.doc { out.push_str(&format!(": {}", doc.replace('\n', " "))); }
+                    out.push('\n');
+                }
+                if !fields.is_empty() { out.push('\n'); }
+                for method in methods {
+                    if let StmtKind::FuncDecl(method_name, params, ret, _) = &method.kind {
+                        out.push_str(&format!("### `{}.{}`\n\n", name, signature(method_name, params, ret)));
+                        push_doc(out, &method.doc);
+                    }
+                }
+            }
+            StmtKind::InterfaceDecl(name, methods) => {
+                out.push_str(&format!("## interface `{}`\n\n", name));
+                push_doc(out, &stmt.doc);
+                for method in methods {
+                    if let StmtKind::FuncDecl(method_name, params, ret, _) = &method.kind {
+                        out.push_str(&format!("### `{}.{}`\n\n", name, signature(method_name, params, ret)));
+                        push_doc(out, &method.doc);
+                    }
+                }
+            }
+            StmtKind::ConstDecl(name, ty, _) => {
+                out.push_str(&format!("## const `{}`\n\n", typed_name(name, ty)));
+                push_doc(out, &stmt.doc);
+            }
+            StmtKind::Module(inner) => render_stmts(inner, out),
+            _ => {}
+        }
+    }
+}
+
+fn push_doc(out: &mut String, doc: &Option<String>) {
+    if let Some(doc) = doc {
+        out.push_str(doc);
+        out.push_str("\n\n");
+    }
+}
+
+/// `name(a: int, b) -> int`, with the annotations as written.
+fn signature(name: &str, params: &[TypedName], ret: &Option<VarType>) -> String {
+    let params: Vec<String> = params.iter().map(|(param, ty)| typed_name(param, ty)).collect();
+    match ret {
+        Some(ret) => format!("{}({}) -> {}", name, params.join(", "), ret),
+        None => format!("{}({})", name, params.join(", ")),
+    }
+}
+
+fn typed_name(name: &str, ty: &Option<VarType>) -> String {
+    match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name.to_string(),
+    }
+}
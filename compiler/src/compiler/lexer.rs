@@ -1,14 +1,24 @@
+use crate::compiler::diagnostic::{CompileResult, Diagnostic};
+use crate::compiler::source::{FileId, Span};
+
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
-    Var, Print, If, Else,
-    While, For, Foreach, In, 
+    Var, Const, Print, If, Else,
+    While, For, Foreach, In, Break, Continue,
     Func, Return, Import, From,
-    Class, New, // Class support
-    Id(String), Number(i32), String(String), Bool(bool),
-    Assign, Plus, Minus, Mul, Div, 
+    Class, New, Extends, Super, // Class support
+    Interface, Implements,
+    Id(String), Number(i64), Float(f64), String(String), Bool(bool),
+    // "a ${x} b ${y} c" is InterpStart("a "), x, InterpMid(" b "), y, InterpEnd(" c")
+    InterpStart(String), InterpMid(String), InterpEnd(String),
+    Assign, Plus, Minus, Mul, Div, Mod,
+    PlusAssign, MinusAssign, MulAssign, DivAssign, ModAssign, // += -= *= /= %=
+    Increment, Decrement, // ++ --
+    BitAnd, BitOr, BitXor, Shl, Shr, // & | ^ << >>
     LParen, RParen, LBrace, RBrace, // { }
     LBracket, RBracket, Comma, Semicolon, Dot, // [ ] , ; .
+    Colon, Arrow, FatArrow,        // : -> => (type annotations, lambdas)
     Eq, Neq, Lt, Gt, Lte, Gte,      // == != < > <= >=
     And, Or, Not,                  // && || !
     EOF,
@@ -17,52 +27,239 @@ pub enum TokenType {
 #[derive(Debug, Clone)]
 pub struct Token { 
     pub kind: TokenType, 
-    pub line: usize 
+    pub span: Span,
+    pub doc: Option<String>, // `///` comment lines right before the token
 }
 
 pub struct Lexer { 
     input: Vec<char>, 
+    file: FileId,
     pos: usize, 
-    line: usize 
+    byte_pos: usize,
+    line: usize,
+    column: usize,
+    // Position where the token currently being scanned started
+    start_line: usize,
+    start_column: usize,
+    start_byte: usize,
+    // One entry per `${` being lexed: the braces opened inside it so far, and where its string starts
+    interpolations: Vec<(usize, Span)>,
+    doc_lines: Vec<String>, // `///` lines waiting for the next token
 }
 
 impl Lexer {
-    pub fn new(input: String) -> Self { 
-        Self { input: input.chars().collect(), pos: 0, line: 1 } 
+    pub fn new(input: String, file: FileId) -> Self { 
+        Self { input: input.chars().collect(), file, pos: 0, byte_pos: 0, line: 1, column: 1, start_line: 1, start_column: 1, start_byte: 0, interpolations: Vec::new(), doc_lines: Vec::new() } 
     }
     
     fn peek(&self) -> Option<char> { 
         self.input.get(self.pos).copied() 
     }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    /// Consumes digits of the given radix, allowing `_` separators between two digits
+    /// (`1_000_000`). Letters and digits that don't belong to a hex or binary literal are errors.
+    fn take_digits(&mut self, s: &mut String, radix: u32) -> CompileResult<()> {
+        let mut after_digit = false;
+        while let Some(ch) = self.peek() {
+            if ch == '_' {
+                // The next character is checked as a digit of this literal on the next iteration
+                let digit_follows = self.peek_at(1).is_some_and(|c| c.is_digit(radix) || (radix != 10 && c.is_ascii_alphanumeric()));
+                if !after_digit || !digit_follows {
+                    self.advance();
+                    return Err(self.error("E0103", "'_' in a number literal must be between two digits"));
+                }
+                after_digit = false;
+            } else if ch.is_digit(radix) {
+                s.push(ch);
+                after_digit = true;
+            } else if radix != 10 && ch.is_ascii_alphanumeric() {
+                self.advance();
+                let kind = if radix == 16 { "hexadecimal" } else { "binary" };
+                return Err(self.error("E0103", format!("invalid digit '{}' in {} literal", ch, kind)));
+            } else {
+                break;
+            }
+            self.advance();
+        }
+        Ok(())
+    }
+
+    /// Scans an int (`42`, `0xFF`, `0b1010`, `1_000`) or float (`3.14`, `1e-3`) literal.
+    fn number(&mut self) -> CompileResult<TokenType> {
+        let radix = match (self.peek(), self.peek_at(1)) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        let mut s = String::new();
+        if radix != 10 {
+            self.advance(); self.advance();
+            self.take_digits(&mut s, radix)?;
+            if s.is_empty() {
+                let prefix = if radix == 16 { "0x" } else { "0b" };
+                return Err(self.error("E0103", format!("expected digits after '{}'", prefix)));
+            }
+            return i64::from_str_radix(&s, radix).map(TokenType::Number).map_err(|_| self.out_of_range(false));
+        }
+
+        self.take_digits(&mut s, 10)?;
+        let mut is_float = false;
+        // Fraction: only when a digit follows, so `1.foo` stays a field access
+        if self.peek() == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            s.push(self.advance().unwrap());
+            self.take_digits(&mut s, 10)?;
+        }
+        // Exponent: 1e3, 2.5E-4
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek_at(1), Some('+' | '-')));
+            if self.peek_at(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                is_float = true;
+                for _ in 0..=sign { s.push(self.advance().unwrap()); }
+                self.take_digits(&mut s, 10)?;
+            }
+        }
+        if is_float {
+            match s.parse::<f64>() {
+                Ok(f) if f.is_finite() => Ok(TokenType::Float(f)),
+                _ => Err(self.out_of_range(true)),
+            }
+        } else {
+            s.parse().map(TokenType::Number).map_err(|_| self.out_of_range(false))
+        }
+    }
+
+    /// Scans string text up to the closing `"` or the next `${`. `continued` is set
+    /// when resuming after the `}` of an interpolation.
+    fn string_part(&mut self, continued: bool) -> CompileResult<TokenType> {
+        let mut s = String::new();
+        while let Some(ch) = self.peek() {
+            if ch == '"' { break; }
+            if ch == '$' && self.peek_at(1) == Some('{') {
+                self.advance(); self.advance();
+                self.interpolations.push((0, self.token_span()));
+                return Ok(if continued { TokenType::InterpMid(s) } else { TokenType::InterpStart(s) });
+            }
+            if ch == '\\' {
+                self.advance(); // consume '\'
+                if let Some(next) = self.peek() {
+                    match next {
+                        '"' => { s.push('"'); self.advance(); },
+                        'n' => { s.push('\n'); self.advance(); },
+                        'r' => { s.push('\r'); self.advance(); },
+                        't' => { s.push('\t'); self.advance(); },
+                        '\\' => { s.push('\\'); self.advance(); },
+                        '$' => { s.push('$'); self.advance(); },
+                        _ => s.push('\\'), // just keep it if unknown
+                    }
+                }
+            } else {
+                s.push(self.advance().unwrap()); 
+            }
+        }
+        if self.peek() == Some('"') { self.advance(); } else { return Err(self.error("E0102", "unterminated string literal").with_note("add a closing '\"'")); }
+        Ok(if continued { TokenType::InterpEnd(s) } else { TokenType::String(s) })
+    }
+
+    fn out_of_range(&self, is_float: bool) -> Diagnostic {
+        // Number literals are ASCII, so the token's byte length is its length in chars
+        let literal: String = self.input[self.pos - (self.byte_pos - self.start_byte)..self.pos].iter().collect();
+        let note = if is_float { "floats are 64-bit: the largest is about 1.8e308" } else { "integers are 64-bit: the largest is 9223372036854775807" };
+        self.error("E0103", format!("number literal '{}' is out of range", literal)).with_note(note)
+    }
     
-    fn create_token(&self, kind: TokenType) -> Token { 
-        Token { kind, line: self.line } 
+    fn create_token(&mut self, kind: TokenType) -> Token { 
+        let doc = if self.doc_lines.is_empty() { None } else { Some(self.doc_lines.join("\n")) };
+        self.doc_lines.clear();
+        Token { kind, span: self.token_span(), doc } 
+    }
+
+    /// Span from the start of the current token up to the cursor.
+    fn token_span(&self) -> Span {
+        Span {
+            file: self.file,
+            start: self.start_byte as u32, end: self.byte_pos as u32,
+            line: self.start_line as u32, column: self.start_column as u32,
+            end_line: self.line as u32, end_column: self.column as u32,
+        }
+    }
+
+    fn mark_token_start(&mut self) {
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.start_byte = self.byte_pos;
+    }
+
+    fn error(&self, code: &'static str, msg: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(code, msg).with_span(self.token_span())
     }
     
     fn advance(&mut self) -> Option<char> {
         let c = self.peek(); self.pos += 1;
-        if let Some('\n') = c { self.line += 1; } c
+        if let Some(ch) = c { self.byte_pos += ch.len_utf8(); }
+        if let Some('\n') = c { self.line += 1; self.column = 1; } else { self.column += 1; }
+        c
     }
     
-    fn skip_whitespace_and_comments(&mut self) {
+    fn skip_whitespace_and_comments(&mut self) -> CompileResult<()> {
         while let Some(c) = self.peek() {
             if c.is_whitespace() { self.advance(); }
-            else if c == '/' {
-                if self.input.get(self.pos+1) == Some(&'/') {
-                    while let Some(x) = self.peek() { if x == '\n' { break; } self.advance(); }
-                } else { break; }
+            else if c == '/' && self.peek_at(1) == Some('/') {
+                // `///` (but not `////`) documents the next declaration
+                let is_doc = self.peek_at(2) == Some('/') && self.peek_at(3) != Some('/');
+                let mut line = String::new();
+                while let Some(x) = self.peek() { if x == '\n' { break; } line.push(x); self.advance(); }
+                if is_doc {
+                    let text = &line[3..];
+                    self.doc_lines.push(text.strip_prefix(' ').unwrap_or(text).trim_end().to_string());
+                }
+            } else if c == '/' && self.peek_at(1) == Some('*') {
+                self.block_comment()?;
             } else { break; }
         }
+        Ok(())
+    }
+
+    /// Skips a `/* ... */` comment. Block comments nest, so code containing
+    /// one can itself be commented out.
+    fn block_comment(&mut self) -> CompileResult<()> {
+        self.mark_token_start();
+        self.advance(); self.advance();
+        let mut depth = 1;
+        while depth > 0 {
+            match (self.peek(), self.peek_at(1)) {
+                (Some('/'), Some('*')) => { self.advance(); self.advance(); depth += 1; }
+                (Some('*'), Some('/')) => { self.advance(); self.advance(); depth -= 1; }
+                (Some(_), _) => { self.advance(); }
+                (None, _) => {
+                    // Point at the opening `/*` only
+                    let mut span = self.token_span();
+                    (span.end, span.end_line, span.end_column) = (span.start + 2, span.line, span.column + 2);
+                    return Err(Diagnostic::error("E0104", "unterminated block comment").with_span(span)
+                        .with_note("add a closing '*/'"));
+                }
+            }
+        }
+        Ok(())
     }
     
-    pub fn tokenize(&mut self) -> Vec<Token> {
+    pub fn tokenize(&mut self) -> CompileResult<Vec<Token>> {
         let mut tokens = Vec::new();
-        while let Some(c) = { self.skip_whitespace_and_comments(); self.peek() } {
+        while let Some(c) = { self.skip_whitespace_and_comments()?; self.peek() } {
+            self.mark_token_start();
             let kind = match c {
                 '.' => { self.advance(); TokenType::Dot },
                 '=' => { 
                     self.advance(); 
-                    if self.peek() == Some('=') { self.advance(); TokenType::Eq } else { TokenType::Assign }
+                    match self.peek() {
+                        Some('=') => { self.advance(); TokenType::Eq },
+                        Some('>') => { self.advance(); TokenType::FatArrow },
+                        _ => TokenType::Assign,
+                    }
                 },
                 '!' => {
                     self.advance();
@@ -70,80 +267,113 @@ impl Lexer {
                 },
                 '<' => {
                     self.advance();
-                    if self.peek() == Some('=') { self.advance(); TokenType::Lte } else { TokenType::Lt }
+                    match self.peek() {
+                        Some('=') => { self.advance(); TokenType::Lte },
+                        Some('<') => { self.advance(); TokenType::Shl },
+                        _ => TokenType::Lt,
+                    }
                 },
                 '>' => {
                     self.advance();
-                    if self.peek() == Some('=') { self.advance(); TokenType::Gte } else { TokenType::Gt }
+                    match self.peek() {
+                        Some('=') => { self.advance(); TokenType::Gte },
+                        Some('>') => { self.advance(); TokenType::Shr },
+                        _ => TokenType::Gt,
+                    }
+                },
+                '+' => {
+                    self.advance();
+                    match self.peek() {
+                        Some('=') => { self.advance(); TokenType::PlusAssign },
+                        Some('+') => { self.advance(); TokenType::Increment },
+                        _ => TokenType::Plus,
+                    }
+                },
+                '-' => {
+                    self.advance();
+                    match self.peek() {
+                        Some('>') => { self.advance(); TokenType::Arrow },
+                        Some('=') => { self.advance(); TokenType::MinusAssign },
+                        Some('-') => { self.advance(); TokenType::Decrement },
+                        _ => TokenType::Minus,
+                    }
+                },
+                '*' | '/' | '%' => {
+                    self.advance();
+                    let assign = self.peek() == Some('=');
+                    if assign { self.advance(); }
+                    match (c, assign) {
+                        ('*', false) => TokenType::Mul,
+                        ('*', true) => TokenType::MulAssign,
+                        ('/', false) => TokenType::Div,
+                        ('/', true) => TokenType::DivAssign,
+                        (_, false) => TokenType::Mod,
+                        (_, true) => TokenType::ModAssign,
+                    }
                 },
-                '+' => { self.advance(); TokenType::Plus },
-                '-' => { self.advance(); TokenType::Minus },
-                '*' => { self.advance(); TokenType::Mul },
-                '/' => { self.advance(); TokenType::Div },
+                '^' => { self.advance(); TokenType::BitXor },
                 '&' => {
                     self.advance();
-                    if self.peek() == Some('&') { self.advance(); TokenType::And } else { panic!("Line {}: Expected '&' after '&'", self.line) }
+                    if self.peek() == Some('&') { self.advance(); TokenType::And } else { TokenType::BitAnd }
                 },
                 '|' => {
                     self.advance();
-                    if self.peek() == Some('|') { self.advance(); TokenType::Or } else { panic!("Line {}: Expected '|' after '|'", self.line) }
+                    if self.peek() == Some('|') { self.advance(); TokenType::Or } else { TokenType::BitOr }
                 },
                 '(' => { self.advance(); TokenType::LParen },
                 ')' => { self.advance(); TokenType::RParen },
-                '{' => { self.advance(); TokenType::LBrace },
-                '}' => { self.advance(); TokenType::RBrace },
+                '{' => {
+                    self.advance();
+                    if let Some((depth, _)) = self.interpolations.last_mut() { *depth += 1; }
+                    TokenType::LBrace
+                },
+                '}' => {
+                    self.advance();
+                    match self.interpolations.last_mut() {
+                        // Closes a `${`: the string continues
+                        Some((0, _)) => { self.interpolations.pop(); self.string_part(true)? },
+                        Some((depth, _)) => { *depth -= 1; TokenType::RBrace },
+                        None => TokenType::RBrace,
+                    }
+                },
                 '[' => { self.advance(); TokenType::LBracket },
                 ']' => { self.advance(); TokenType::RBracket },
                 ',' => { self.advance(); TokenType::Comma },
                 ';' => { self.advance(); TokenType::Semicolon },
-                '"' => {
-                    self.advance(); let mut s = String::new();
-                    while let Some(ch) = self.peek() { 
-                        if ch == '"' { break; } 
-                        if ch == '\\' {
-                            self.advance(); // consume '\'
-                            if let Some(next) = self.peek() {
-                                match next {
-                                    '"' => { s.push('"'); self.advance(); },
-                                    'n' => { s.push('\n'); self.advance(); },
-                                    'r' => { s.push('\r'); self.advance(); },
-                                    't' => { s.push('\t'); self.advance(); },
-                                    '\\' => { s.push('\\'); self.advance(); },
-                                    _ => s.push('\\'), // just keep it if unknown
-                                }
-                            }
-                        } else {
-                            s.push(self.advance().unwrap()); 
-                        }
-                    }
-                    if self.peek() == Some('"') { self.advance(); } else { panic!("Run-away string literal!"); }
-                    TokenType::String(s)
-                },
-                '0'..='9' => {
-                    let mut s = String::new();
-                    while let Some(ch) = self.peek() { if ch.is_digit(10) { s.push(self.advance().unwrap()); } else { break; } }
-                    TokenType::Number(s.parse().unwrap())
-                },
+                ':' => { self.advance(); TokenType::Colon },
+                '"' => { self.advance(); self.string_part(false)? },
+                '0'..='9' => self.number()?,
                 'a'..='z'|'A'..='Z'|'_' => {
                     let mut s = String::new();
                     while let Some(ch) = self.peek() { if ch.is_alphanumeric()||ch=='_' { s.push(self.advance().unwrap()); } else { break; } }
                     match s.as_str() { 
-                        "var"=>TokenType::Var, "print"=>TokenType::Print, 
+                        "var"=>TokenType::Var, "const"=>TokenType::Const, "print"=>TokenType::Print, 
                         "if"=>TokenType::If, "else"=>TokenType::Else, 
                         "while"=>TokenType::While, "for"=>TokenType::For,
                         "foreach"=>TokenType::Foreach, "in"=>TokenType::In,
+                        "break"=>TokenType::Break, "continue"=>TokenType::Continue,
                         "func"=>TokenType::Func, "return"=>TokenType::Return,
                         "import"=>TokenType::Import, "from"=>TokenType::From,
                         "class"=>TokenType::Class, "new"=>TokenType::New,
+                        "extends"=>TokenType::Extends, "super"=>TokenType::Super,
+                        "interface"=>TokenType::Interface, "implements"=>TokenType::Implements,
                         "true"=>TokenType::Bool(true), "false"=>TokenType::Bool(false),
                         _=>TokenType::Id(s) 
                     }
                 },
-                _ => panic!("Unknown character: {}", c),
+                _ => {
+                    self.advance();
+                    return Err(self.error("E0100", format!("unknown character '{}'", c)));
+                }
             };
             tokens.push(self.create_token(kind));
         }
+        if let Some((_, span)) = self.interpolations.first() {
+            return Err(Diagnostic::error("E0102", "unterminated string interpolation").with_span(*span)
+                .with_note("close the '${' with a '}'"));
+        }
+        self.mark_token_start();
         tokens.push(self.create_token(TokenType::EOF));
-        tokens
+        Ok(tokens)
     }
 }
@@ -1,3 +1,8 @@
-﻿pub mod lexer;
+﻿pub mod diagnostic;
+pub mod source;
+pub mod types;
+pub mod lexer;
 pub mod parser;
+pub mod sema;
 pub mod compiler;
+pub mod docgen;
@@ -1,48 +1,113 @@
+use crate::compiler::diagnostic::{CompileResult, Diagnostic};
 use crate::compiler::lexer::{Token, TokenType, Lexer}; // Updated import to include Lexer
+use crate::compiler::source::{SourceMap, Span};
+use crate::compiler::types::VarType;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
-pub enum Expr {
-    Number(i32), 
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+    pub ty: Option<VarType>, // Filled in by sema
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Number(i64), 
+    Float(f64),
     String(String), 
     Bool(bool),
     Variable(String),
     ArrayLiteral(Vec<Expr>), 
-    IndexAccess(String, Box<Expr>), 
+    MapLiteral(Vec<(Expr, Expr)>), // {"key": value, ...}
+    Interpolated(Vec<Expr>), // "Hello, ${name}!": text parts are String exprs
+    IndexAccess(Box<Expr>, Box<Expr>), // array[index]
     Call(String, Vec<Expr>), 
     Binary(Box<Expr>, TokenType, Box<Expr>),
     Unary(TokenType, Box<Expr>),
-    New(String), // new ClassName()
+    New(String, Vec<Expr>), // new ClassName(args), runs init(args)
+    SuperCall(String, Vec<Expr>), // super.method(args), the base class's implementation
     Get(Box<Expr>, String), // obj.field
     Set(Box<Expr>, String, Box<Expr>), // obj.field = val
     MethodCall(Box<Expr>, String, Vec<Expr>), // obj.method(args)
     NamespacedCall(Vec<String>, Vec<Expr>), // std.net.api_listen(args)
+    CallValue(Box<Expr>, Vec<Expr>), // f(args)(args), callbacks[0](args): calls a function value
+    Lambda(Box<Lambda>), // func(a, b: int) -> type { ... }, a => expr or (a, b) => { ... }
+}
+
+/// A parameter or field name with its optional type annotation (`name: int`).
+pub type TypedName = (String, Option<VarType>);
+
+/// An anonymous function.
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub params: Vec<TypedName>,
+    pub ret: Option<VarType>,
+    pub body: Vec<Stmt>,
+    pub captures: Vec<String>, // Variables of the enclosing functions it uses (filled in by sema)
+}
+
+/// A `var name: type;` field of a class.
+#[derive(Debug, Clone)]
+pub struct FieldDecl {
+    pub name: String,
+    pub ty: Option<VarType>,
+    pub doc: Option<String>, // From `///` comments
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+    pub doc: Option<String>, // `///` comments on a function, method, class, interface or constant
 }
 
 #[derive(Debug, Clone)]
-pub enum Stmt {
-    VarDecl(String, Expr), 
+pub enum StmtKind {
+    VarDecl(String, Option<VarType>, Expr), // var name: type = value;
+    ConstDecl(String, Option<VarType>, Expr), // const NAME: type = value; (top level only)
     Assignment(String, Expr), 
+    IndexAssignment(Expr, Expr, Expr), // array[index] = value
+    CompoundAssignment(Expr, TokenType, Expr), // obj.field += value, array[index] -= value (`++` adds 1)
     Print(Expr),
     IfStmt(Expr, Vec<Stmt>, Option<Vec<Stmt>>), 
-    WhileStmt(Expr, Vec<Stmt>),
+    WhileStmt(Expr, Vec<Stmt>, Vec<Stmt>), // while (cond) { body }, then the `for` step (also run on continue)
+    Foreach(String, Expr, Vec<Stmt>), // foreach (item in collection) { ... }
     BlockStmt(Vec<Stmt>), 
-    FuncDecl(String, Vec<String>, Vec<Stmt>), 
-    ClassDecl(String, Vec<String>, Vec<Stmt>), // class Name { var f1; methods... }
+    Module(Vec<Stmt>), // The statements of an imported file, which share the importer's top level
+    FuncDecl(String, Vec<TypedName>, Option<VarType>, Vec<Stmt>), // func name(a: type) -> type { ... }
+    ClassDecl(String, Option<String>, Vec<String>, Vec<FieldDecl>, Vec<Stmt>), // class Name extends Base implements I1, I2 { var f1: type; methods... }
+    InterfaceDecl(String, Vec<Stmt>), // interface Name { func m(a, b: int) -> int; } (methods are bodiless FuncDecls)
     ReturnStmt(Option<Expr>),
+    Break,
+    Continue,
     ExprStmt(Expr), 
     ImportStmt(String), // import "std"
 }
 
-pub struct Parser { 
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span, ty: None }
+    }
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span, doc: None }
+    }
+}
+
+pub struct Parser<'a> { 
     tokens: Vec<Token>, 
     pos: usize,
-    base_path: PathBuf
+    base_path: PathBuf,
+    sources: &'a mut SourceMap, // Imported files are registered here to keep their own FileId
+    errors: Vec<Diagnostic>, // Syntax errors recovered from so far
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>, base_path: PathBuf) -> Self { 
-        Self { tokens, pos: 0, base_path } 
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, base_path: PathBuf, sources: &'a mut SourceMap) -> Self { 
+        Self { tokens, pos: 0, base_path, sources, errors: Vec::new() } 
     }
     
     fn peek(&self) -> &Token { &self.tokens[self.pos] }
@@ -52,17 +117,54 @@ impl Parser {
         if t.kind != TokenType::EOF { self.pos += 1; } 
         t 
     }
+
+    /// Span of the most recently consumed token.
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    /// Span from `start` up to and including the most recently consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev_span())
+    }
     
-    fn consume(&mut self, expected: TokenType, msg: &str) {
-        let t = self.advance(); 
-        if std::mem::discriminant(&t.kind) != std::mem::discriminant(&expected) { 
-            panic!("{}", msg); 
+    /// Builds an error diagnostic pointing at the given token.
+    fn error_at(&self, token: &Token, code: &'static str, msg: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(code, msg).with_span(token.span)
+    }
+
+    /// Builds an "unexpected token" error for the next token.
+    fn unexpected(&self, msg: &str) -> Diagnostic {
+        let t = self.peek();
+        self.error_at(t, "E0200", format!("{}, found {}", msg, describe(&t.kind)))
+    }
+    
+    fn consume(&mut self, expected: TokenType, msg: &str) -> CompileResult<Token> {
+        if std::mem::discriminant(&self.peek().kind) != std::mem::discriminant(&expected) { 
+            let mut err = self.unexpected(msg);
+            // A missing ';' is best reported right after the previous token, like rustc does
+            if expected == TokenType::Semicolon && self.pos > 0 {
+                err.span = Some(self.prev_span().after());
+            }
+            return Err(err);
+        }
+        Ok(self.advance())
+    }
+
+    /// Consumes an identifier token and returns its name.
+    fn consume_id(&mut self, msg: &str) -> CompileResult<String> {
+        if let TokenType::Id(name) = &self.peek().kind {
+            let name = name.clone();
+            self.advance();
+            Ok(name)
+        } else {
+            Err(self.unexpected(msg))
         }
     }
 
-    fn import_file(&self, path: &str) -> Stmt {
+    fn import_file(&mut self, path: &str, path_token: &Token) -> CompileResult<StmtKind> {
         if path == "system" {
-            return Stmt::ImportStmt(path.to_string());
+            return Ok(StmtKind::ImportStmt(path.to_string()));
         }
 
         let mut full_path = self.base_path.clone();
@@ -71,400 +173,821 @@ impl Parser {
         // New base path is the directory of the imported file
         let new_base = full_path.parent().unwrap_or(Path::new(".")).to_path_buf();
         
-        let content = std::fs::read_to_string(&full_path).expect(&format!("Could not read imported file: {:?}", full_path)); let mut lexer = Lexer::new(content);
-        let mut parser = Parser::new(lexer.tokenize(), new_base);
-        let imported_stmts = parser.parse();
-        Stmt::BlockStmt(imported_stmts)
+        let content = std::fs::read_to_string(&full_path).map_err(|e| {
+            self.error_at(path_token, "E0210", format!("could not read imported file '{}'", path))
+                .with_note(format!("{:?}: {}", full_path, e))
+        })?;
+        let file_id = self.sources.add(full_path, content.clone());
+        let mut lexer = Lexer::new(content, file_id);
+        let tokens = lexer.tokenize()?;
+        let mut parser = Parser::new(tokens, new_base, self.sources);
+        let (imported_stmts, errors) = parser.parse();
+        self.errors.extend(errors);
+        Ok(StmtKind::Module(imported_stmts))
     }
     
     // Primary expressions: Number, String, Variable, ArrayLiteral, Paren, New
-    fn parse_primary(&mut self) -> Expr {
+    fn parse_primary(&mut self) -> CompileResult<Expr> {
         let t = self.peek().clone();
+        let start = t.span;
         
         // 1. Atom Parsing
-        let mut expr = match t.kind {
-            TokenType::Number(n) => { self.advance(); Expr::Number(n) },
-            TokenType::String(s) => { self.advance(); Expr::String(s) },
-            TokenType::Bool(b) => { self.advance(); Expr::Bool(b) },
-            TokenType::Id(n) => { self.advance(); Expr::Variable(n) },
-            TokenType::Not => { self.advance(); Expr::Unary(TokenType::Not, Box::new(self.parse_primary())) },
+        let kind = match t.kind {
+            TokenType::Number(n) => { self.advance(); ExprKind::Number(n) },
+            TokenType::Float(f) => { self.advance(); ExprKind::Float(f) },
+            TokenType::String(s) => { self.advance(); ExprKind::String(s) },
+            TokenType::InterpStart(text) => { self.advance(); ExprKind::Interpolated(self.parse_interpolation(text)?) },
+            TokenType::Bool(b) => { self.advance(); ExprKind::Bool(b) },
+            TokenType::Id(n) if self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenType::FatArrow) => {
+                self.advance();
+                self.advance();
+                self.parse_arrow_body(vec![(n, None)])?
+            },
+            TokenType::Id(n) => { self.advance(); ExprKind::Variable(n) },
+            TokenType::Func => { // Anonymous function: func(a, b) -> int { ... }
+                self.advance();
+                let params = self.parse_params()?;
+                let ret = self.parse_return_type()?;
+                let body = self.parse_block()?;
+                lambda(params, ret, body)
+            },
+            TokenType::LParen if self.at_arrow_params() => {
+                let params = self.parse_params()?;
+                self.consume(TokenType::FatArrow, "expected '=>' after lambda parameters")?;
+                self.parse_arrow_body(params)?
+            },
             TokenType::New => {
                  self.advance();
-                 if let TokenType::Id(class_name) = self.advance().kind {
-                     self.consume(TokenType::LParen, "Expected '('");
-                     self.consume(TokenType::RParen, "Expected ')'");
-                     Expr::New(class_name)
-                 } else { panic!("Expected class name after 'new'"); }
+                 let class_name = self.consume_id("expected class name after 'new'")?;
+                 self.consume(TokenType::LParen, "expected '(' after class name")?;
+                 ExprKind::New(class_name, self.parse_args()?)
+            },
+            TokenType::Super => {
+                 self.advance();
+                 self.consume(TokenType::Dot, "expected '.' after 'super'")?;
+                 let method = self.consume_id("expected a method name after 'super.'")?;
+                 self.consume(TokenType::LParen, "expected '(' after method name")?;
+                 ExprKind::SuperCall(method, self.parse_args()?)
             },
             TokenType::LBracket => { // Array Literal [1, 2, 3]
                 self.advance();
                 let mut elements = Vec::new();
                 if self.peek().kind != TokenType::RBracket {
-                    elements.push(self.parse_expr());
+                    elements.push(self.parse_expr()?);
+                    while self.peek().kind == TokenType::Comma {
+                        self.advance();
+                        elements.push(self.parse_expr()?);
+                    }
+                }
+                self.consume(TokenType::RBracket, "expected ']' after array elements")?;
+                ExprKind::ArrayLiteral(elements)
+            },
+            TokenType::LBrace => { // Map Literal {"a": 1, "b": 2}
+                self.advance();
+                let mut entries = Vec::new();
+                if self.peek().kind != TokenType::RBrace {
+                    entries.push(self.parse_map_entry()?);
                     while self.peek().kind == TokenType::Comma {
                         self.advance();
-                        elements.push(self.parse_expr());
+                        entries.push(self.parse_map_entry()?);
                     }
                 }
-                self.consume(TokenType::RBracket, "Expected ']'");
-                Expr::ArrayLiteral(elements)
+                self.consume(TokenType::RBrace, "expected '}' after map entries")?;
+                ExprKind::MapLiteral(entries)
             },
             TokenType::LParen => {
                 self.advance();
-                let e = self.parse_expr(); 
-                self.consume(TokenType::RParen, "Missing ')'"); 
-                e 
+                let e = self.parse_expr()?; 
+                self.consume(TokenType::RParen, "expected ')'")?; 
+                e.kind
             },
-            _ => panic!("Unexpected token: {:?}", t),
+            _ => return Err(self.unexpected("expected an expression")),
         };
+        let mut expr = Expr::new(kind, self.span_from(start));
 
         // 2. Postfix Loop (Call (), Index [], Member Access .)
         loop {
             match self.peek().kind {
                 TokenType::LParen => {
                     // Call
-                    self.advance(); 
-                    let mut args = Vec::new();
-                    if self.peek().kind != TokenType::RParen {
-                        args.push(self.parse_expr());
-                        while self.peek().kind == TokenType::Comma {
-                             self.advance();
-                             args.push(self.parse_expr());
-                        }
-                    }
-                    self.consume(TokenType::RParen, "Expected ')'");
+                    self.advance();
+                    let args = self.parse_args()?;
                     
-                    if let Expr::Variable(name) = expr {
-                        expr = Expr::Call(name, args);
-                    } else if let Expr::Get(obj, method_name) = expr {
+                    let span = self.span_from(start);
+                    let namespace = self.resolve_namespace_chain(&expr);
+                    if let ExprKind::Variable(name) = expr.kind {
+                        expr = Expr::new(ExprKind::Call(name, args), span);
+                    } else if let ExprKind::Get(obj, method_name) = expr.kind {
                         // Support obj.method()
-                        if let Some(parts) = self.resolve_namespace_chain(&Expr::Get(obj.clone(), method_name.clone())) {
-                             if parts[0] == "system" {
-                                 expr = Expr::NamespacedCall(parts, args);
-                             } else {
-                                 expr = Expr::MethodCall(obj, method_name, args);
-                             }
+                        if let Some(parts) = namespace.filter(|parts| parts[0] == "system") {
+                             expr = Expr::new(ExprKind::NamespacedCall(parts, args), span);
                         } else {
-                             expr = Expr::MethodCall(obj, method_name, args);
+                             expr = Expr::new(ExprKind::MethodCall(obj, method_name, args), span);
                         }
                     } else {
-                        // For now we only support calling named functions directly, 
-                        // but technically `obj.method()` could be supported via this or similar.
-                        // Since we don't have first-class func pointers yet, let's restrict or adapt.
-                        // Actually, if we add methods later, `Expr::Get` might need to be handled here.
-                        // For this iteration: just wrap it. 
-                        // Note: Compiler needs to handle Call on non-Variable if we want `(get_func())()`
-                        panic!("Function call only supported on identifiers or field access (methods) for now. Got: {:?}", expr);
+                        // Any other callee is a function value: `makeAdder(1)(2)`, `handlers[0](x)`
+                        expr = Expr::new(ExprKind::CallValue(Box::new(expr), args), span);
                     }
                 },
                 TokenType::LBracket => {
                     // Index
+                    // Any expression can be indexed: `items[0]`, `this.items[i]`, `getUsers()[0]`, `matrix[i][j]`
                     self.advance();
-                    let index = self.parse_expr();
-                    self.consume(TokenType::RBracket, "Expected ']'");
-                    if let Expr::Variable(name) = expr {
-                         // Simplify: IndexAccess logic in compiler assumes variable name.
-                         // But `arr[0][1]` should work. 
-                         // Current Compiler `IndexAccess` takes `String` name.
-                         // We need to upgrade IndexAccess to take `Box<Expr>` target to support `getArr()[0]`.
-                         // For now, let's keep the limitation: `IndexAccess(String, Box<Expr>)`.
-                         expr = Expr::IndexAccess(name, Box::new(index));
-                    } else {
-                         // If we want to support `expression[index]`, we need to change Expr definition.
-                         // For this request, let's stick to simple `obj.field`.
-                         panic!("Indexing only supported on variables for now");
-                    }
+                    let index = self.parse_expr()?;
+                    self.consume(TokenType::RBracket, "expected ']' after index")?;
+                    expr = Expr::new(ExprKind::IndexAccess(Box::new(expr), Box::new(index)), self.span_from(start));
                 },
                     TokenType::Dot => { // Member Access (Dot)
                     self.advance();
-                    let field = match self.advance().kind {
-                        TokenType::Id(s) => s,
-                        TokenType::Print => "print".to_string(),
-                        _ => panic!("Expected identifier or keyword after '.' at line {:?}", self.peek().line),
+                    let field = match self.peek().kind.clone() {
+                        TokenType::Id(s) => { self.advance(); s },
+                        TokenType::Print => { self.advance(); "print".to_string() },
+                        _ => return Err(self.unexpected("expected field or method name after '.'")),
                     };
-                    expr = Expr::Get(Box::new(expr), field); }
+                    expr = Expr::new(ExprKind::Get(Box::new(expr), field), self.span_from(start)); }
                 _ => break,
             }
         }
         
-        expr
+        Ok(expr)
+    }
+
+    /// Whether the '(' at the current position opens the parameter list of `(a, b) => expr`.
+    fn at_arrow_params(&self) -> bool {
+        let mut depth = 0;
+        for (i, t) in self.tokens.iter().enumerate().skip(self.pos) {
+            match t.kind {
+                TokenType::LParen => depth += 1,
+                TokenType::RParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.tokens.get(i + 1).is_some_and(|t| t.kind == TokenType::FatArrow);
+                    }
+                }
+                TokenType::Semicolon | TokenType::EOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// The body after `=>`: a block, or an expression the lambda returns
+    /// (a map literal must be parenthesized to not be read as a block).
+    fn parse_arrow_body(&mut self, params: Vec<TypedName>) -> CompileResult<ExprKind> {
+        if self.peek().kind == TokenType::LBrace {
+            return Ok(lambda(params, None, self.parse_block()?));
+        }
+        let body = self.parse_expr()?;
+        let span = body.span;
+        Ok(lambda(params, None, vec![Stmt::new(StmtKind::ReturnStmt(Some(body)), span)]))
+    }
+
+    /// Comma-separated arguments after an opening '(', up to and including the ')'.
+    fn parse_args(&mut self) -> CompileResult<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.peek().kind != TokenType::RParen {
+            args.push(self.parse_expr()?);
+            while self.peek().kind == TokenType::Comma {
+                 self.advance();
+                 args.push(self.parse_expr()?);
+            }
+        }
+        self.consume(TokenType::RParen, "expected ')' after arguments")?;
+        Ok(args)
     }
     
-    fn parse_term(&mut self) -> Expr {
-        let mut node = self.parse_primary();
-        while matches!(self.peek().kind, TokenType::Mul|TokenType::Div) {
+    // Prefix operators: !x, -x
+    fn parse_unary(&mut self) -> CompileResult<Expr> {
+        if matches!(self.peek().kind, TokenType::Not|TokenType::Minus) {
+            let op = self.advance();
+            let operand = self.parse_unary()?;
+            let span = op.span.to(operand.span);
+            return Ok(Expr::new(ExprKind::Unary(op.kind, Box::new(operand)), span));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_term(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_unary()?;
+        while matches!(self.peek().kind, TokenType::Mul|TokenType::Div|TokenType::Mod) {
             let op = self.advance().kind.clone();
-            node = Expr::Binary(Box::new(node), op, Box::new(self.parse_primary()));
+            node = binary(node, op, self.parse_unary()?);
         }
-        node
+        Ok(node)
     }
     
-    fn parse_arithmetic(&mut self) -> Expr {
-        let mut node = self.parse_term();
+    fn parse_arithmetic(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_term()?;
         while matches!(self.peek().kind, TokenType::Plus|TokenType::Minus) {
             let op = self.advance().kind.clone();
-            node = Expr::Binary(Box::new(node), op, Box::new(self.parse_term()));
+            node = binary(node, op, self.parse_term()?);
         }
-        node
+        Ok(node)
     }
 
-    fn parse_comparison(&mut self) -> Expr {
-        let mut node = self.parse_arithmetic();
+    fn parse_shift(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_arithmetic()?;
+        while matches!(self.peek().kind, TokenType::Shl|TokenType::Shr) {
+            let op = self.advance().kind.clone();
+            node = binary(node, op, self.parse_arithmetic()?);
+        }
+        Ok(node)
+    }
+
+    // Bitwise operators bind tighter than comparisons, so `x & 1 == 0` is `(x & 1) == 0`
+    fn parse_bit_and(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_shift()?;
+        while self.peek().kind == TokenType::BitAnd {
+            let op = self.advance().kind.clone();
+            node = binary(node, op, self.parse_shift()?);
+        }
+        Ok(node)
+    }
+
+    fn parse_bit_xor(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_bit_and()?;
+        while self.peek().kind == TokenType::BitXor {
+            let op = self.advance().kind.clone();
+            node = binary(node, op, self.parse_bit_and()?);
+        }
+        Ok(node)
+    }
+
+    fn parse_bit_or(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_bit_xor()?;
+        while self.peek().kind == TokenType::BitOr {
+            let op = self.advance().kind.clone();
+            node = binary(node, op, self.parse_bit_xor()?);
+        }
+        Ok(node)
+    }
+
+    fn parse_comparison(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_bit_or()?;
         while matches!(self.peek().kind, TokenType::Eq|TokenType::Neq|TokenType::Lt|TokenType::Gt|TokenType::Lte|TokenType::Gte) {
              let op = self.advance().kind.clone();
-             node = Expr::Binary(Box::new(node), op, Box::new(self.parse_arithmetic()));
+             node = binary(node, op, self.parse_bit_or()?);
         }
-        node
+        Ok(node)
     }
 
-    fn parse_and(&mut self) -> Expr {
-        let mut node = self.parse_comparison();
+    fn parse_and(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_comparison()?;
         while self.peek().kind == TokenType::And {
             let op = self.advance().kind.clone();
-            node = Expr::Binary(Box::new(node), op, Box::new(self.parse_comparison()));
+            node = binary(node, op, self.parse_comparison()?);
         }
-        node
+        Ok(node)
     }
 
-    fn parse_or(&mut self) -> Expr {
-        let mut node = self.parse_and();
+    fn parse_or(&mut self) -> CompileResult<Expr> {
+        let mut node = self.parse_and()?;
         while self.peek().kind == TokenType::Or {
             let op = self.advance().kind.clone();
-            node = Expr::Binary(Box::new(node), op, Box::new(self.parse_and()));
+            node = binary(node, op, self.parse_and()?);
         }
-        node
+        Ok(node)
     }
 
     fn resolve_namespace_chain(&self, expr: &Expr) -> Option<Vec<String>> {
-        match expr {
-            Expr::Variable(n) => Some(vec![n.clone()]),
-            Expr::Get(inner, field) => {
-                if let Some(mut parts) = self.resolve_namespace_chain(inner) {
-                    parts.push(field.clone());
-                    Some(parts)
-                } else { None }
+        match &expr.kind {
+            ExprKind::Variable(n) => Some(vec![n.clone()]),
+            ExprKind::Get(inner, field) => {
+                let mut parts = self.resolve_namespace_chain(inner)?;
+                parts.push(field.clone());
+                Some(parts)
             },
             _ => None
         }
     }
 
-    fn parse_expr(&mut self) -> Expr {
+    fn parse_expr(&mut self) -> CompileResult<Expr> {
         self.parse_or()
     }
     
-    fn parse_block(&mut self) -> Vec<Stmt> {
-        self.consume(TokenType::LBrace, "Expected '{'");
+    fn parse_block(&mut self) -> CompileResult<Vec<Stmt>> {
+        self.consume(TokenType::LBrace, "expected '{'")?;
         let mut stmts = Vec::new();
         while self.peek().kind != TokenType::RBrace && self.peek().kind != TokenType::EOF {
-            stmts.push(self.parse_stmt());
+            if let Some(stmt) = self.parse_stmt_recovering() {
+                stmts.push(stmt);
+            }
+        }
+        self.consume(TokenType::RBrace, "expected '}' to close the block")?;
+        Ok(stmts)
+    }
+
+    /// Parses one statement. On a syntax error the error is recorded, the parser
+    /// skips ahead to the next statement boundary and None is returned.
+    fn parse_stmt_recovering(&mut self) -> Option<Stmt> {
+        let start = self.pos;
+        match self.parse_stmt() {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+
+    /// Skips tokens until a likely statement boundary: just past a ';', or right
+    /// before a '}', 'func', 'class' or 'var'. Blocks opened while skipping are
+    /// skipped whole so their '}' doesn't close the enclosing one. Always makes
+    /// progress past `start`.
+    fn synchronize(&mut self, start: usize) {
+        if self.pos == start { self.advance(); }
+        let mut depth = 0;
+        while self.peek().kind != TokenType::EOF {
+            match self.peek().kind {
+                TokenType::LBrace => depth += 1,
+                TokenType::RBrace if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 { self.advance(); return; }
+                }
+                TokenType::Semicolon if depth == 0 => { self.advance(); return; }
+                TokenType::RBrace | TokenType::Func | TokenType::Class | TokenType::Interface | TokenType::Var | TokenType::Const if depth == 0 => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses `(a, b: int, c)` parameter lists of functions and methods.
+    fn parse_params(&mut self) -> CompileResult<Vec<TypedName>> {
+        self.consume(TokenType::LParen, "expected '(' after function name")?;
+        let mut args = Vec::new();
+        if self.peek().kind != TokenType::RParen {
+            args.push(self.parse_typed_name("expected parameter name")?);
+            while self.peek().kind == TokenType::Comma {
+                self.advance();
+                args.push(self.parse_typed_name("expected parameter name")?);
+            }
+        }
+        self.consume(TokenType::RParen, "expected ')' after parameters")?;
+        Ok(args)
+    }
+
+    /// Parses a name followed by an optional `: type` annotation.
+    fn parse_typed_name(&mut self, msg: &str) -> CompileResult<TypedName> {
+        let name = self.consume_id(msg)?;
+        let ty = if self.peek().kind == TokenType::Colon {
+            self.advance();
+            Some(self.parse_type()?)
+        } else { None };
+        Ok((name, ty))
+    }
+
+    /// Parses the optional `-> type` return annotation of a function.
+    fn parse_return_type(&mut self) -> CompileResult<Option<VarType>> {
+        if self.peek().kind != TokenType::Arrow { return Ok(None); }
+        self.advance();
+        Ok(Some(self.parse_type()?))
+    }
+
+    /// Parses the rest of an interpolated string after its `InterpStart` token.
+    fn parse_interpolation(&mut self, first: String) -> CompileResult<Vec<Expr>> {
+        let mut parts = Vec::new();
+        let mut text = first;
+        loop {
+            if !text.is_empty() { parts.push(Expr::new(ExprKind::String(text), self.prev_span())); }
+            parts.push(self.parse_expr()?);
+            match self.peek().kind.clone() {
+                TokenType::InterpMid(next) => { self.advance(); text = next; }
+                TokenType::InterpEnd(last) => {
+                    self.advance();
+                    if !last.is_empty() { parts.push(Expr::new(ExprKind::String(last), self.prev_span())); }
+                    return Ok(parts);
+                }
+                _ => return Err(self.unexpected("expected '}' to close the interpolation")),
+            }
         }
-        self.consume(TokenType::RBrace, "Expected '}'");
-        stmts
     }
 
-    fn parse_stmt(&mut self) -> Stmt {
+    /// Parses one `key: value` entry of a map literal.
+    fn parse_map_entry(&mut self) -> CompileResult<(Expr, Expr)> {
+        let key = self.parse_expr()?;
+        self.consume(TokenType::Colon, "expected ':' after map key")?;
+        let value = self.parse_expr()?;
+        Ok((key, value))
+    }
+
+    /// Parses a type: `int`, `float`, `string`, `bool`, `void`, a class name, `[type]`, `{key: value}`
+    /// or `func(types) -> type`.
+    fn parse_type(&mut self) -> CompileResult<VarType> {
+        let t = self.peek().clone();
+        match t.kind {
+            TokenType::Id(name) => {
+                self.advance();
+                Ok(match name.as_str() {
+                    "int" => VarType::Int,
+                    "string" => VarType::Str,
+                    "bool" => VarType::Bool,
+                    "float" => VarType::Float,
+                    "void" => VarType::Void,
+                    _ => VarType::Instance(name),
+                })
+            }
+            TokenType::LBracket => {
+                self.advance();
+                let elem = self.parse_type()?;
+                if self.peek().kind == TokenType::Semicolon {
+                    return Err(self.unexpected("expected ']' after the element type")
+                        .with_note(format!("arrays grow as needed, so they have no length in their type: write '[{}]'", elem)));
+                }
+                self.consume(TokenType::RBracket, "expected ']' after the element type")?;
+                Ok(VarType::Array(Box::new(elem)))
+            }
+            TokenType::LBrace => {
+                self.advance();
+                let key = self.parse_type()?;
+                self.consume(TokenType::Colon, "expected ':' after the key type")?;
+                let value = self.parse_type()?;
+                self.consume(TokenType::RBrace, "expected '}' after the value type")?;
+                Ok(VarType::Map(Box::new(key), Box::new(value)))
+            }
+            TokenType::Func => {
+                self.advance();
+                self.consume(TokenType::LParen, "expected '(' after 'func'")?;
+                let mut params = Vec::new();
+                if self.peek().kind != TokenType::RParen {
+                    params.push(self.parse_type()?);
+                    while self.peek().kind == TokenType::Comma {
+                        self.advance();
+                        params.push(self.parse_type()?);
+                    }
+                }
+                self.consume(TokenType::RParen, "expected ')' after the parameter types")?;
+                let ret = self.parse_return_type()?.unwrap_or(VarType::Void);
+                Ok(VarType::Function(params, Box::new(ret)))
+            }
+            _ => Err(self.unexpected("expected a type")),
+        }
+    }
+
+    fn parse_stmt(&mut self) -> CompileResult<Stmt> {
+        let start = self.peek().span;
+        let doc = self.peek().doc.clone();
+        let kind = self.parse_stmt_kind()?;
+        let mut stmt = Stmt::new(kind, self.span_from(start));
+        if matches!(stmt.kind, StmtKind::FuncDecl(..) | StmtKind::ClassDecl(..) | StmtKind::InterfaceDecl(..) | StmtKind::ConstDecl(..)) { stmt.doc = doc; }
+        Ok(stmt)
+    }
+
+    fn parse_stmt_kind(&mut self) -> CompileResult<StmtKind> {
         let t = self.peek().clone();
         match t.kind {
             TokenType::Class => {
                 self.advance();
-                let name = if let TokenType::Id(n) = self.advance().kind { n } else { panic!("Expected class name") };
-                self.consume(TokenType::LBrace, "Expected '{'");
+                let name = self.consume_id("expected class name")?;
+                let parent = if self.peek().kind == TokenType::Extends {
+                    self.advance();
+                    Some(self.consume_id("expected base class name after 'extends'")?)
+                } else { None };
+                let mut interfaces = Vec::new();
+                if self.peek().kind == TokenType::Implements {
+                    self.advance();
+                    interfaces.push(self.consume_id("expected interface name after 'implements'")?);
+                    while self.peek().kind == TokenType::Comma {
+                        self.advance();
+                        interfaces.push(self.consume_id("expected interface name")?);
+                    }
+                }
+                self.consume(TokenType::LBrace, "expected '{' after class name")?;
                 let mut fields = Vec::new();
                 let mut methods = Vec::new();
                 while self.peek().kind != TokenType::RBrace && self.peek().kind != TokenType::EOF {
-                    match self.peek().kind {
-                        TokenType::Var => {
-                            self.advance();
-                            if let TokenType::Id(f_name) = self.advance().kind {
-                                self.consume(TokenType::Semicolon, "Expected ';'");
-                                fields.push(f_name);
-                            } else { panic!("Expected field name"); }
-                        },
-                        TokenType::Func => {
-                             self.advance();
-                             let func_name = if let TokenType::Id(n) = self.advance().kind { n } else { panic!("Function name missing") };
-                             self.consume(TokenType::LParen, "Expected '('");
-                             let mut args = Vec::new();
-                             if self.peek().kind != TokenType::RParen {
-                                 if let TokenType::Id(arg) = self.advance().kind { args.push(arg); }
-                                 while self.peek().kind == TokenType::Comma {
-                                     self.advance();
-                                     if let TokenType::Id(arg) = self.advance().kind { args.push(arg); }
-                                 }
-                             }
-                             self.consume(TokenType::RParen, "Expected ')'");
-                             let body = self.parse_block();
-                             methods.push(Stmt::FuncDecl(func_name, args, body));
-                        },
-                        _ => panic!("Only variables and functions allowed in class definition. Got: {:?}", self.peek().kind),
+                    let member_start = self.pos;
+                    if let Err(err) = self.parse_class_member(&mut fields, &mut methods) {
+                        self.errors.push(err);
+                        self.synchronize(member_start);
+                    }
+                }
+                self.consume(TokenType::RBrace, "expected '}' to close the class body")?;
+                Ok(StmtKind::ClassDecl(name, parent, interfaces, fields, methods))
+            }
+            TokenType::Interface => {
+                self.advance();
+                let name = self.consume_id("expected interface name")?;
+                self.consume(TokenType::LBrace, "expected '{' after interface name")?;
+                let mut methods = Vec::new();
+                while self.peek().kind != TokenType::RBrace && self.peek().kind != TokenType::EOF {
+                    let member_start = self.pos;
+                    match self.parse_interface_method() {
+                        Ok(method) => methods.push(method),
+                        Err(err) => {
+                            self.errors.push(err);
+                            self.synchronize(member_start);
+                        }
                     }
                 }
-                self.consume(TokenType::RBrace, "Expected '}'");
-                Stmt::ClassDecl(name, fields, methods)
+                self.consume(TokenType::RBrace, "expected '}' to close the interface body")?;
+                Ok(StmtKind::InterfaceDecl(name, methods))
             }
             TokenType::Import => { 
                 self.advance();
                 
                 match &self.peek().kind {
-                    TokenType::String(path) => {
+                    TokenType::String(_) => {
                         // import "file.aur";
-                        let path = path.clone();
-                        self.advance();
-                        self.consume(TokenType::Semicolon, "Expected ';'");
-                        self.import_file(&path)
+                        self.import_path()
                     },
                     TokenType::LBrace => {
                         // import { A, B } from "file.aur";
                         self.advance();
-                        let mut _names = Vec::new();
-                        if let TokenType::Id(name) = self.advance().kind { _names.push(name); }
+                        let mut _names = vec![self.consume_id("expected imported name")?];
                         while self.peek().kind == TokenType::Comma {
                             self.advance();
-                            if let TokenType::Id(name) = self.advance().kind { _names.push(name); }
+                            _names.push(self.consume_id("expected imported name")?);
                         }
-                        self.consume(TokenType::RBrace, "Expected '}'");
-                        self.consume(TokenType::From, "Expected 'from'");
-                        if let TokenType::String(path) = self.advance().kind {
-                            self.consume(TokenType::Semicolon, "Expected ';'");
-                            self.import_file(&path)
-                        } else { panic!("Expected string path"); }
+                        self.consume(TokenType::RBrace, "expected '}' after imported names")?;
+                        self.consume(TokenType::From, "expected 'from'")?;
+                        self.import_path()
                     },
                     TokenType::Id(_) => {
                         // import A from "file.aur";
                         self.advance(); // Name
-                        self.consume(TokenType::From, "Expected 'from'");
-                        if let TokenType::String(path) = self.advance().kind {
-                            self.consume(TokenType::Semicolon, "Expected ';'");
-                            self.import_file(&path)
-                        } else { panic!("Expected string path"); }
+                        self.consume(TokenType::From, "expected 'from'")?;
+                        self.import_path()
                     },
-                    _ => panic!("Unexpected token after import: {:?}", self.peek().kind)
+                    _ => Err(self.unexpected("expected a path or names after 'import'"))
                 }
             }
             // ... (Existing Func, Return, Var, Print, If, While, For)
             TokenType::Func => {
                  self.advance();
-                 let name = if let TokenType::Id(n) = self.advance().kind { n } else { panic!("Function name missing") };
-                 self.consume(TokenType::LParen, "Expected '('");
-                 let mut args = Vec::new();
-                 if self.peek().kind != TokenType::RParen {
-                     if let TokenType::Id(arg) = self.advance().kind { args.push(arg); }
-                     while self.peek().kind == TokenType::Comma {
-                         self.advance();
-                         if let TokenType::Id(arg) = self.advance().kind { args.push(arg); }
-                     }
-                 }
-                 self.consume(TokenType::RParen, "Expected ')'");
-                 let body = self.parse_block();
-                 Stmt::FuncDecl(name, args, body)
+                 let name = self.consume_id("expected function name")?;
+                 let args = self.parse_params()?;
+                 let ret = self.parse_return_type()?;
+                 let body = self.parse_block()?;
+                 Ok(StmtKind::FuncDecl(name, args, ret, body))
             }
             TokenType::Return => {
                 self.advance(); 
-                let expr = if self.peek().kind != TokenType::Semicolon { Some(self.parse_expr()) } else { None };
-                self.consume(TokenType::Semicolon, "Expected ';'");
-                Stmt::ReturnStmt(expr)
+                let expr = if self.peek().kind != TokenType::Semicolon { Some(self.parse_expr()?) } else { None };
+                self.consume(TokenType::Semicolon, "expected ';' after return")?;
+                Ok(StmtKind::ReturnStmt(expr))
             }
             TokenType::Var => {
                 self.advance();
-                if let TokenType::Id(name) = self.advance().kind {
-                    self.consume(TokenType::Assign, "Expected '='");
-                    let expr = self.parse_expr();
-                    self.consume(TokenType::Semicolon, "Expected ';'");
-                    Stmt::VarDecl(name, expr)
-                } else { panic!("Expected variable name"); }
+                let (name, ty) = self.parse_typed_name("expected variable name after 'var'")?;
+                self.consume(TokenType::Assign, "expected '=' after variable name")?;
+                let expr = self.parse_expr()?;
+                self.consume(TokenType::Semicolon, "expected ';' after variable declaration")?;
+                Ok(StmtKind::VarDecl(name, ty, expr))
+            }
+            TokenType::Const => {
+                self.advance();
+                let (name, ty) = self.parse_typed_name("expected constant name after 'const'")?;
+                self.consume(TokenType::Assign, "expected '=' after constant name")?;
+                let expr = self.parse_expr()?;
+                self.consume(TokenType::Semicolon, "expected ';' after constant declaration")?;
+                Ok(StmtKind::ConstDecl(name, ty, expr))
             }
             TokenType::Print => {
-                self.advance(); self.consume(TokenType::LParen, "Expected '('");
-                let e = self.parse_expr();
-                self.consume(TokenType::RParen, "Expected ')'");
-                self.consume(TokenType::Semicolon, "Expected ';'");
-                Stmt::Print(e)
+                self.advance(); self.consume(TokenType::LParen, "expected '(' after 'print'")?;
+                let e = self.parse_expr()?;
+                self.consume(TokenType::RParen, "expected ')'")?;
+                self.consume(TokenType::Semicolon, "expected ';' after statement")?;
+                Ok(StmtKind::Print(e))
             }
             TokenType::If => {
                  // ... (Keep existing If logic)
                 self.advance();
-                self.consume(TokenType::LParen, "Expected '('");
-                let condition = self.parse_expr();
-                self.consume(TokenType::RParen, "Expected ')'");
-                let then_block = self.parse_block();
+                self.consume(TokenType::LParen, "expected '(' after 'if'")?;
+                let condition = self.parse_expr()?;
+                self.consume(TokenType::RParen, "expected ')' after condition")?;
+                let then_block = self.parse_block()?;
                 let mut else_block = None;
                 if matches!(self.peek().kind, TokenType::Else) {
                     self.advance();
                     if matches!(self.peek().kind, TokenType::If) {
-                        let nested_stmt = self.parse_stmt();
+                        let nested_stmt = self.parse_stmt()?;
                         else_block = Some(vec![nested_stmt]);
                     } else {
-                        else_block = Some(self.parse_block());
+                        else_block = Some(self.parse_block()?);
                     }
                 }
-                Stmt::IfStmt(condition, then_block, else_block)
+                Ok(StmtKind::IfStmt(condition, then_block, else_block))
             }
             TokenType::While => {
                  // ... (Keep existing While logic)
                 self.advance();
-                self.consume(TokenType::LParen, "Expected '('");
-                let condition = self.parse_expr();
-                self.consume(TokenType::RParen, "Expected ')'");
-                let block = self.parse_block();
-                Stmt::WhileStmt(condition, block)
+                self.consume(TokenType::LParen, "expected '(' after 'while'")?;
+                let condition = self.parse_expr()?;
+                self.consume(TokenType::RParen, "expected ')' after condition")?;
+                let block = self.parse_block()?;
+                Ok(StmtKind::WhileStmt(condition, block, Vec::new()))
+            }
+            TokenType::Break | TokenType::Continue => {
+                self.advance();
+                let what = if t.kind == TokenType::Break { "break" } else { "continue" };
+                self.consume(TokenType::Semicolon, &format!("expected ';' after '{}'", what))?;
+                Ok(if t.kind == TokenType::Break { StmtKind::Break } else { StmtKind::Continue })
+            }
+            TokenType::Foreach => {
+                self.advance();
+                self.consume(TokenType::LParen, "expected '(' after 'foreach'")?;
+                let name = self.consume_id("expected loop variable name")?;
+                self.consume(TokenType::In, "expected 'in' after loop variable")?;
+                let collection = self.parse_expr()?;
+                self.consume(TokenType::RParen, "expected ')' after collection")?;
+                let body = self.parse_block()?;
+                Ok(StmtKind::Foreach(name, collection, body))
             }
             TokenType::For => {
                  // ... (Keep existing For logic)
                 self.advance();
-                self.consume(TokenType::LParen, "Expected '('");
+                self.consume(TokenType::LParen, "expected '(' after 'for'")?;
                 let mut init_stmts = Vec::new();
                 if self.peek().kind != TokenType::Semicolon {
-                    init_stmts.push(self.parse_stmt()); 
+                    init_stmts.push(self.parse_stmt()?); 
                 } else { self.advance(); }
                 let condition = if self.peek().kind != TokenType::Semicolon {
-                    self.parse_expr()
-                } else { Expr::Number(1) };
-                self.consume(TokenType::Semicolon, "Expected ';' after condition");
+                    self.parse_expr()?
+                } else { Expr::new(ExprKind::Number(1), t.span) };
+                self.consume(TokenType::Semicolon, "expected ';' after loop condition")?;
                 let mut step_stmts = Vec::new();
                 if self.peek().kind != TokenType::RParen {
-                    if let TokenType::Id(name) = self.peek().kind.clone() {
-                        self.advance(); 
-                        self.consume(TokenType::Assign, "Expected '='");
-                        let expr = self.parse_expr();
-                        step_stmts.push(Stmt::Assignment(name, expr));
-                    }
+                    let step_start = self.peek().span;
+                    let (step, _) = self.parse_simple_stmt()?;
+                    step_stmts.push(Stmt::new(step, self.span_from(step_start)));
                 }
-                self.consume(TokenType::RParen, "Expected ')'");
-                let mut body = self.parse_block();
-                body.extend(step_stmts);
-                let while_loop = Stmt::WhileStmt(condition, body);
+                self.consume(TokenType::RParen, "expected ')' after loop header")?;
+                let body = self.parse_block()?;
+                let while_loop = Stmt::new(StmtKind::WhileStmt(condition, body, step_stmts), self.span_from(t.span));
                 init_stmts.push(while_loop);
-                Stmt::BlockStmt(init_stmts)
+                Ok(StmtKind::BlockStmt(init_stmts))
             }
             _ => {
-                // Determine if it's Assignment or Expression Statement
-                // Unlike before, we don't assume `Id` always means assignment.
-                // We parse an expression first.
-                let expr = self.parse_expr();
-                
-                if self.peek().kind == TokenType::Assign {
-                     // Assignment: l-value = r-value
-                     self.advance();
-                     let r_val = self.parse_expr();
-                     self.consume(TokenType::Semicolon, "Expected ';'");
-                     
-                     match expr {
-                         Expr::Variable(name) => Stmt::Assignment(name, r_val),
-                         Expr::Get(obj, field) => Stmt::ExprStmt(Expr::Set(obj, field, Box::new(r_val))),
-                         _ => panic!("Invalid assignment target"),
-                     }
-                } else {
-                     // Expression Statement (e.g. Call)
-                     self.consume(TokenType::Semicolon, "Expected ';'");
-                     Stmt::ExprStmt(expr)
-                }
+                let (kind, what) = self.parse_simple_stmt()?;
+                self.consume(TokenType::Semicolon, &format!("expected ';' after {}", what))?;
+                Ok(kind)
             }
         }
     }
+
+    /// Parses an assignment (`x = v`, `x += v`, `x++`) or an expression statement,
+    /// without the trailing ';'. Also returns what was parsed, for error messages.
+    fn parse_simple_stmt(&mut self) -> CompileResult<(StmtKind, &'static str)> {
+        // Determine if it's Assignment or Expression Statement
+        // Unlike before, we don't assume `Id` always means assignment.
+        // We parse an expression first.
+        let target = self.parse_expr()?;
+        let assign = self.peek().clone();
+        let compound = match assign.kind {
+            TokenType::PlusAssign | TokenType::MinusAssign | TokenType::MulAssign | TokenType::DivAssign | TokenType::ModAssign => {
+                self.advance();
+                let op = match assign.kind {
+                    TokenType::PlusAssign => TokenType::Plus,
+                    TokenType::MinusAssign => TokenType::Minus,
+                    TokenType::MulAssign => TokenType::Mul,
+                    TokenType::DivAssign => TokenType::Div,
+                    _ => TokenType::Mod,
+                };
+                Some((op, self.parse_expr()?))
+            }
+            TokenType::Increment | TokenType::Decrement => {
+                self.advance();
+                let op = if assign.kind == TokenType::Increment { TokenType::Plus } else { TokenType::Minus };
+                Some((op, Expr::new(ExprKind::Number(1), assign.span)))
+            }
+            _ => None,
+        };
+        let r_val = match compound {
+            // Fields and elements keep the target whole so its object and index run only once
+            Some((op, value)) if matches!(target.kind, ExprKind::Get(..) | ExprKind::IndexAccess(..)) => {
+                return Ok((StmtKind::CompoundAssignment(target, op, value), "assignment"));
+            }
+            // A variable is simply read again: `x += v` is `x = x + v`, `x++` is `x = x + 1`
+            Some((op, value)) => binary(target.clone(), op, value),
+            // Assignment: l-value = r-value
+            None if assign.kind == TokenType::Assign => { self.advance(); self.parse_expr()? }
+            // Expression Statement (e.g. Call)
+            None => return Ok((StmtKind::ExprStmt(target), "expression")),
+        };
+
+        let span = target.span.to(self.prev_span());
+        let kind = match target.kind {
+            ExprKind::Variable(name) => StmtKind::Assignment(name, r_val),
+            ExprKind::Get(obj, field) => StmtKind::ExprStmt(Expr::new(ExprKind::Set(obj, field, Box::new(r_val)), span)),
+            ExprKind::IndexAccess(array, index) => StmtKind::IndexAssignment(*array, *index, r_val),
+            _ => return Err(self.error_at(&assign, "E0203", "invalid assignment target")
+                    .with_note("only variables, fields and array elements can be assigned")),
+        };
+        Ok((kind, "assignment"))
+    }
+
+    /// Parses one `var field: type;` or `func method() {}` inside a class body.
+    fn parse_class_member(&mut self, fields: &mut Vec<FieldDecl>, methods: &mut Vec<Stmt>) -> CompileResult<()> {
+        let doc = self.peek().doc.clone();
+        match self.peek().kind {
+            TokenType::Var => {
+                self.advance();
+                let (name, ty) = self.parse_typed_name("expected field name")?;
+                self.consume(TokenType::Semicolon, "expected ';' after field declaration")?;
+                fields.push(FieldDecl { name, ty, doc });
+            },
+            TokenType::Func => {
+                 let method_start = self.advance().span;
+                 let func_name = self.consume_id("expected method name")?;
+                 let args = self.parse_params()?;
+                 let ret = self.parse_return_type()?;
+                 let body = self.parse_block()?;
+                 let mut method = Stmt::new(StmtKind::FuncDecl(func_name, args, ret, body), self.span_from(method_start));
+                 method.doc = doc;
+                 methods.push(method);
+            },
+            _ => return Err(self.unexpected("expected 'var' or 'func' in class body")),
+        }
+        Ok(())
+    }
+
+    /// Parses `func name(params) -> ret;`, a method an interface requires.
+    fn parse_interface_method(&mut self) -> CompileResult<Stmt> {
+        let doc = self.peek().doc.clone();
+        if self.peek().kind != TokenType::Func {
+            return Err(self.unexpected("expected 'func' in interface body"));
+        }
+        let method_start = self.advance().span;
+        let name = self.consume_id("expected method name")?;
+        let args = self.parse_params()?;
+        let ret = self.parse_return_type()?;
+        self.consume(TokenType::Semicolon, "expected ';' after interface method")?;
+        let mut method = Stmt::new(StmtKind::FuncDecl(name, args, ret, Vec::new()), self.span_from(method_start));
+        method.doc = doc;
+        Ok(method)
+    }
+
+    /// Parses the `"path";` tail shared by all import forms.
+    fn import_path(&mut self) -> CompileResult<StmtKind> {
+        let path_token = self.peek().clone();
+        if let TokenType::String(path) = &path_token.kind {
+            self.advance();
+            self.consume(TokenType::Semicolon, "expected ';' after import")?;
+            self.import_file(path, &path_token)
+        } else {
+            Err(self.unexpected("expected a string path"))
+        }
+    }
     
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    /// Parses the whole token stream. Syntax errors don't stop the parser: it
+    /// returns the (partial) AST together with every error it recovered from.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<Diagnostic>) {
         let mut s = Vec::new();
-        while self.peek().kind != TokenType::EOF { s.push(self.parse_stmt()); }
-        s
+        while self.peek().kind != TokenType::EOF {
+            if let Some(stmt) = self.parse_stmt_recovering() {
+                s.push(stmt);
+            }
+        }
+        (s, std::mem::take(&mut self.errors))
+    }
+}
+
+/// Name a lambda is compiled under (`@fn_lambda.<file>.<offset>`) and listed with in the TypeTable.
+pub fn lambda_name(span: Span) -> String {
+    format!("lambda.{}.{}", span.file, span.start)
+}
+
+fn lambda(params: Vec<TypedName>, ret: Option<VarType>, body: Vec<Stmt>) -> ExprKind {
+    ExprKind::Lambda(Box::new(Lambda { params, ret, body, captures: Vec::new() }))
+}
+
+fn binary(left: Expr, op: TokenType, right: Expr) -> Expr {
+    let span = left.span.to(right.span);
+    Expr::new(ExprKind::Binary(Box::new(left), op, Box::new(right)), span)
+}
+
+/// Human readable token description for "expected X, found Y" messages.
+fn describe(kind: &TokenType) -> String {
+    match kind {
+        TokenType::Id(n) => format!("identifier '{}'", n),
+        TokenType::Number(n) => format!("number '{}'", n),
+        TokenType::Float(f) => format!("number '{}'", f),
+        TokenType::String(_) | TokenType::InterpStart(_) => "string literal".to_string(),
+        TokenType::InterpMid(_) | TokenType::InterpEnd(_) => "'}'".to_string(),
+        TokenType::Bool(b) => format!("'{}'", b),
+        TokenType::EOF => "end of file".to_string(),
+        other => {
+            let text = match other {
+                TokenType::Assign => "=", TokenType::Plus => "+", TokenType::Minus => "-",
+                TokenType::Mul => "*", TokenType::Div => "/", TokenType::Mod => "%",
+                TokenType::PlusAssign => "+=", TokenType::MinusAssign => "-=",
+                TokenType::MulAssign => "*=", TokenType::DivAssign => "/=", TokenType::ModAssign => "%=",
+                TokenType::Increment => "++", TokenType::Decrement => "--",
+                TokenType::BitAnd => "&", TokenType::BitOr => "|", TokenType::BitXor => "^",
+                TokenType::Shl => "<<", TokenType::Shr => ">>",
+                TokenType::LParen => "(", TokenType::RParen => ")",
+                TokenType::LBrace => "{", TokenType::RBrace => "}",
+                TokenType::LBracket => "[", TokenType::RBracket => "]",
+                TokenType::Comma => ",", TokenType::Semicolon => ";", TokenType::Dot => ".",
+                TokenType::Colon => ":", TokenType::Arrow => "->", TokenType::FatArrow => "=>",
+                TokenType::Eq => "==", TokenType::Neq => "!=", TokenType::Lt => "<", TokenType::Gt => ">",
+                TokenType::Lte => "<=", TokenType::Gte => ">=",
+                TokenType::And => "&&", TokenType::Or => "||", TokenType::Not => "!",
+                _ => return format!("keyword '{}'", format!("{:?}", other).to_lowercase()),
+            };
+            format!("'{}'", text)
+        }
     }
 }
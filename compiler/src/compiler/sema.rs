@@ -0,0 +1,1614 @@
+use std::collections::{HashMap, HashSet};
+use crate::compiler::diagnostic::{CompileResult, Diagnostic};
+use crate::compiler::lexer::TokenType;
+use crate::compiler::parser::{lambda_name, Expr, ExprKind, Lambda, Stmt, StmtKind, TypedName};
+use crate::compiler::source::Span;
+use crate::compiler::types::{ClassInfo, FuncSig, InterfaceInfo, TypeTable, VarType};
+
+/// Inference state of a function signature. `None` means "not known yet".
+#[derive(Clone, Default)]
+struct FuncSlots {
+    param_names: Vec<String>,
+    params: Vec<Option<VarType>>,
+    ret: Option<VarType>,
+    ret_declared: bool, // The return type comes from a `-> type` annotation
+}
+
+/// Also used for interfaces, which only have methods.
+#[derive(Clone, Default)]
+struct ClassSlots {
+    parent: Option<String>,
+    interfaces: Vec<String>, // Named in `implements`
+    fields: Vec<(String, Option<VarType>)>, // Own fields; inherited ones stay with the base class
+    methods: HashMap<String, FuncSlots>,
+    method_order: Vec<String>, // Declaration order, which is the vtable order
+    span: Span,
+}
+
+/// The function or method whose signature is being looked at.
+#[derive(Clone)]
+enum FuncRef {
+    Func(String),
+    Method(String, String),
+    Lambda(String, u32), // Its `lambda_name` and line
+}
+
+impl FuncRef {
+    fn display(&self) -> String {
+        match self {
+            FuncRef::Func(name) => name.clone(),
+            FuncRef::Method(class, method) => format!("{}.{}", class, method),
+            FuncRef::Lambda(_, line) => format!("lambda on line {}", line),
+        }
+    }
+
+    /// The name codegen compiles it under (without the `fn_` prefix).
+    fn ir_name(&self) -> String {
+        match self {
+            FuncRef::Func(name) | FuncRef::Lambda(name, _) => name.clone(),
+            FuncRef::Method(class, method) => format!("{}_{}", class, method),
+        }
+    }
+}
+
+/// A top-level variable or constant, which functions and methods can use too.
+struct GlobalSlot {
+    ty: Option<VarType>,
+    constant: bool,
+}
+
+/// State of the function body currently being checked.
+struct FuncContext {
+    func: FuncRef,
+    value_returns: usize,
+}
+
+/// The function (or top-level code) around a lambda whose body is being checked.
+struct Frame {
+    scopes: Vec<HashMap<String, Option<VarType>>>,
+    func: Option<FuncContext>,
+    loop_depth: usize,
+    captures: Vec<String>, // Variables of this frame or further out that the lambda uses
+}
+
+/// Semantic analysis pass between the Parser and the Compiler.
+/// Resolves names, infers the type of every expression (stored in `Expr::ty`)
+/// and rejects ill-typed programs before any IR is generated.
+///
+/// Annotated parameter, return, field and variable types are taken as written.
+/// The others are inferred from their uses: call sites,
+/// `return` statements and field assignments. Since one inferred type can
+/// depend on another, the program is checked repeatedly until a pass learns
+/// nothing new. Whatever is still unknown then (e.g. parameters of functions
+/// that are never called) defaults to int, the type codegen always used.
+pub struct Sema {
+    functions: HashMap<String, FuncSlots>,
+    classes: HashMap<String, ClassSlots>,
+    interfaces: HashMap<String, ClassSlots>,
+    globals: HashMap<String, GlobalSlot>,
+    lambdas: HashMap<String, FuncSlots>,
+    captured: HashMap<String, HashSet<String>>, // See TypeTable::captured
+    escaping: HashMap<String, HashSet<String>>, // See TypeTable::escaping
+    scopes: Vec<HashMap<String, Option<VarType>>>,
+    current_func: Option<FuncContext>,
+    enclosing: Vec<Frame>, // Innermost last, one per lambda being checked
+    loop_depth: usize, // Number of loops around the statement being checked
+    changed: bool, // Set when the current pass inferred a new type
+    errors: Vec<Diagnostic>,
+}
+
+impl Sema {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+            interfaces: HashMap::new(),
+            globals: HashMap::new(),
+            lambdas: HashMap::new(),
+            captured: HashMap::new(),
+            escaping: HashMap::new(),
+            scopes: Vec::new(),
+            current_func: None,
+            enclosing: Vec::new(),
+            loop_depth: 0,
+            changed: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Type-checks the program, annotating every expression with its type.
+    /// Returns the inferred declarations together with all type errors found.
+    pub fn check(&mut self, stmts: &mut [Stmt]) -> (TypeTable, Vec<Diagnostic>) {
+        self.collect_declarations(stmts, false);
+        self.collect_globals(stmts);
+        self.link_classes();
+        let mut errors = std::mem::take(&mut self.errors);
+
+        loop {
+            self.errors.clear();
+            self.changed = false;
+            self.scopes = vec![HashMap::new()];
+            for stmt in stmts.iter_mut() { self.check_stmt(stmt); }
+            if !self.changed && !self.default_unknown_types() { break; }
+        }
+
+        errors.append(&mut self.errors);
+        (self.type_table(), errors)
+    }
+
+    /// Registers every function and class so they can be used before their declaration.
+    /// Declarations may sit inside imported files (blocks) but not inside function bodies.
+    fn collect_declarations(&mut self, stmts: &[Stmt], in_function: bool) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::FuncDecl(name, params, ret, body) => {
+                    if !in_function {
+                        if self.functions.contains_key(name) {
+                            self.errors.push(already_defined("function", name).with_span(stmt.span));
+                        } else {
+                            self.functions.insert(name.clone(), FuncSlots::new(params, ret));
+                        }
+                    }
+                    self.collect_declarations(body, true);
+                }
+                StmtKind::ClassDecl(name, parent, interfaces, fields, methods) => {
+                    if in_function { continue; }
+                    if self.classes.contains_key(name) || self.interfaces.contains_key(name) {
+                        self.errors.push(already_defined("class", name).with_span(stmt.span));
+                        continue;
+                    }
+                    let mut class = ClassSlots { parent: parent.clone(), interfaces: interfaces.clone(), span: stmt.span, ..ClassSlots::default() };
+                    for field in fields {
+                        if class.fields.iter().any(|(f, _)| *f == field.name) {
+                            self.errors.push(already_defined("field", &format!("{}.{}", name, field.name)).with_span(stmt.span));
+                        } else {
+                            class.fields.push((field.name.clone(), declared_slot(&field.ty)));
+                        }
+                    }
+                    self.collect_methods(name, methods, &mut class);
+                    self.classes.insert(name.clone(), class);
+                }
+                StmtKind::InterfaceDecl(name, methods) => {
+                    if in_function { continue; }
+                    if self.classes.contains_key(name) || self.interfaces.contains_key(name) {
+                        self.errors.push(already_defined("interface", name).with_span(stmt.span));
+                        continue;
+                    }
+                    let mut interface = ClassSlots { span: stmt.span, ..ClassSlots::default() };
+                    self.collect_methods(name, methods, &mut interface);
+                    self.interfaces.insert(name.clone(), interface);
+                }
+                StmtKind::BlockStmt(body) | StmtKind::Module(body) | StmtKind::WhileStmt(_, body, _) | StmtKind::Foreach(_, _, body) => self.collect_declarations(body, in_function),
+                StmtKind::IfStmt(_, then_block, else_block) => {
+                    self.collect_declarations(then_block, in_function);
+                    if let Some(else_block) = else_block { self.collect_declarations(else_block, in_function); }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Registers the variables and constants declared at the top level of the program
+    /// or of an imported file, so functions can use them wherever they are declared.
+    fn collect_globals(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::VarDecl(name, ..) | StmtKind::ConstDecl(name, ..) => {
+                    let constant = matches!(stmt.kind, StmtKind::ConstDecl(..));
+                    // A second declaration is reported when it is checked
+                    self.globals.entry(name.clone()).or_insert(GlobalSlot { ty: None, constant });
+                }
+                StmtKind::Module(body) => self.collect_globals(body),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_methods(&mut self, owner: &str, methods: &[Stmt], slots: &mut ClassSlots) {
+        for method in methods {
+            if let StmtKind::FuncDecl(method_name, params, ret, _) = &method.kind {
+                if slots.methods.contains_key(method_name) {
+                    self.errors.push(already_defined("method", &format!("{}.{}", owner, method_name)).with_span(method.span));
+                } else {
+                    slots.methods.insert(method_name.clone(), FuncSlots::new(params, ret));
+                    slots.method_order.push(method_name.clone());
+                }
+            }
+        }
+    }
+
+    /// Checks the `extends` and `implements` clauses once every class is known: the base class
+    /// and interfaces must exist, a class can't inherit from itself and inherited fields can't
+    /// be redeclared.
+    fn link_classes(&mut self) {
+        let mut names: Vec<String> = self.classes.keys().cloned().collect();
+        names.sort();
+        for name in &names {
+            let span = self.classes[name].span;
+            let interfaces = std::mem::take(&mut self.classes.get_mut(name).expect("listed above").interfaces);
+            for interface in interfaces {
+                if self.interfaces.contains_key(&interface) {
+                    self.classes.get_mut(name).expect("listed above").interfaces.push(interface);
+                } else if self.classes.contains_key(&interface) {
+                    let diag = Diagnostic::error("E0319", format!("'{}' is a class, not an interface", interface))
+                        .with_note(format!("use 'extends {}' to inherit from it", interface));
+                    self.errors.push(diag.with_span(span));
+                } else {
+                    self.errors.push(Diagnostic::error("E0308", format!("unknown interface '{}'", interface)).with_span(span));
+                }
+            }
+
+            let Some(parent) = self.classes[name].parent.clone() else { continue };
+            let diag = if self.interfaces.contains_key(&parent) {
+                Diagnostic::error("E0319", format!("'{}' is an interface, not a class", parent))
+                    .with_note(format!("use 'implements {}' instead", parent))
+            } else if !self.classes.contains_key(&parent) {
+                unknown_class(&parent)
+            } else if self.ancestors(&parent).contains(name) {
+                let note = if parent == *name { "a class cannot extend itself".to_string() } else { format!("'{}' already inherits from '{}'", parent, name) };
+                Diagnostic::error("E0318", format!("class '{}' cannot extend '{}'", name, parent)).with_note(note)
+            } else { continue };
+            self.errors.push(diag.with_span(span));
+            if let Some(class) = self.classes.get_mut(name) { class.parent = None; }
+        }
+        for name in &names {
+            let Some(parent) = self.classes[name].parent.clone() else { continue };
+            for (field, _) in &self.classes[name].fields {
+                if let Some(owner) = self.ancestors(&parent).into_iter().find(|c| self.classes[c].fields.iter().any(|(f, _)| f == field)) {
+                    let diag = already_defined("field", &format!("{}.{}", name, field))
+                        .with_note(format!("'{}' is inherited from '{}'", field, owner));
+                    self.errors.push(diag.with_span(self.classes[name].span));
+                }
+            }
+        }
+    }
+
+    /// `class` followed by its base classes, nearest first.
+    fn ancestors(&self, class: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = Some(class.to_string());
+        while let Some(name) = current {
+            // A cyclic `extends` (reported by link_classes) ends the chain
+            if chain.contains(&name) { break; }
+            current = self.classes.get(&name).and_then(|c| c.parent.clone());
+            chain.push(name);
+        }
+        chain
+    }
+
+    /// Whether a `class` object can be used where a `target` (a base class or an
+    /// implemented interface) is expected.
+    fn is_subtype(&self, class: &str, target: &str) -> bool {
+        class != target && self.ancestors(class).iter().any(|c| {
+            c == target || self.classes.get(c).is_some_and(|info| info.interfaces.iter().any(|i| i == target))
+        })
+    }
+
+    fn type_slots(&self, name: &str) -> Option<&ClassSlots> {
+        self.classes.get(name).or_else(|| self.interfaces.get(name))
+    }
+
+    /// The class whose implementation of `method` runs for a `class` object:
+    /// the class itself or the nearest base class that declares it.
+    /// For an interface, the interface itself.
+    fn method_owner(&self, class: &str, method: &str) -> Option<String> {
+        self.ancestors(class).into_iter()
+            .find(|c| self.type_slots(c).is_some_and(|info| info.methods.contains_key(method)))
+    }
+
+    fn method_ref(&self, class: &str, method: &str) -> FuncRef {
+        let owner = self.method_owner(class, method).unwrap_or_else(|| class.to_string());
+        FuncRef::Method(owner, method.to_string())
+    }
+
+    /// Gives every type that could not be inferred the historical default (int).
+    /// Returns true if anything changed, in which case another pass is needed.
+    fn default_unknown_types(&mut self) -> bool {
+        // Lambdas first: the functions they are returned from or passed to learn their types from them
+        let lambdas_unknown = self.lambdas.values().any(|lambda| lambda.params.iter().any(Option::is_none) || lambda.ret.is_none());
+        let mut changed = false;
+        let mut default = |slot: &mut Option<VarType>| {
+            if slot.is_none() {
+                *slot = Some(VarType::Int);
+                changed = true;
+            }
+        };
+        for lambda in self.lambdas.values_mut() {
+            lambda.params.iter_mut().for_each(&mut default);
+            default(&mut lambda.ret);
+        }
+        if lambdas_unknown { return true; }
+        for func in self.functions.values_mut() {
+            func.params.iter_mut().for_each(&mut default);
+            default(&mut func.ret);
+        }
+        for class in self.classes.values_mut().chain(self.interfaces.values_mut()) {
+            class.fields.iter_mut().for_each(|(_, ty)| default(ty));
+            for method in class.methods.values_mut() {
+                method.params.iter_mut().for_each(&mut default);
+                default(&mut method.ret);
+            }
+        }
+        changed
+    }
+
+    fn type_table(&self) -> TypeTable {
+        TypeTable {
+            functions: self.functions.iter().chain(&self.lambdas).map(|(name, func)| (name.clone(), func.signature())).collect(),
+            classes: self.classes.keys().map(|name| (name.clone(), self.class_info(name))).collect(),
+            globals: self.globals.iter().map(|(name, global)| (name.clone(), resolved(&global.ty))).collect(),
+            interfaces: self.interfaces.iter().map(|(name, interface)| {
+                let methods = interface.method_order.iter().map(|m| (m.clone(), interface.methods[m].signature())).collect();
+                (name.clone(), InterfaceInfo { methods })
+            }).collect(),
+            captured: self.captured.clone(),
+            escaping: self.escaping.clone(),
+        }
+    }
+
+    /// Flattens a class and its base classes into the struct layout and vtable codegen uses.
+    fn class_info(&self, name: &str) -> ClassInfo {
+        let mut info = ClassInfo { parent: self.classes[name].parent.clone(), ..ClassInfo::default() };
+        for class in self.ancestors(name).iter().rev() {
+            let slots = &self.classes[class];
+            info.interfaces.extend(slots.interfaces.iter().filter(|i| !info.interfaces.contains(i)).cloned().collect::<Vec<_>>());
+            info.fields.extend(slots.fields.iter().map(|(f, ty)| (f.clone(), resolved(ty))));
+            for method in &slots.method_order {
+                info.methods.insert(method.clone(), slots.methods[method].signature());
+                // An override takes over the slot of the method it replaces
+                match info.vtable.iter_mut().find(|(m, _)| m == method) {
+                    Some(entry) => entry.1 = class.clone(),
+                    None => info.vtable.push((method.clone(), class.clone())),
+                }
+            }
+        }
+        info
+    }
+
+    fn error(&mut self, diag: Diagnostic, span: Span) {
+        let diag = diag.or_span(span);
+        // Desugared code (`x += v` is `x = x + v`) checks the same expression twice
+        let repeated = self.errors.iter().any(|e| e.span == diag.span && e.code == diag.code && e.message == diag.message);
+        if !repeated {
+            self.errors.push(diag);
+        }
+    }
+
+    fn func_slots(&mut self, func: &FuncRef) -> Option<&mut FuncSlots> {
+        match func {
+            FuncRef::Func(name) => self.functions.get_mut(name),
+            FuncRef::Lambda(name, _) => self.lambdas.get_mut(name),
+            FuncRef::Method(class, method) => {
+                let owner = if self.classes.contains_key(class) { &mut self.classes } else { &mut self.interfaces };
+                owner.get_mut(class).and_then(|c| c.methods.get_mut(method))
+            }
+        }
+    }
+
+    // --- Scopes ---
+
+    fn declare(&mut self, name: &str, ty: Option<VarType>, span: Span) {
+        let scope = self.scopes.last_mut().expect("sema always has a scope");
+        if scope.contains_key(name) {
+            let diag = Diagnostic::error("E0313", format!("variable '{}' is already declared in this scope", name))
+                .with_note("assign to it instead, or declare it in a nested block");
+            self.error(diag, span);
+            return;
+        }
+        scope.insert(name.to_string(), ty);
+    }
+
+    /// Looks a variable up from the innermost scope outwards, then in the functions
+    /// around a lambda (capturing it) and finally among the globals when inside a
+    /// function. `Ok(None)` means the variable exists but its type isn't known yet.
+    fn lookup(&mut self, name: &str) -> CompileResult<Option<VarType>> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(ty) = scope.get(name) {
+                return Ok(ty.clone());
+            }
+        }
+        for k in (0..self.enclosing.len()).rev() {
+            let frame = &self.enclosing[k];
+            let Some(ty) = frame_locals(frame).iter().rev().find_map(|scope| scope.get(name)).cloned() else { continue };
+            // Every lambda between the variable's function and this one carries it in its environment
+            let owner = frame.func.as_ref().map(|ctx| ctx.func.ir_name()).unwrap_or_default();
+            self.captured.entry(owner).or_default().insert(name.to_string());
+            for frame in &mut self.enclosing[k..] {
+                if !frame.captures.iter().any(|c| c == name) { frame.captures.push(name.to_string()); }
+            }
+            return Ok(ty);
+        }
+        if self.current_func.is_some() && let Some(global) = self.globals.get(name) {
+            return Ok(global.ty.clone());
+        }
+        if name == "this" {
+            return Err(Diagnostic::error("E0311", "'this' can only be used inside a class method"));
+        }
+        Err(undefined_variable(name))
+    }
+
+    /// Whether `name` is a variable here (possibly captured or global) rather than a function.
+    fn resolves_to_variable(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+            || self.enclosing.iter().any(|frame| frame_locals(frame).iter().any(|scope| scope.contains_key(name)))
+            || (self.current_func.is_some() && self.globals.contains_key(name))
+    }
+
+    /// Whether a declaration here is a global: at the top level of the program
+    /// (or of an imported file), outside any function or block.
+    fn at_top_level(&self) -> bool {
+        self.current_func.is_none() && self.scopes.len() == 1
+    }
+
+    /// Whether `name` refers to a global constant rather than to a variable shadowing it.
+    fn is_constant(&self, name: &str) -> bool {
+        let locals = if self.current_func.is_some() { &self.scopes[..] } else { &self.scopes[1..] };
+        !locals.iter().any(|scope| scope.contains_key(name))
+            && !self.enclosing.iter().any(|frame| frame_locals(frame).iter().any(|scope| scope.contains_key(name)))
+            && self.globals.get(name).is_some_and(|global| global.constant)
+    }
+
+    fn check_block(&mut self, stmts: &mut [Stmt]) {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts.iter_mut() { self.check_stmt(stmt); }
+        self.scopes.pop();
+    }
+
+    /// Checks a function or method body. Functions can't see the variables of
+    /// the code around them, so the body gets a fresh scope stack.
+    fn check_function(&mut self, func: FuncRef, params: &[TypedName], ret: &Option<VarType>, body: &mut [Stmt], span: Span) {
+        if self.current_func.is_some() {
+            let diag = Diagnostic::error("E0314", format!("'{}' must be declared at the top level", func.display()))
+                .with_note("functions and classes cannot be nested inside functions");
+            self.error(diag, span);
+            return;
+        }
+        for (_, ty) in params {
+            if let Some(diag) = ty.as_ref().and_then(|ty| self.check_annotation(ty, "parameter")) { self.error(diag, span); }
+        }
+        if let Some(diag) = ret.as_ref().and_then(|ty| self.check_annotation(ty, "return")) { self.error(diag, span); }
+
+        let param_types = self.func_slots(&func).map(|f| f.params.clone()).unwrap_or_default();
+        let outer_scopes = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        if let FuncRef::Method(class, _) = &func {
+            self.declare("this", Some(VarType::Instance(class.clone())), span);
+        }
+        for ((param, _), ty) in params.iter().zip(param_types) {
+            self.declare(param, ty, span);
+        }
+
+        self.current_func = Some(FuncContext { func: func.clone(), value_returns: 0 });
+        // The body shares the parameters' scope: redeclaring a parameter is an error
+        for stmt in body.iter_mut() { self.check_stmt(stmt); }
+        let ctx = self.current_func.take().expect("set above");
+        self.scopes = outer_scopes;
+        self.check_returns(&func, &ctx, span);
+    }
+
+    /// Checks a lambda. Its body sees the variables of the code around it: the ones it
+    /// uses are recorded in its `captures`. `expected` holds the parameter types the context
+    /// passes it (`nums.map(x => x * 2)`). Returns its type once every slot is known.
+    fn check_lambda(&mut self, span: Span, lambda: &mut Lambda, expected: Option<&[VarType]>) -> Option<VarType> {
+        let Lambda { params, ret, body, captures } = lambda;
+        let name = lambda_name(span);
+        let func = FuncRef::Lambda(name.clone(), span.line);
+        for (_, ty) in params.iter() {
+            if let Some(diag) = ty.as_ref().and_then(|ty| self.check_annotation(ty, "parameter")) { self.error(diag, span); }
+        }
+        if let Some(diag) = ret.as_ref().and_then(|ty| self.check_annotation(ty, "return")) { self.error(diag, span); }
+
+        let slots = self.lambdas.entry(name.clone()).or_insert_with(|| FuncSlots::new(params, ret));
+        if let Some(expected) = expected.filter(|expected| expected.len() == params.len()) {
+            for (slot, ty) in slots.params.iter_mut().zip(expected) {
+                if slot.is_none() {
+                    *slot = Some(ty.clone());
+                    self.changed = true;
+                }
+            }
+        }
+        let param_types = slots.params.clone();
+
+        let frame = Frame {
+            scopes: std::mem::replace(&mut self.scopes, vec![HashMap::new()]),
+            func: self.current_func.take(),
+            loop_depth: std::mem::take(&mut self.loop_depth),
+            captures: Vec::new(),
+        };
+        self.enclosing.push(frame);
+        for ((param, _), ty) in params.iter().zip(param_types) {
+            self.declare(param, ty, span);
+        }
+        self.current_func = Some(FuncContext { func: func.clone(), value_returns: 0 });
+        // An arrow body (`x => f(x)`) is a `return` spanning just its expression; calling a
+        // function that returns nothing makes it a plain statement
+        if let [stmt] = body.as_mut_slice()
+            && let StmtKind::ReturnStmt(Some(value)) = &mut stmt.kind
+            && stmt.span == value.span
+            && self.check_expr(value) == Some(VarType::Void) {
+            stmt.kind = StmtKind::ExprStmt(value.clone());
+        }
+        for stmt in body.iter_mut() { self.check_stmt(stmt); }
+        let ctx = self.current_func.take().expect("set above");
+        let frame = self.enclosing.pop().expect("pushed above");
+        self.scopes = frame.scopes;
+        self.current_func = frame.func;
+        self.loop_depth = frame.loop_depth;
+        *captures = frame.captures;
+        self.check_returns(&func, &ctx, span);
+        self.lambdas[&name].function_type()
+    }
+
+    /// Settles the return type of a function whose body was just checked.
+    fn check_returns(&mut self, func: &FuncRef, ctx: &FuncContext, span: Span) {
+        // A function without any `return value;` doesn't produce a value
+        if ctx.value_returns == 0 && let Some(slots) = self.func_slots(func) {
+            match &slots.ret {
+                None => {
+                    slots.ret = Some(VarType::Void);
+                    self.changed = true;
+                }
+                Some(ty) if *ty != VarType::Void => {
+                    let diag = Diagnostic::error("E0307", format!("'{}' is declared to return '{}' but never returns a value", func.display(), ty))
+                        .with_note("add a 'return' statement, or declare it '-> void'");
+                    self.error(diag, span);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &mut Stmt) {
+        let span = stmt.span;
+        match &mut stmt.kind {
+            StmtKind::VarDecl(name, declared, init) => self.check_declaration(name, declared, init, false, span),
+            StmtKind::ConstDecl(name, declared, init) => self.check_declaration(name, declared, init, true, span),
+            StmtKind::Assignment(name, value) => {
+                if self.is_constant(name) {
+                    let diag = Diagnostic::error("E0320", format!("cannot assign to constant '{}'", name))
+                        .with_note(format!("'{}' is declared with 'const'", name));
+                    self.error(diag, span);
+                }
+                let var_ty = match self.lookup(name) {
+                    Ok(ty) => ty,
+                    Err(diag) => { self.error(diag, span); None }
+                };
+                let value_ty = self.check_value_as(value, var_ty.as_ref());
+                if let (Some(var_ty), Some(value_ty)) = (var_ty, value_ty)
+                    && !assignable(&var_ty, &value_ty) {
+                    let diag = mismatched(&var_ty, &value_ty).with_note(format!("'{}' was declared as '{}'", name, var_ty));
+                    self.error(diag, value.span);
+                }
+            }
+            StmtKind::IndexAssignment(array, index, value) => {
+                let elem_ty = self.check_element(array, index);
+                let value_ty = self.check_value_as(value, elem_ty.as_ref());
+                if let (Some(elem_ty), Some(value_ty)) = (elem_ty, value_ty)
+                    && !assignable(&elem_ty, &value_ty) {
+                    let holder = if matches!(array.ty, Some(VarType::Map(..))) { "map" } else { "array" };
+                    let diag = mismatched(&elem_ty, &value_ty).with_note(format!("the {} holds '{}' values", holder, elem_ty));
+                    self.error(diag, value.span);
+                }
+            }
+            StmtKind::CompoundAssignment(target, op, value) => {
+                let target_ty = self.check_value(target);
+                let value_ty = self.check_value(value);
+                let (Some(target_ty), Some(value_ty)) = (target_ty, value_ty) else { return };
+                match binary_result(op, &target_ty, &value_ty) {
+                    Some(ty) if assignable(&target_ty, &ty) => {}
+                    Some(ty) => {
+                        let note = match &target.kind {
+                            ExprKind::Get(obj, field) => match &obj.ty {
+                                Some(VarType::Instance(class)) => format!("field '{}.{}' has type '{}'", class, field, target_ty),
+                                _ => format!("field '{}' has type '{}'", field, target_ty),
+                            },
+                            ExprKind::IndexAccess(array, _) if matches!(array.ty, Some(VarType::Map(..))) => format!("the map holds '{}' values", target_ty),
+                            _ => format!("the array holds '{}' values", target_ty),
+                        };
+                        self.error(mismatched(&target_ty, &ty).with_note(note), value.span);
+                    }
+                    None => {
+                        let diag = Diagnostic::error("E0307", format!("cannot apply '{}' to '{}' and '{}'", operator(op), target_ty, value_ty));
+                        self.error(diag, span);
+                    }
+                }
+            }
+            StmtKind::Print(expr) => { self.check_value(expr); }
+            StmtKind::ExprStmt(expr) => { self.check_expr(expr); }
+            StmtKind::IfStmt(cond, then_block, else_block) => {
+                self.check_condition(cond);
+                self.check_block(then_block);
+                if let Some(else_block) = else_block { self.check_block(else_block); }
+            }
+            StmtKind::WhileStmt(cond, body, step) => {
+                self.check_condition(cond);
+                self.loop_depth += 1;
+                self.check_block(body);
+                self.loop_depth -= 1;
+                self.check_block(step);
+            }
+            StmtKind::Break => self.check_in_loop("break", span),
+            StmtKind::Continue => self.check_in_loop("continue", span),
+            StmtKind::Foreach(name, collection, body) => {
+                let elem_ty = match self.check_value(collection) {
+                    Some(VarType::Array(elem)) => Some(*elem),
+                    // Maps are iterated over their keys
+                    Some(VarType::Map(key, _)) => Some(*key),
+                    Some(other) => {
+                        self.error(type_error("foreach can only iterate over arrays and maps", &other), collection.span);
+                        None
+                    }
+                    None => None,
+                };
+                // The loop variable lives in its own scope around the body
+                self.scopes.push(HashMap::new());
+                self.declare(name, elem_ty, span);
+                self.loop_depth += 1;
+                self.check_block(body);
+                self.loop_depth -= 1;
+                self.scopes.pop();
+            }
+            StmtKind::BlockStmt(stmts) => self.check_block(stmts),
+            // An imported file's declarations share the top-level scope
+            StmtKind::Module(stmts) => for stmt in stmts.iter_mut() { self.check_stmt(stmt); },
+            StmtKind::FuncDecl(name, params, ret, body) => {
+                let func = FuncRef::Func(name.clone());
+                self.check_function(func, params, ret, body, span);
+            }
+            StmtKind::InterfaceDecl(name, methods) => {
+                if self.current_func.is_some() {
+                    let diag = Diagnostic::error("E0314", format!("interface '{}' must be declared at the top level", name))
+                        .with_note("interfaces cannot be nested inside functions");
+                    self.error(diag, span);
+                    return;
+                }
+                for method in methods.iter() {
+                    if let StmtKind::FuncDecl(_, params, ret, _) = &method.kind {
+                        for (_, ty) in params {
+                            if let Some(diag) = ty.as_ref().and_then(|ty| self.check_annotation(ty, "parameter")) { self.error(diag, method.span); }
+                        }
+                        if let Some(diag) = ret.as_ref().and_then(|ty| self.check_annotation(ty, "return")) { self.error(diag, method.span); }
+                    }
+                }
+            }
+            StmtKind::ClassDecl(name, _, _, fields, methods) => {
+                if self.current_func.is_some() {
+                    let diag = Diagnostic::error("E0314", format!("class '{}' must be declared at the top level", name))
+                        .with_note("functions and classes cannot be nested inside functions");
+                    self.error(diag, span);
+                    return;
+                }
+                for field in fields.iter() {
+                    if let Some(diag) = field.ty.as_ref().and_then(|ty| self.check_annotation(ty, "field")) { self.error(diag, span); }
+                }
+                let class = name.clone();
+                let parent = self.classes.get(&class).and_then(|c| c.parent.clone());
+                for method in methods.iter_mut() {
+                    let method_span = method.span;
+                    if let StmtKind::FuncDecl(method_name, params, ret, body) = &mut method.kind {
+                        // Constructors aren't called through the vtable, so they don't override
+                        if let Some(base) = parent.as_ref().and_then(|parent| self.method_owner(parent, method_name))
+                            && method_name != "init" {
+                            self.check_override(&class, &base, method_name, "overrides", method_span);
+                        }
+                        let func = FuncRef::Method(class.clone(), method_name.clone());
+                        self.check_function(func, params, ret, body, method_span);
+                    }
+                }
+                self.check_conformance(&class, span);
+            }
+            StmtKind::ReturnStmt(value) => self.check_return(value.as_mut(), span),
+            StmtKind::ImportStmt(_) => {}
+        }
+    }
+
+    /// Checks a `var` or `const` declaration. At the top level it also settles the type
+    /// of the global, which functions see.
+    fn check_declaration(&mut self, name: &str, declared: &Option<VarType>, init: &mut Expr, constant: bool, span: Span) {
+        if constant && !self.at_top_level() {
+            let diag = Diagnostic::error("E0314", format!("constant '{}' must be declared at the top level", name))
+                .with_note("use 'var' inside functions and blocks");
+            self.error(diag, span);
+        }
+        let declared = declared.clone().filter(|ty| match self.check_annotation(ty, "variable") {
+            Some(diag) => { self.error(diag, span); false }
+            None => true,
+        });
+        let ty = self.check_value_as(init, declared.as_ref());
+        if let (Some(declared), Some(found)) = (&declared, &ty)
+            && !assignable(declared, found) {
+            let diag = mismatched(declared, found).with_note(format!("'{}' is declared as '{}'", name, declared));
+            self.error(diag, init.span);
+        }
+        let ty = declared.or(ty);
+        if self.at_top_level() && let Some(global) = self.globals.get_mut(name)
+            && global.ty.is_none() && ty.is_some() {
+            global.ty = ty.clone();
+            self.changed = true;
+        }
+        self.declare(name, ty, span);
+    }
+
+    /// Checks that a class has (or inherits) every method of the interfaces it implements.
+    fn check_conformance(&mut self, class: &str, span: Span) {
+        let interfaces = self.classes.get(class).map(|c| c.interfaces.clone()).unwrap_or_default();
+        for interface in interfaces {
+            let required = self.interfaces[&interface].method_order.clone();
+            for method in required {
+                match self.method_owner(class, &method) {
+                    Some(owner) => self.check_override(&owner, &interface, &method, "implements", span),
+                    None => {
+                        let params = self.interfaces[&interface].methods[&method].param_names.join(", ");
+                        let diag = Diagnostic::error("E0323", format!("class '{}' doesn't implement '{}.{}'", class, interface, method))
+                            .with_note(format!("add 'func {}({})' to '{}'", method, params, class));
+                        self.error(diag, span);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks that an overriding (or interface) method has the signature of the one it
+    /// `relation`s, which calls through the base class or interface reach via the vtable.
+    /// Types inferred for either of them are shared with the other.
+    fn check_override(&mut self, class: &str, base: &str, method: &str, relation: &str, span: Span) {
+        let own = FuncRef::Method(class.to_string(), method.to_string());
+        let inherited = FuncRef::Method(base.to_string(), method.to_string());
+        let (Some(mine), Some(theirs)) = (self.func_slots(&own).cloned(), self.func_slots(&inherited).cloned()) else { return };
+        let code = if relation == "implements" { "E0322" } else { "E0321" };
+        if mine.params.len() != theirs.params.len() {
+            let diag = Diagnostic::error(code, format!("'{}' {} '{}' with a different number of parameters", own.display(), relation, inherited.display()))
+                .with_note(format!("'{}' takes {} argument(s)", inherited.display(), theirs.params.len()));
+            self.error(diag, span);
+            return;
+        }
+        let mut merged = theirs.clone();
+        let count = theirs.params.len();
+        let pairs = mine.params.iter().zip(&theirs.params).chain(std::iter::once((&mine.ret, &theirs.ret)));
+        for (i, pair) in pairs.enumerate() {
+            match pair {
+                (Some(ours), Some(base_ty)) if ours != base_ty => {
+                    let what = if i < count { format!("parameter '{}'", theirs.param_names[i]) } else { "the return type".to_string() };
+                    let diag = Diagnostic::error(code, format!("'{}' {} '{}' with a different signature", own.display(), relation, inherited.display()))
+                        .with_note(format!("{} is '{}' in '{}'", what, base_ty, inherited.display()));
+                    self.error(diag, span);
+                    return;
+                }
+                (Some(ours), None) if i < count => merged.params[i] = Some(ours.clone()),
+                (Some(ours), None) => merged.ret = Some(ours.clone()),
+                _ => {}
+            }
+        }
+        if merged.params != mine.params || merged.ret != mine.ret || merged.params != theirs.params || merged.ret != theirs.ret {
+            self.changed = true;
+        }
+        for func in [own, inherited] {
+            if let Some(slots) = self.func_slots(&func) {
+                slots.params = merged.params.clone();
+                slots.ret = merged.ret.clone();
+            }
+        }
+    }
+
+    fn check_return(&mut self, value: Option<&mut Expr>, span: Span) {
+        let (ty, value_span) = match value {
+            Some(expr) => {
+                if let Some(ctx) = &mut self.current_func { ctx.value_returns += 1; }
+                let func = self.current_func.as_ref().map(|ctx| ctx.func.clone());
+                let expected = func.and_then(|func| self.func_slots(&func).and_then(|slots| slots.ret.clone()));
+                (self.check_value_as(expr, expected.as_ref()), expr.span)
+            }
+            None => (Some(VarType::Void), span),
+        };
+        let Some(ty) = ty else { return };
+
+        let Some(ctx) = &self.current_func else {
+            // Top level code returns the process exit code
+            if !matches!(ty, VarType::Int | VarType::Bool | VarType::Void) {
+                self.error(type_error("the program can only return an int exit code", &ty), value_span);
+            }
+            return;
+        };
+        let func = ctx.func.clone();
+
+        let Some(slots) = self.func_slots(&func) else { return };
+        let ret_declared = slots.ret_declared;
+        match unify(&mut slots.ret, &ty) {
+            Ok(learned) => self.changed |= learned,
+            Err(expected) => {
+                let note = match expected {
+                    _ if ret_declared => format!("'{}' is declared to return '{}'", func.display(), expected),
+                    VarType::Void => format!("other return statements in '{}' don't return a value", func.display()),
+                    _ => format!("other return statements in '{}' return '{}'", func.display(), expected),
+                };
+                self.error(mismatched(&expected, &ty).with_note(note), value_span);
+            }
+        }
+    }
+
+    /// Checks a type annotation written in the given position
+    /// ("variable", "parameter", "field", "return" or "element").
+    fn check_annotation(&self, ty: &VarType, position: &str) -> Option<Diagnostic> {
+        match ty {
+            VarType::Instance(class) if self.type_slots(class).is_none() => Some(unknown_class(class)),
+            VarType::Void if position != "return" => Some(Diagnostic::error("E0307", "'void' can only be used as a return type")),
+            VarType::Array(elem) => self.check_annotation(elem, "element"),
+            VarType::Map(key, _) if !is_map_key(key) => Some(invalid_map_key(key)),
+            VarType::Map(_, value) => self.check_annotation(value, "element"),
+            VarType::Function(params, ret) => params.iter().find_map(|p| self.check_annotation(p, "parameter"))
+                .or_else(|| self.check_annotation(ret, "return")),
+            _ => None,
+        }
+    }
+
+    /// Reports `break`/`continue` used outside of any loop.
+    fn check_in_loop(&mut self, keyword: &str, span: Span) {
+        if self.loop_depth == 0 {
+            let diag = Diagnostic::error("E0316", format!("'{}' outside of a loop", keyword))
+                .with_note("it can only be used inside 'while', 'for' and 'foreach' bodies");
+            self.error(diag, span);
+        }
+    }
+
+    /// Checks `array[index]` or `map[key]` and returns the element or value type.
+    fn check_element(&mut self, array: &mut Expr, index: &mut Expr) -> Option<VarType> {
+        let array_ty = self.check_value(array);
+        let (index_ty, note) = match &array_ty {
+            Some(VarType::Map(key, _)) => ((**key).clone(), format!("the map has '{}' keys", key)),
+            _ => (VarType::Int, "array indices must be ints".to_string()),
+        };
+        if let Some(ty) = self.check_value(index)
+            && ty != index_ty {
+            self.error(mismatched(&index_ty, &ty).with_note(note), index.span);
+        }
+        match array_ty? {
+            VarType::Array(elem) => Some(*elem),
+            VarType::Map(_, value) => Some(*value),
+            other => { self.error(type_error("only arrays and maps can be indexed", &other), array.span); None }
+        }
+    }
+
+    /// Checks an `if`/`while` condition. Ints, strings and objects are truthy when non-zero/non-null;
+    /// floats are rejected (compare them explicitly).
+    fn check_condition(&mut self, cond: &mut Expr) {
+        if let Some(ty) = self.check_value(cond)
+            && !is_truthy(&ty) {
+            let what = match ty { VarType::Array(..) => "arrays", VarType::Map(..) => "maps", VarType::Function(..) => "functions", _ => "floats" };
+            self.error(type_error(&format!("{} cannot be used as a condition", what), &ty), cond.span);
+        }
+    }
+
+    /// Checks an array literal. Its element type comes from the context when known
+    /// (`var xs: [float] = [1, 2]`), otherwise from the elements (`[1, 2.5]` is `[float]`).
+    fn check_array_literal(&mut self, elements: &mut [Expr], span: Span, expected: Option<&VarType>) -> Option<VarType> {
+        if elements.is_empty() && expected.is_none() {
+            let diag = Diagnostic::error("E0306", "cannot infer the element type of an empty array")
+                .with_note("give it a type: var items: [int] = [];");
+            self.error(diag, span);
+            return None;
+        }
+        let mut elem_ty = expected.cloned();
+        let mut known = true;
+        for element in elements.iter_mut() {
+            known &= self.check_literal_element(element, &mut elem_ty, expected.is_some(), "element");
+        }
+        if !known { return None; }
+        elem_ty.map(|elem| VarType::Array(Box::new(elem)))
+    }
+
+    /// Checks a map literal like an array literal, with separate key and value types.
+    fn check_map_literal(&mut self, entries: &mut [(Expr, Expr)], span: Span, expected: Option<(&VarType, &VarType)>) -> Option<VarType> {
+        if entries.is_empty() && expected.is_none() {
+            let diag = Diagnostic::error("E0306", "cannot infer the key and value types of an empty map")
+                .with_note("give it a type: var ages: {string: int} = {};");
+            self.error(diag, span);
+            return None;
+        }
+        let mut key_ty = expected.map(|(key, _)| key.clone());
+        let mut value_ty = expected.map(|(_, value)| value.clone());
+        let mut known = true;
+        for (key, value) in entries.iter_mut() {
+            known &= self.check_literal_element(key, &mut key_ty, expected.is_some(), "key");
+            known &= self.check_literal_element(value, &mut value_ty, expected.is_some(), "value");
+        }
+        if !known { return None; }
+        let (key_ty, value_ty) = (key_ty?, value_ty?);
+        if !is_map_key(&key_ty) {
+            self.error(invalid_map_key(&key_ty), entries[0].0.span);
+            return None;
+        }
+        Some(VarType::Map(Box::new(key_ty), Box::new(value_ty)))
+    }
+
+    /// Checks one element, key or value of a literal against the type found so far
+    /// (or given by the context when `fixed`). Returns false if its type is unknown.
+    fn check_literal_element(&mut self, expr: &mut Expr, current: &mut Option<VarType>, fixed: bool, role: &str) -> bool {
+        let context = current.clone();
+        let Some(ty) = self.check_value_as(expr, context.as_ref()) else { return false };
+        match current {
+            None => *current = Some(ty),
+            Some(cur) if assignable(cur, &ty) => {}
+            // Ints before the first float widen along with it
+            Some(cur) if !fixed && assignable(&ty, cur) => *current = Some(ty),
+            Some(cur) => {
+                let note = match (fixed, role) {
+                    (true, "element") => format!("the array holds '{}' values", cur),
+                    (true, "key") => format!("the map has '{}' keys", cur),
+                    (true, _) => format!("the map holds '{}' values", cur),
+                    (false, "element") => "all elements of an array must have the same type".to_string(),
+                    (false, _) => format!("all {}s of a map must have the same type", role),
+                };
+                let diag = mismatched(cur, &ty).with_note(note);
+                self.error(diag, expr.span);
+            }
+        }
+        true
+    }
+
+    /// Checks a value stored where a type is already known (an annotated variable,
+    /// a field, a parameter...). Array and map literals take their element types from there.
+    fn check_value_as(&mut self, expr: &mut Expr, expected: Option<&VarType>) -> Option<VarType> {
+        let span = expr.span;
+        let ty = match (&mut expr.kind, expected) {
+            (ExprKind::Lambda(lambda), expected) => {
+                let expected = match expected { Some(VarType::Function(params, _)) => Some(params.as_slice()), _ => None };
+                self.check_lambda(span, lambda, expected)
+            }
+            // A function passed where its type is known learns its parameter types from there
+            (ExprKind::Variable(name), Some(VarType::Function(expected, _))) if !self.resolves_to_variable(name) && self.functions.contains_key(name.as_str()) => {
+                let func = self.functions.get_mut(name.as_str()).expect("checked above");
+                if func.params.len() == expected.len() {
+                    for (slot, ty) in func.params.iter_mut().zip(expected) {
+                        if slot.is_none() {
+                            *slot = Some(ty.clone());
+                            self.changed = true;
+                        }
+                    }
+                }
+                func.function_type()
+            }
+            (ExprKind::ArrayLiteral(elements), Some(VarType::Array(elem))) => self.check_array_literal(elements, expr.span, Some(elem)),
+            (ExprKind::MapLiteral(entries), Some(VarType::Map(key, value))) => self.check_map_literal(entries, expr.span, Some((key, value))),
+            _ => {
+                let ty = self.check_value(expr)?;
+                // A subclass instance can be stored wherever its base class is expected
+                return match (ty, expected) {
+                    (VarType::Instance(class), Some(VarType::Instance(base))) if self.is_subtype(&class, base) => Some(VarType::Instance(base.clone())),
+                    (ty, _) => Some(ty),
+                };
+            }
+        };
+        expr.ty = ty.clone();
+        ty
+    }
+
+    /// Checks `push`, `pop`, `len`, `map`, `filter` and `reduce` on an array with the given element type.
+    fn check_array_method(&mut self, elem: &VarType, method: &str, args: &mut [Expr], span: Span) -> Option<VarType> {
+        let (params, ret) = match method {
+            "push" => (vec![elem.clone()], VarType::Void),
+            "pop" => (vec![], elem.clone()),
+            "len" => (vec![], VarType::Int),
+            "map" | "filter" | "reduce" => return self.check_higher_order(elem, method, args, span),
+            _ => {
+                for arg in args.iter_mut() { self.check_value(arg); }
+                let diag = Diagnostic::error("E0315", format!("arrays have no method '{}'", method))
+                    .with_note("available methods: filter, len, map, pop, push, reduce");
+                self.error(diag, span);
+                return None;
+            }
+        };
+        let note = format!("the array holds '{}' values", elem);
+        self.check_builtin_method(method, &params, ret, args, span, &note)
+    }
+
+    /// Checks `map(f)`, `filter(f)` and `reduce(f, initial)` on an array with the given element type.
+    fn check_higher_order(&mut self, elem: &VarType, method: &str, args: &mut [Expr], span: Span) -> Option<VarType> {
+        let count = if method == "reduce" { 2 } else { 1 };
+        if args.len() != count {
+            for arg in args.iter_mut() { self.check_value(arg); }
+            let diag = Diagnostic::error("E0303", format!("'{}' takes {} argument(s) but {} were supplied", method, count, args.len()));
+            self.error(diag, span);
+            return None;
+        }
+        match method {
+            "map" => {
+                let ret = self.check_callback(method, &mut args[0], std::slice::from_ref(elem))?;
+                Some(VarType::Array(Box::new(ret)))
+            }
+            "filter" => {
+                let ret = self.check_callback(method, &mut args[0], std::slice::from_ref(elem))?;
+                if ret != VarType::Bool {
+                    self.error(mismatched(&VarType::Bool, &ret).with_note("the function passed to 'filter' decides which elements to keep"), args[0].span);
+                }
+                Some(VarType::Array(Box::new(elem.clone())))
+            }
+            _ => {
+                // The accumulator has the type of the initial value
+                let (callback, initial) = args.split_at_mut(1);
+                let acc = self.check_value(&mut initial[0]);
+                let ret = self.check_callback(method, &mut callback[0], &[acc.clone()?, elem.clone()])?;
+                let acc = acc?;
+                if !assignable(&acc, &ret) {
+                    self.error(mismatched(&acc, &ret).with_note(format!("the initial value of 'reduce' is a '{}'", acc)), callback[0].span);
+                }
+                Some(acc)
+            }
+        }
+    }
+
+    /// Checks `has`, `remove`, `keys` and `len` on a map with the given key type.
+    fn check_map_method(&mut self, key: &VarType, method: &str, args: &mut [Expr], span: Span) -> Option<VarType> {
+        let (params, ret) = match method {
+            "has" => (vec![key.clone()], VarType::Bool),
+            "remove" => (vec![key.clone()], VarType::Void),
+            "keys" => (vec![], VarType::Array(Box::new(key.clone()))),
+            "len" => (vec![], VarType::Int),
+            _ => {
+                for arg in args.iter_mut() { self.check_value(arg); }
+                let diag = Diagnostic::error("E0315", format!("maps have no method '{}'", method))
+                    .with_note("available methods: has, keys, len, remove");
+                self.error(diag, span);
+                return None;
+            }
+        };
+        let note = format!("the map has '{}' keys", key);
+        self.check_builtin_method(method, &params, ret, args, span, &note)
+    }
+
+    /// Checks the arguments of a built-in method or system function; `note` explains a mismatched argument.
+    fn check_builtin_method(&mut self, method: &str, params: &[VarType], ret: VarType, args: &mut [Expr], span: Span, note: &str) -> Option<VarType> {
+        if args.len() != params.len() {
+            for arg in args.iter_mut() { self.check_value(arg); }
+            let diag = Diagnostic::error("E0303", format!("'{}' takes {} argument(s) but {} were supplied", method, params.len(), args.len()));
+            self.error(diag, span);
+            return Some(ret);
+        }
+        for (arg, param) in args.iter_mut().zip(params) {
+            if let Some(ty) = self.check_value_as(arg, Some(param))
+                && !assignable(param, &ty) {
+                self.error(mismatched(param, &ty).with_note(note), arg.span);
+            }
+        }
+        Some(ret)
+    }
+
+    /// Checks an expression whose value is used, rejecting calls that don't return anything.
+    fn check_value(&mut self, expr: &mut Expr) -> Option<VarType> {
+        let ty = self.check_expr(expr)?;
+        if ty == VarType::Void {
+            let diag = Diagnostic::error("E0307", "this expression doesn't produce a value")
+                .with_note("the function it calls has no 'return' with a value");
+            self.error(diag, expr.span);
+            return None;
+        }
+        Some(ty)
+    }
+
+    /// Infers the type of an expression and records it in `expr.ty`.
+    /// Returns None if the type is not known (yet) or the expression is invalid.
+    fn check_expr(&mut self, expr: &mut Expr) -> Option<VarType> {
+        let ty = self.check_expr_kind(expr);
+        expr.ty = ty.clone();
+        ty
+    }
+
+    fn check_expr_kind(&mut self, expr: &mut Expr) -> Option<VarType> {
+        let span = expr.span;
+        match &mut expr.kind {
+            ExprKind::Number(_) => Some(VarType::Int),
+            ExprKind::Float(_) => Some(VarType::Float),
+            ExprKind::String(_) => Some(VarType::Str),
+            ExprKind::Bool(_) => Some(VarType::Bool),
+            // A function name that no variable shadows is a function value
+            ExprKind::Variable(name) if !self.resolves_to_variable(name) && self.functions.contains_key(name.as_str()) => {
+                self.functions[name.as_str()].function_type()
+            }
+            // Used as a whole, the variable's value may be kept beyond its scope
+            ExprKind::Variable(name) => {
+                self.escape(name);
+                match self.lookup(name) {
+                    Ok(ty) => ty,
+                    Err(diag) => { self.error(diag, span); None }
+                }
+            }
+            ExprKind::Lambda(lambda) => self.check_lambda(span, lambda, None),
+            ExprKind::CallValue(callee, args) => {
+                let callee_ty = self.check_value(callee);
+                let what = callee_ty.as_ref().map(|ty| ty.to_string()).unwrap_or_default();
+                self.check_value_call(callee_ty, &what, args, span)
+            }
+            ExprKind::ArrayLiteral(elements) => self.check_array_literal(elements, span, None),
+            ExprKind::MapLiteral(entries) => self.check_map_literal(entries, span, None),
+            ExprKind::Interpolated(parts) => {
+                for part in parts.iter_mut() {
+                    if let Some(ty) = self.check_value(part)
+                        && !matches!(ty, VarType::Int | VarType::Float | VarType::Str | VarType::Bool) {
+                        self.error(type_error("only ints, floats, strings and bools can be interpolated", &ty), part.span);
+                    }
+                }
+                Some(VarType::Str)
+            }
+            ExprKind::IndexAccess(array, index) => self.check_element(array, index),
+            ExprKind::Unary(op, inner) => {
+                let ty = self.check_value(inner)?;
+                if *op == TokenType::Not && ty != VarType::Bool {
+                    self.error(type_error("the '!' operator only supports bool values", &ty), span);
+                    return None;
+                }
+                if *op == TokenType::Minus && !matches!(ty, VarType::Int | VarType::Float) {
+                    self.error(type_error("the '-' operator only supports numbers", &ty), span);
+                    return None;
+                }
+                Some(ty)
+            }
+            ExprKind::Binary(left, op, right) => {
+                let left_ty = self.check_value(left);
+                let right_ty = self.check_value(right);
+                let (left_ty, right_ty) = (left_ty?, right_ty?);
+                match binary_result(op, &left_ty, &right_ty) {
+                    Some(ty) => Some(ty),
+                    None => {
+                        let diag = Diagnostic::error("E0307", format!("cannot apply '{}' to '{}' and '{}'", operator(op), left_ty, right_ty));
+                        self.error(diag, span);
+                        None
+                    }
+                }
+            }
+            ExprKind::New(class, args) => {
+                if !self.classes.contains_key(class.as_str()) {
+                    for arg in args.iter_mut() { self.check_value(arg); }
+                    let diag = if self.interfaces.contains_key(class.as_str()) {
+                        Diagnostic::error("E0319", format!("cannot create an instance of interface '{}'", class))
+                            .with_note("create an object of a class that implements it")
+                    } else { unknown_class(class) };
+                    self.error(diag, span);
+                    return None;
+                }
+                // The arguments go to the constructor, if the class has or inherits one
+                if self.method_owner(class, "init").is_some() {
+                    self.check_call(self.method_ref(class, "init"), args, span);
+                } else if !args.is_empty() {
+                    for arg in args.iter_mut() { self.check_value(arg); }
+                    let diag = Diagnostic::error("E0303", format!("'new {}' takes 0 argument(s) but {} were supplied", class, args.len()))
+                        .with_note(format!("class '{}' has no 'init' method", class));
+                    self.error(diag, span);
+                }
+                Some(VarType::Instance(class.clone()))
+            }
+            ExprKind::SuperCall(method, args) => {
+                let parent = match &self.current_func {
+                    Some(FuncContext { func: FuncRef::Method(class, _), .. }) => self.classes.get(class).and_then(|c| c.parent.clone()),
+                    _ => None,
+                };
+                let Some(parent) = parent else {
+                    for arg in args.iter_mut() { self.check_value(arg); }
+                    let diag = Diagnostic::error("E0311", "'super' can only be used inside a method of a class that extends another");
+                    self.error(diag, span);
+                    return None;
+                };
+                self.check_call(self.method_ref(&parent, method), args, span)
+            }
+            ExprKind::Get(obj, field) => {
+                let class = self.check_object(obj, &format!("cannot read field '{}' of a non-object", field))?;
+                match self.field_slot(&class, field) {
+                    Ok(slot) => slot.clone(),
+                    Err(diag) => { self.error(diag, span); None }
+                }
+            }
+            ExprKind::Set(obj, field, value) => {
+                let class = self.check_object(obj, &format!("cannot set field '{}' of a non-object", field));
+                let field_ty = class.as_ref().and_then(|class| self.field_slot(class, field).ok()).and_then(|slot| slot.clone());
+                let value_ty = self.check_value_as(value, field_ty.as_ref());
+                let (class, value_ty) = (class?, value_ty?);
+                match self.field_slot(&class, field) {
+                    Ok(slot) => match unify(slot, &value_ty) {
+                        Ok(learned) => self.changed |= learned,
+                        Err(expected) => {
+                            let diag = mismatched(&expected, &value_ty).with_note(format!("field '{}.{}' has type '{}'", class, field, expected));
+                            self.error(diag, value.span);
+                        }
+                    },
+                    Err(diag) => self.error(diag, span),
+                }
+                Some(value_ty)
+            }
+            ExprKind::Call(name, args) => match name.as_str() {
+                "print" | "println" | "print_str" | "free" => {
+                    self.check_builtin_args(name, args, 1);
+                    Some(VarType::Void)
+                }
+                "int" | "float" => self.check_conversion(name, args, span),
+                _ if self.resolves_to_variable(name) => {
+                    let callee_ty = match self.lookup(name) {
+                        Ok(ty) => ty,
+                        Err(diag) => { self.error(diag, span); None }
+                    };
+                    self.check_value_call(callee_ty, name, args, span)
+                }
+                _ => self.check_call(FuncRef::Func(name.clone()), args, span),
+            },
+            ExprKind::MethodCall(obj, method, args) => match self.check_receiver(obj) {
+                Some(VarType::Instance(class)) => {
+                    // Without such a method, a field holding a function is called
+                    let field_ty = match self.method_owner(&class, method) {
+                        None => self.field_slot(&class, method).ok().and_then(|slot| slot.clone()),
+                        Some(_) => None,
+                    };
+                    match field_ty {
+                        Some(ty @ VarType::Function(..)) => self.check_value_call(Some(ty), &format!("{}.{}", class, method), args, span),
+                        _ => self.check_call(self.method_ref(&class, method), args, span),
+                    }
+                }
+                Some(VarType::Array(elem)) => self.check_array_method(&elem, method, args, span),
+                Some(VarType::Map(key, _)) => self.check_map_method(&key, method, args, span),
+                other => {
+                    if let Some(other) = other {
+                        self.error(type_error(&format!("cannot call method '{}' on a non-object", method), &other), obj.span);
+                    }
+                    for arg in args.iter_mut() { self.check_value(arg); }
+                    None
+                }
+            },
+            ExprKind::NamespacedCall(parts, args) => {
+                let full_name = parts.join(".");
+                let sig = match &parts[..] {
+                    [system, module, name] if system == "system" && module == "str" => str_function(name),
+                    _ => system_function(&full_name),
+                };
+                if let Some(sig) = sig {
+                    let params: Vec<String> = sig.params.iter().map(|p| p.to_string()).collect();
+                    let note = format!("{}({}) -> {}", full_name, params.join(", "), sig.ret);
+                    return self.check_builtin_method(&full_name, &sig.params, sig.ret, args, span, &note);
+                }
+                for arg in args.iter_mut() { self.check_value(arg); }
+                let ty = system_call_type(parts);
+                if ty.is_none() {
+                    let diag = Diagnostic::error("E0302", format!("unknown system function '{}'", parts.join(".")))
+                        .with_note("available modules: system.net, system.io, system.mvc, system.str");
+                    self.error(diag, span);
+                }
+                ty
+            }
+        }
+    }
+
+    /// Checks a call of a function value (a variable, field or expression of a function type).
+    fn check_value_call(&mut self, callee: Option<VarType>, what: &str, args: &mut [Expr], span: Span) -> Option<VarType> {
+        match callee {
+            Some(VarType::Function(params, ret)) => {
+                let note = format!("'{}' has type '{}'", what, VarType::Function(params.clone(), ret.clone()));
+                self.check_builtin_method(what, &params, *ret, args, span, &note)
+            }
+            other => {
+                for arg in args.iter_mut() { self.check_value(arg); }
+                if let Some(other) = other {
+                    self.error(type_error(&format!("'{}' is not a function", what), &other), span);
+                }
+                None
+            }
+        }
+    }
+
+    /// Checks the callback of `map`, `filter` or `reduce`, which is passed `params`.
+    /// Returns what it returns.
+    fn check_callback(&mut self, method: &str, callback: &mut Expr, params: &[VarType]) -> Option<VarType> {
+        let expected = VarType::Function(params.to_vec(), Box::new(VarType::Void));
+        match self.check_value_as(callback, Some(&expected))? {
+            VarType::Function(found, ret) if found == params && *ret != VarType::Void => Some(*ret),
+            found => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                let diag = Diagnostic::error("E0307", format!("'{}' expects a function of ({}) that returns a value", method, params.join(", ")))
+                    .with_note(format!("found a value of type '{}'", found));
+                self.error(diag, callback.span);
+                None
+            }
+        }
+    }
+
+    /// Checks the receiver of a field access or method call and returns its class.
+    fn check_object(&mut self, obj: &mut Expr, msg: &str) -> Option<String> {
+        match self.check_receiver(obj)? {
+            VarType::Instance(class) => Some(class),
+            other => { self.error(type_error(msg, &other), obj.span); None }
+        }
+    }
+
+    /// Checks the object of a field access or method call. Reading its fields or calling
+    /// its methods doesn't keep a variable's object, so unlike other uses it doesn't escape.
+    fn check_receiver(&mut self, obj: &mut Expr) -> Option<VarType> {
+        let ExprKind::Variable(name) = &obj.kind else { return self.check_value(obj) };
+        if !self.resolves_to_variable(name) { return self.check_value(obj); }
+        obj.ty = match self.lookup(name) {
+            Ok(ty) => ty,
+            Err(diag) => { self.error(diag, obj.span); None }
+        };
+        obj.ty.clone()
+    }
+
+    /// Records that a variable of the current function escapes: see TypeTable::escaping.
+    fn escape(&mut self, name: &str) {
+        let owner = self.current_func.as_ref().map(|ctx| ctx.func.ir_name()).unwrap_or_default();
+        self.escaping.entry(owner).or_default().insert(name.to_string());
+    }
+
+    fn field_slot(&mut self, class: &str, field: &str) -> CompileResult<&mut Option<VarType>> {
+        if self.interfaces.contains_key(class) {
+            return Err(Diagnostic::error("E0309", format!("interface '{}' has no field '{}'", class, field))
+                .with_note("interfaces only declare methods"));
+        }
+        if !self.classes.contains_key(class) { return Err(unknown_class(class)); }
+        // Inherited fields are typed in the class that declares them
+        let chain = self.ancestors(class);
+        let owner = chain.iter().find(|c| self.classes[c.as_str()].fields.iter().any(|(f, _)| f == field)).cloned();
+        let Some(owner) = owner else {
+            let names: Vec<String> = chain.iter().rev().flat_map(|c| self.classes[c.as_str()].fields.iter().map(|(f, _)| f.clone())).collect();
+            return Err(Diagnostic::error("E0309", format!("class '{}' has no field '{}'", class, field))
+                .with_note(format!("available fields: {}", names.join(", "))));
+        };
+        let info = self.classes.get_mut(&owner).expect("found above");
+        Ok(info.fields.iter_mut().find(|(f, _)| f == field).map(|(_, ty)| ty).expect("found above"))
+    }
+
+    /// Checks the arguments of a built-in function like print or free.
+    fn check_builtin_args(&mut self, name: &str, args: &mut [Expr], count: usize) {
+        let mut types = Vec::new();
+        for arg in args.iter_mut() { types.push(self.check_value(arg)); }
+        if let Err(diag) = expect_args(name, args, count) {
+            self.error(diag, args.first().map(|a| a.span).unwrap_or_default());
+            return;
+        }
+        let arg_ty = types[0].clone();
+        match (name, arg_ty) {
+            ("print_str", Some(ty)) if !matches!(ty, VarType::Str | VarType::Int) => {
+                self.error(type_error("print_str() expects a string", &ty), args[0].span);
+            }
+            ("free", Some(ty)) if !matches!(ty, VarType::Instance(_)) => {
+                self.error(type_error("free() only supports class instances", &ty), args[0].span);
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks an explicit numeric conversion: `int(x)` truncates a float,
+    /// `float(x)` widens an int.
+    fn check_conversion(&mut self, name: &str, args: &mut [Expr], span: Span) -> Option<VarType> {
+        let types: Vec<Option<VarType>> = args.iter_mut().map(|arg| self.check_value(arg)).collect();
+        if let Err(diag) = expect_args(name, args, 1) {
+            self.error(diag, span);
+            return None;
+        }
+        let target = if name == "int" { VarType::Int } else { VarType::Float };
+        match types[0].clone()? {
+            VarType::Int | VarType::Float => {}
+            VarType::Bool if target == VarType::Int => {}
+            other => {
+                self.error(type_error(&format!("cannot convert to '{}'", target), &other), args[0].span);
+                return None;
+            }
+        }
+        Some(target)
+    }
+
+    /// Checks a call to a user function or method. Argument types are merged
+    /// into the callee's parameter types; the result is its return type.
+    fn check_call(&mut self, func: FuncRef, args: &mut [Expr], span: Span) -> Option<VarType> {
+        let Some(slots) = self.func_slots(&func).cloned() else {
+            for arg in args.iter_mut() { self.check_value(arg); }
+            let diag = match &func {
+                FuncRef::Func(name) | FuncRef::Lambda(name, _) => Diagnostic::error("E0312", format!("cannot find function '{}'", name)),
+                FuncRef::Method(class, method) => {
+                    let methods = self.type_slots(class).is_some().then(|| {
+                        let mut names: Vec<&String> = self.ancestors(class).iter()
+                            .filter_map(|c| self.type_slots(c)).flat_map(|c| c.methods.keys()).collect();
+                        names.sort();
+                        names.dedup();
+                        names.into_iter().cloned().collect::<Vec<_>>().join(", ")
+                    });
+                    let kind = if self.interfaces.contains_key(class) { "interface" } else { "class" };
+                    let diag = Diagnostic::error("E0315", format!("{} '{}' has no method '{}'", kind, class, method));
+                    match methods {
+                        Some(m) if !m.is_empty() => diag.with_note(format!("available methods: {}", m)),
+                        _ => diag,
+                    }
+                }
+            };
+            self.error(diag, span);
+            return None;
+        };
+
+        if args.len() != slots.params.len() {
+            for arg in args.iter_mut() { self.check_value(arg); }
+            let diag = Diagnostic::error("E0303", format!("'{}' takes {} argument(s) but {} were supplied", func.display(), slots.params.len(), args.len()));
+            self.error(diag, span);
+            return slots.ret;
+        }
+
+        for (i, arg) in args.iter_mut().enumerate() {
+            let Some(ty) = self.check_value_as(arg, slots.params[i].as_ref()) else { continue };
+            let Some(callee) = self.func_slots(&func) else { continue };
+            match unify(&mut callee.params[i], &ty) {
+                Ok(learned) => self.changed |= learned,
+                Err(expected) => {
+                    let note = format!("parameter '{}' of '{}' has type '{}'", slots.param_names[i], func.display(), expected);
+                    self.error(mismatched(&expected, &ty).with_note(note), arg.span);
+                }
+            }
+        }
+        slots.ret
+    }
+}
+
+impl FuncSlots {
+    fn new(params: &[TypedName], ret: &Option<VarType>) -> Self {
+        Self {
+            param_names: params.iter().map(|(name, _)| name.clone()).collect(),
+            params: params.iter().map(|(_, ty)| declared_slot(ty)).collect(),
+            ret: ret.clone(),
+            ret_declared: ret.is_some(),
+        }
+    }
+
+    fn signature(&self) -> FuncSig {
+        FuncSig { params: self.params.iter().map(resolved).collect(), ret: resolved(&self.ret) }
+    }
+
+    /// The type of the function as a value, once all its types are known.
+    fn function_type(&self) -> Option<VarType> {
+        let params = self.params.iter().cloned().collect::<Option<Vec<VarType>>>()?;
+        Some(VarType::Function(params, Box::new(self.ret.clone()?)))
+    }
+}
+
+/// The scopes of a frame holding variables a lambda captures. The outermost scope of
+/// top-level code holds the globals, which are used directly.
+fn frame_locals(frame: &Frame) -> &[HashMap<String, Option<VarType>>] {
+    if frame.func.is_some() { &frame.scopes } else { &frame.scopes[1..] }
+}
+
+/// Final type of a slot; anything still unknown is int.
+fn resolved(slot: &Option<VarType>) -> VarType {
+    slot.clone().unwrap_or(VarType::Int)
+}
+
+/// Initial slot of an annotated parameter or field. Annotations that can never
+/// be valid there are left to inference (`check_annotation` reports them).
+fn declared_slot(ty: &Option<VarType>) -> Option<VarType> {
+    ty.clone().filter(|ty| *ty != VarType::Void)
+}
+
+/// Merges a use of type `found` into an inferred slot. Returns whether the slot
+/// learned its type from this use, or the type it already had on a conflict.
+fn unify(slot: &mut Option<VarType>, found: &VarType) -> Result<bool, VarType> {
+    match slot {
+        None => { *slot = Some(found.clone()); Ok(true) }
+        Some(ty) if assignable(ty, found) => Ok(false),
+        Some(ty) => Err(ty.clone()),
+    }
+}
+
+/// Whether a value of type `from` can be stored where a `to` is expected.
+/// Ints widen to floats implicitly; every other conversion must be explicit.
+pub fn assignable(to: &VarType, from: &VarType) -> bool {
+    to == from || (*to == VarType::Float && *from == VarType::Int)
+}
+
+/// Result type of a binary operator, or None if it doesn't apply to the operands.
+fn binary_result(op: &TokenType, left: &VarType, right: &VarType) -> Option<VarType> {
+    use VarType::*;
+    match (op, left, right) {
+        // Strings concatenate with strings and numbers
+        (TokenType::Plus, Str, Str | Int | Float) | (TokenType::Plus, Int | Float, Str) => Some(Str),
+        (TokenType::Plus | TokenType::Minus | TokenType::Mul | TokenType::Div | TokenType::Mod, Int, Int) => Some(Int),
+        // Mixed int/float arithmetic widens the int
+        (TokenType::Plus | TokenType::Minus | TokenType::Mul | TokenType::Div | TokenType::Mod, Int | Float, Int | Float) => Some(Float),
+        (TokenType::BitAnd | TokenType::BitOr | TokenType::BitXor | TokenType::Shl | TokenType::Shr, Int, Int) => Some(Int),
+        (TokenType::Lt | TokenType::Gt | TokenType::Lte | TokenType::Gte, Int | Float, Int | Float) => Some(Bool),
+        (TokenType::Eq | TokenType::Neq, Int | Float, Int | Float) | (TokenType::Eq | TokenType::Neq, Bool, Bool) => Some(Bool),
+        // Strings compare by content, ordered byte by byte
+        (TokenType::Eq | TokenType::Neq | TokenType::Lt | TokenType::Gt | TokenType::Lte | TokenType::Gte, Str, Str) => Some(Bool),
+        (TokenType::Eq | TokenType::Neq, Instance(a), Instance(b)) if a == b => Some(Bool),
+        (TokenType::And | TokenType::Or, l, r) if is_truthy(l) && is_truthy(r) => Some(Bool),
+        _ => None,
+    }
+}
+
+/// Map keys are hashed by value (ints) or by content (strings).
+fn is_map_key(ty: &VarType) -> bool {
+    matches!(ty, VarType::Int | VarType::Str)
+}
+
+fn invalid_map_key(found: &VarType) -> Diagnostic {
+    Diagnostic::error("E0317", format!("'{}' cannot be used as a map key", found))
+        .with_note("map keys must be ints or strings")
+}
+
+/// Types that can be used as a condition (non-zero / non-null is true).
+fn is_truthy(ty: &VarType) -> bool {
+    matches!(ty, VarType::Int | VarType::Bool | VarType::Str | VarType::Instance(_))
+}
+
+/// Signature of a `system.str` function, or None if there is no such function.
+pub fn str_function(name: &str) -> Option<FuncSig> {
+    use VarType::*;
+    let (params, ret) = match name {
+        "len" => (vec![Str], Int),
+        "substr" => (vec![Str, Int, Int], Str),
+        "split" => (vec![Str, Str], Array(Box::new(Str))),
+        "trim" | "to_upper" | "to_lower" => (vec![Str], Str),
+        "find" => (vec![Str, Str], Int),
+        "contains" | "starts_with" => (vec![Str, Str], Bool),
+        "replace" => (vec![Str, Str, Str], Str),
+        "to_int" => (vec![Str], Int),
+        "from_int" => (vec![Int], Str),
+        _ => return None,
+    };
+    Some(FuncSig { params, ret })
+}
+
+/// Signature of the system functions outside `system.str` whose arguments are typed.
+pub fn system_function(name: &str) -> Option<FuncSig> {
+    use VarType::*;
+    let (params, ret) = match name {
+        // A route handler gets the `?id=` parameter of the request and returns the response body
+        "system.mvc.route" => (vec![Str, Function(vec![Int], Box::new(Str))], Void),
+        "system.net.listen" => (vec![Int], Void),
+        _ => return None,
+    };
+    Some(FuncSig { params, ret })
+}
+
+/// Result type of a system library function, or None if there is no such function.
+fn system_call_type(parts: &[String]) -> Option<VarType> {
+    match parts.join(".").as_str() {
+        "system.io.print" | "system.io.println" | "system.net.api_listen" => Some(VarType::Void),
+        "system.io.read_file" | "system.io.input_str" | "system.mvc.render" | "system.mvc.render_list" => Some(VarType::Str),
+        "system.io.input" => Some(VarType::Int),
+        _ => None,
+    }
+}
+
+fn operator(op: &TokenType) -> &'static str {
+    match op {
+        TokenType::Plus => "+", TokenType::Minus => "-", TokenType::Mul => "*", TokenType::Div => "/", TokenType::Mod => "%",
+        TokenType::BitAnd => "&", TokenType::BitOr => "|", TokenType::BitXor => "^", TokenType::Shl => "<<", TokenType::Shr => ">>",
+        TokenType::Eq => "==", TokenType::Neq => "!=", TokenType::Lt => "<", TokenType::Gt => ">",
+        TokenType::Lte => "<=", TokenType::Gte => ">=", TokenType::And => "&&", TokenType::Or => "||",
+        _ => "?",
+    }
+}
+
+fn already_defined(what: &str, name: &str) -> Diagnostic {
+    Diagnostic::error("E0313", format!("{} '{}' is defined more than once", what, name))
+}
+
+fn unknown_class(name: &str) -> Diagnostic {
+    Diagnostic::error("E0308", format!("unknown class '{}'", name))
+}
+
+fn mismatched(expected: &VarType, found: &VarType) -> Diagnostic {
+    let diag = Diagnostic::error("E0307", format!("mismatched types: expected '{}', found '{}'", expected, found));
+    if (expected, found) == (&VarType::Int, &VarType::Float) {
+        return diag.with_note("floats are not truncated implicitly, use int(...)");
+    }
+    diag
+}
+
+pub fn type_error(msg: &str, found: &VarType) -> Diagnostic {
+    Diagnostic::error("E0307", msg).with_note(format!("found a value of type '{}'", found))
+}
+
+pub fn undefined_variable(name: &str) -> Diagnostic {
+    Diagnostic::error("E0310", format!("undefined variable '{}'", name))
+        .with_note("variables must be declared with 'var' before use")
+}
+
+pub fn unknown_method(module: &str, method: &str) -> Diagnostic {
+    Diagnostic::error("E0302", format!("unknown function '{}.{}'", module, method))
+}
+
+/// Checks the argument count of a built-in call.
+pub fn expect_args(name: &str, args: &[Expr], count: usize) -> CompileResult<()> {
+    if args.len() != count {
+        return Err(Diagnostic::error("E0303", format!("{} takes {} argument(s) but {} were supplied", name, count, args.len())));
+    }
+    Ok(())
+}
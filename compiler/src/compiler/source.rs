@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+/// Index of a file inside the SourceMap.
+pub type FileId = u32;
+
+/// A region of source code. Offsets are byte offsets into the file,
+/// lines and columns are 1-based and counted in characters.
+/// Stored as u32 to keep Diagnostic (and every AST node) small.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub start: u32,
+    pub end: u32,
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+impl Span {
+    /// A span covering both `self` and `other` (which must come later in the same file).
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end, end_line: other.end_line, end_column: other.end_column, ..self }
+    }
+
+    /// Zero-width span right after this one, used for "missing ';'" style errors.
+    pub fn after(self) -> Span {
+        Span { start: self.end, line: self.end_line, column: self.end_column, ..self }
+    }
+}
+
+pub struct SourceFile {
+    pub name: String, // Display name, relative to the entry file's directory
+    pub path: PathBuf,
+    pub src: String,
+}
+
+/// Owns every file read during a build (the entry file plus imports) so that
+/// diagnostics can be rendered against the right source.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Display names of all files, indexed by FileId.
+    pub fn names(&self) -> Vec<String> {
+        self.files.iter().map(|f| f.name.clone()).collect()
+    }
+
+    /// Registers a file. The first file added is the entry file; later files
+    /// are named relative to its directory.
+    pub fn add(&mut self, path: PathBuf, src: String) -> FileId {
+        let root = self.files.first().and_then(|f| f.path.parent());
+        let name = match root.and_then(|r| path.strip_prefix(r).ok()) {
+            Some(rel) => rel.display().to_string(),
+            None => path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string()),
+        };
+        self.files.push(SourceFile { name, path, src });
+        (self.files.len() - 1) as FileId
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id as usize]
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The static type of an Aura value. Inferred by the semantic analysis pass
+/// (sema.rs) and consumed by the code generator.
+#[derive(Clone, PartialEq, Debug)]
+pub enum VarType {
+    Int,
+    Float, // f64, lowered to LLVM double
+    Str,
+    Bool,
+    Array(Box<VarType>), // Growable, heap allocated; passed by reference
+    Map(Box<VarType>, Box<VarType>), // Hash map from int or string keys; passed by reference
+    Instance(String),
+    Function(Vec<VarType>, Box<VarType>), // Closure: code pointer plus captured environment; passed by reference
+    Void, // Result of calls that don't produce a value
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarType::Int => write!(f, "int"),
+            VarType::Float => write!(f, "float"),
+            VarType::Str => write!(f, "string"),
+            VarType::Bool => write!(f, "bool"),
+            VarType::Array(elem) => write!(f, "[{}]", elem),
+            VarType::Map(key, value) => write!(f, "{{{}: {}}}", key, value),
+            VarType::Instance(cls) => write!(f, "{}", cls),
+            VarType::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "func({}) -> {}", params.join(", "), ret)
+            }
+            VarType::Void => write!(f, "void"),
+        }
+    }
+}
+
+/// Parameter and return types of a function or method (`this` not included).
+#[derive(Clone, Debug)]
+pub struct FuncSig {
+    pub params: Vec<VarType>,
+    pub ret: VarType,
+}
+
+/// Field layout and method signatures of a class.
+#[derive(Clone, Debug, Default)]
+pub struct ClassInfo {
+    pub parent: Option<String>, // The class it `extends`
+    pub fields: Vec<(String, VarType)>, // Inherited fields first, then its own in declaration order (= struct layout after the vtable pointer)
+    pub methods: HashMap<String, FuncSig>, // Including inherited methods
+    pub vtable: Vec<(String, String)>, // (method, class whose implementation runs); inherited slots keep their index
+    pub interfaces: Vec<String>, // Interfaces it implements, including those of its base classes
+}
+
+/// Methods an interface requires, in declaration order.
+#[derive(Clone, Debug, Default)]
+pub struct InterfaceInfo {
+    pub methods: Vec<(String, FuncSig)>,
+}
+
+/// Everything sema learned about the program's declarations, handed to codegen
+/// together with the typed AST.
+#[derive(Clone, Debug, Default)]
+pub struct TypeTable {
+    pub functions: HashMap<String, FuncSig>,
+    pub classes: HashMap<String, ClassInfo>,
+    pub interfaces: HashMap<String, InterfaceInfo>,
+    pub globals: HashMap<String, VarType>, // Top-level variables and constants, visible in every function
+    // Variables that closures capture, per function ("" for top-level code); they live on the heap
+    pub captured: HashMap<String, HashSet<String>>,
+    // Variables whose value is stored, passed or returned somewhere, per function; a scope
+    // only frees the objects of its other variables, which can't outlive it
+    pub escaping: HashMap<String, HashSet<String>>,
+}
+
+impl TypeTable {
+    /// The slots every vtable starts with: one per method of every interface, so a call
+    /// through an interface finds the implementation at the same index in any class.
+    /// Classes fill the slots of interfaces they don't implement with null.
+    pub fn interface_slots(&self) -> Vec<(String, String)> {
+        let mut names: Vec<&String> = self.interfaces.keys().collect();
+        names.sort();
+        names.into_iter()
+            .flat_map(|name| self.interfaces[name].methods.iter().map(move |(method, _)| (name.clone(), method.clone())))
+            .collect()
+    }
+
+    /// Signature of a method of a class or interface.
+    pub fn method_sig(&self, class: &str, method: &str) -> Option<&FuncSig> {
+        match self.classes.get(class) {
+            Some(info) => info.methods.get(method),
+            None => self.interfaces.get(class)?.methods.iter().find(|(m, _)| m == method).map(|(_, sig)| sig),
+        }
+    }
+
+    /// Whether any class extends `class`, in which case its methods are called through the vtable.
+    pub fn has_subclasses(&self, class: &str) -> bool {
+        self.classes.values().any(|info| info.parent.as_deref() == Some(class))
+    }
+
+    /// The vtable slot of a method and the class that implements it
+    /// (for interfaces, the interface itself).
+    pub fn resolve_method(&self, class: &str, method: &str) -> Option<(usize, String)> {
+        let interface_slots = self.interface_slots();
+        if self.interfaces.contains_key(class) {
+            let slot = interface_slots.iter().position(|(i, m)| i == class && m == method)?;
+            return Some((slot, class.to_string()));
+        }
+        self.classes.get(class)?.vtable.iter().enumerate()
+            .find(|(_, (name, _))| name == method)
+            .map(|(slot, (_, owner))| (interface_slots.len() + slot, owner.clone()))
+    }
+}
@@ -1,10 +1,69 @@
+// Token/AST naming (EOF, *Stmt, compiler::compiler) predates clippy enforcement
+#![allow(clippy::upper_case_acronyms, clippy::enum_variant_names, clippy::module_inception)]
+
 mod compiler;
 
 use std::fs;
 use std::path::{Path, PathBuf};
 use compiler::lexer::Lexer;
 use compiler::parser::Parser;
+use compiler::sema::Sema;
 use compiler::compiler::Compiler;
+use compiler::docgen;
+use compiler::diagnostic::{Diagnostic, Severity};
+use compiler::source::SourceMap;
+
+/// Prints a diagnostic in rustc style, with a source snippet and carets under the offending span.
+fn render_diagnostic(diag: &Diagnostic, sources: &SourceMap) {
+    let label = match diag.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    eprintln!("{}[{}]: {}", label, diag.code, diag.message);
+
+    if let Some(span) = diag.span {
+        let file = sources.get(span.file);
+        let gutter = " ".repeat(span.line.to_string().len());
+        eprintln!("{}--> {}:{}:{}", gutter, file.name, span.line, span.column);
+        if let Some(line_src) = file.src.lines().nth(span.line.saturating_sub(1) as usize) {
+            let line_src = line_src.trim_end();
+            let column = span.column as usize;
+            // Keep tabs in the padding so the carets line up with the source line
+            let padding: String = line_src.chars().take(column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            // Multi-line spans are underlined up to the end of their first line
+            let line_len = line_src.chars().count() + 1;
+            let end_column = if span.end_line == span.line { span.end_column as usize } else { line_len };
+            let width = end_column.saturating_sub(column).clamp(1, line_len.saturating_sub(column).max(1));
+            eprintln!("{} |", gutter);
+            eprintln!("{} | {}", span.line, line_src);
+            eprintln!("{} | {}{}", gutter, padding, "^".repeat(width));
+        }
+        for note in &diag.notes {
+            eprintln!("{} = note: {}", gutter, note);
+        }
+    } else {
+        for note in &diag.notes {
+            eprintln!("  = note: {}", note);
+        }
+    }
+    eprintln!();
+}
+
+/// Renders a fatal diagnostic and aborts the build.
+fn fail(diag: Diagnostic, sources: &SourceMap) -> ! {
+    fail_all(&[diag], sources)
+}
+
+/// Renders every error of a failed stage and aborts the build.
+fn fail_all(diags: &[Diagnostic], sources: &SourceMap) -> ! {
+    for diag in diags {
+        render_diagnostic(diag, sources);
+    }
+    let what = if diags.len() == 1 { "a previous error".to_string() } else { format!("{} previous errors", diags.len()) };
+    eprintln!("error: could not compile '{}' due to {}", sources.get(0).name, what);
+    std::process::exit(1);
+}
 
 /// Helper function to automatically find Visual Studio paths
 fn find_msvc_paths() -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
@@ -37,13 +96,12 @@ fn find_msvc_paths() -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
     }
     
     // OFFLINE FALLBACK: Look into our own 'lib' folder (Aura-SDK/lib)
-    if let Ok(exe_p) = std::env::current_exe() {
-        if let Some(parent) = exe_p.parent() {
-            let local_lib = parent.parent().unwrap_or(parent).join("lib");
-            if local_lib.exists() {
-                println!("✅ Found local library path: {:?}", local_lib);
-                return Some((vec![local_lib], Vec::new()));
-            }
+    if let Ok(exe_p) = std::env::current_exe()
+        && let Some(parent) = exe_p.parent() {
+        let local_lib = parent.parent().unwrap_or(parent).join("lib");
+        if local_lib.exists() {
+            println!("✅ Found local library path: {:?}", local_lib);
+            return Some((vec![local_lib], Vec::new()));
         }
     }
 
@@ -54,17 +112,26 @@ fn find_msvc_paths() -> Option<(Vec<PathBuf>, Vec<PathBuf>)> {
 fn main() {
     // 1. Get file path
     let args: Vec<String> = std::env::args().collect();
+    // Build flags can appear anywhere on the command line
+    let checked = args.iter().any(|a| a == "--checked");
+    let unchecked = args.iter().any(|a| a == "--unchecked");
+    let args: Vec<String> = args.into_iter().filter(|a| a != "--checked" && a != "--unchecked").collect();
     
     if args.len() < 2 {
         println!(" Aura Programming Language v0.1.0");
         println!("Usage:");
         println!("  aura build <file.aur>   - Compile to EXE");
         println!("  aura <file.aur>         - Compile to EXE (Direct)");
+        println!("  aura doc <file.aur>     - Print the /// documentation as Markdown");
         println!("  aura version            - Show version");
+        println!("Options:");
+        println!("  --checked               - Stop with an error on integer overflow (+, -, *)");
+        println!("  --unchecked             - Leave out array bounds checks (faster, unsafe)");
         return;
     }
 
     let mut arg_path = &args[1];
+    let mut doc_only = false;
     
     // Subcommands
     if arg_path == "build" {
@@ -74,6 +141,14 @@ fn main() {
              println!(" Error: Please provide a file to build.");
              return;
         }
+    } else if arg_path == "doc" {
+        if args.len() > 2 {
+             arg_path = &args[2];
+             doc_only = true;
+        } else {
+             println!(" Error: Please provide a file to document.");
+             return;
+        }
     } else if arg_path == "version" {
         println!("Aura Engine v0.1.0 (Experimental)");
         return;
@@ -88,21 +163,37 @@ fn main() {
 
     let source_dir = input_file.parent().unwrap();
     let dist_dir = source_dir.join("dist");
-    if !dist_dir.exists() { fs::create_dir_all(&dist_dir).unwrap(); }
+    if !doc_only && !dist_dir.exists() { fs::create_dir_all(&dist_dir).unwrap(); }
 
     if let Err(e) = std::env::set_current_dir(source_dir) { println!("⚠️ Warning: Cwd error: {}", e); }
     
-    println!(" Compiling: {:?}", input_file);
+    if !doc_only { println!(" Compiling: {:?}", input_file); }
     let src = fs::read_to_string(&input_file).unwrap();
     let file_stem = input_file.file_stem().unwrap().to_str().unwrap();
+    let mut sources = SourceMap::new();
+    let file_id = sources.add(input_file.clone(), src.clone());
 
     // -- Compilation Stages --
-    let mut lexer = Lexer::new(src);
-    let tokens = lexer.tokenize();
-    let mut parser = Parser::new(tokens, source_dir.to_path_buf());
-    let ast = parser.parse(); 
+    let mut lexer = Lexer::new(src, file_id);
+    let tokens = lexer.tokenize().unwrap_or_else(|d| fail(d, &sources));
+    let mut parser = Parser::new(tokens, source_dir.to_path_buf(), &mut sources);
+    let (mut ast, syntax_errors) = parser.parse();
+    if !syntax_errors.is_empty() { fail_all(&syntax_errors, &sources); }
+    if doc_only {
+        print!("{}", docgen::render_markdown(&ast));
+        return;
+    }
+    let mut sema = Sema::new();
+    let (types, type_errors) = sema.check(&mut ast);
+    if !type_errors.is_empty() { fail_all(&type_errors, &sources); }
     let mut compiler = Compiler::new();
-    let ir = compiler.compile(&ast);
+    compiler.set_file_names(sources.names());
+    if checked { compiler.enable_overflow_checks(); }
+    if unchecked { compiler.disable_bounds_checks(); }
+    let ir = compiler.compile(&ast, types).unwrap_or_else(|d| fail(d, &sources));
+    for warning in &compiler.warnings {
+        render_diagnostic(warning, &sources);
+    }
     
     let ll_path = dist_dir.join(format!("{}.ll", file_stem));
     fs::write(&ll_path, ir).unwrap();
@@ -167,7 +258,7 @@ fn main() {
         let p = exe_p.parent().unwrap_or(&exe_p);
         let proj_root = p.parent().unwrap_or(p).parent().unwrap_or(p);
 
-        if let Some(_) = exe_p.parent() {
+        if exe_p.parent().is_some() {
             // Development: compiler/target/debug/aura.exe -> proj_root is compiler/
             let runtime_file = proj_root.join("src").join("compiler").join("aura_runtime.c");
             if runtime_file.exists() {
@@ -0,0 +1,390 @@
+//! Compiles small Aura programs with the `aura` binary and checks the generated LLVM IR.
+
+use std::fs;
+use std::process::Command;
+
+/// Writes `source` to a fresh directory as main.aur and compiles it. Returns dist/main.ll,
+/// if it was generated, and the compiler's output.
+fn build(test: &str, source: &str) -> (Option<String>, String) {
+    let dir = std::env::temp_dir().join(format!("aura-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.aur"), source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aura")).arg("build").arg(dir.join("main.aur")).output().unwrap();
+    let ir = fs::read_to_string(dir.join("dist").join("main.ll")).ok();
+    let _ = fs::remove_dir_all(&dir);
+    (ir, format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Compiles `source` and returns the generated IR.
+fn compile(test: &str, source: &str) -> String {
+    let (ir, output) = build(test, source);
+    ir.unwrap_or_else(|| panic!("no IR was generated:\n{}", output))
+}
+
+/// Compiles `source`, which must be rejected, and returns the diagnostics.
+fn errors(test: &str, source: &str) -> String {
+    let (ir, output) = build(test, source);
+    assert!(ir.is_none(), "the program was accepted:\n{}", output);
+    output
+}
+
+/// The body of the function defined as `@name` in the IR.
+fn function<'a>(ir: &'a str, name: &str) -> &'a str {
+    let header = format!(" @{}(", name);
+    let start = ir.match_indices("define ").map(|(start, _)| start)
+        .find(|&start| ir[start..].lines().next().is_some_and(|line| line.contains(&header)))
+        .unwrap_or_else(|| panic!("'{}' is not defined in:\n{}", name, ir));
+    let end = ir[start..].find("\n}\n").map_or(ir.len(), |end| start + end);
+    &ir[start..end]
+}
+
+/// Checks that all allocas of a function body are in its entry block, before the first label.
+fn assert_allocas_in_entry(body: &str) {
+    let entry_end = body.find("\nL").unwrap_or_else(|| panic!("no label in:\n{}", body));
+    assert!(!body[entry_end..].contains(" = alloca "), "{}", body);
+}
+
+#[test]
+fn compound_assignment_evaluates_object_and_index_once() {
+    let ir = compile("compound", r#"
+class Counter {
+    var n: int;
+}
+
+var counter = 0;
+func next() -> int {
+    counter++;
+    return counter;
+}
+
+func make() -> Counter {
+    return new Counter();
+}
+
+func update(arr: [int], m: {string: int}) {
+    arr[next()] += 5;
+    arr[next()]++;
+    m["k" + "ey"] -= 1;
+    make().n += 2;
+}
+"#);
+    let update = function(&ir, "fn_update");
+    assert_eq!(update.matches("call i64 @fn_next()").count(), 2, "{}", update);
+    assert_eq!(update.matches("@aura_str_concat").count(), 1, "{}", update);
+    assert_eq!(update.matches("@fn_make()").count(), 1, "{}", update);
+}
+
+#[test]
+fn multiplicative_compound_assignments() {
+    let ir = compile("mul_assign", r#"
+class Box {
+    var n: int;
+    var f: float;
+}
+
+func update(x: int, arr: [int], m: {string: int}, b: Box) -> int {
+    x *= 6;
+    arr[0] /= 4;
+    m["k"] %= 5;
+    b.f /= 2.0;
+    return x;
+}
+"#);
+    let update = function(&ir, "fn_update");
+    assert_eq!(update.matches("mul i64").count(), 1, "{}", update);
+    assert_eq!(update.matches("sdiv i64").count(), 1, "{}", update);
+    assert_eq!(update.matches("srem i64").count(), 1, "{}", update);
+    assert_eq!(update.matches("fdiv double").count(), 1, "{}", update);
+}
+
+#[test]
+fn global_is_initialized_before_a_function_reads_it() {
+    let ir = compile("lazy_global", r#"
+func host() -> string {
+    return "srv";
+}
+
+func show() {
+    var i = 0;
+    while (i < 3) {
+        print(name);
+        i++;
+    }
+}
+
+show();
+var name = host() + "1";
+"#);
+    // show() runs before the declaration, so it computes the global itself, once on entry
+    let show = function(&ir, "fn_show");
+    let init = show.find("call void @g_name.init()").unwrap_or_else(|| panic!("{}", show));
+    assert_eq!(show.matches("call void @g_name.init()").count(), 1, "{}", show);
+    assert!(init < show.find("\nL").unwrap(), "{}", show);
+    // ...only the first time
+    let body = function(&ir, "g_name.init");
+    assert!(body.contains("load i1, i1* @g_name.ready"), "{}", body);
+    assert!(body.contains("store i1 1, i1* @g_name.ready"), "{}", body);
+    assert!(function(&ir, "main").contains("call void @g_name.init()"), "{}", ir);
+}
+
+#[test]
+fn constant_initializers_are_folded() {
+    let ir = compile("const_fold", r#"
+const PORT = 40 + 2;
+const HALF = PORT / 4.0;
+const URL = "http://" + "localhost";
+const MASK = -(1 << 4);
+var started = PORT * 2;
+"#);
+    assert!(ir.contains("@g_PORT = constant i64 42"), "{}", ir);
+    assert!(ir.contains(&format!("@g_HALF = constant double 0x{:016X}", 10.5f64.to_bits())), "{}", ir);
+    assert!(ir.contains("@g_URL = constant i8* getelementptr"), "{}", ir);
+    assert!(ir.contains("c\"http://localhost\\00\""), "{}", ir);
+    assert!(ir.contains("@g_MASK = constant i64 -16"), "{}", ir);
+    assert!(ir.contains("@g_started = global i64 84"), "{}", ir);
+}
+
+#[test]
+fn render_list_allocates_in_the_entry_block() {
+    let ir = compile("render_list", r#"
+import "system";
+
+class Item {
+    var name: string;
+}
+
+func pages(items: [Item], n: int) -> string {
+    var html = "";
+    var i = 0;
+    while (i < n) {
+        html = html + system.mvc.render_list("<ul>{items}</ul>", "{items}", items, "<li>{name}</li>");
+        i++;
+    }
+    return html;
+}
+"#);
+    let pages = function(&ir, "fn_pages");
+    assert!(pages.contains("@aura_str_replace"), "{}", pages);
+    assert_allocas_in_entry(pages);
+}
+
+#[test]
+fn only_string_handlers_are_routes() {
+    let ir = compile("routes", r#"
+import "system";
+
+class Api {
+    var hits: int;
+    func init() { this.hits = 0; }
+    func count() -> int { return this.hits; }
+    func hello(id: int) -> string { return "hello ${id}"; }
+    func reset(id: int) { this.hits = id; }
+}
+
+system.net.api_listen(8080, new Api());
+"#);
+    let main = function(&ir, "main");
+    assert!(main.contains("@fn_Api_hello to i8*"), "{}", main);
+    for method in ["init", "count", "reset"] {
+        assert!(!main.contains(&format!("@fn_Api_{} to i8*", method)), "{}", main);
+    }
+}
+
+#[test]
+fn inherited_and_overridden_handlers_are_routes() {
+    let ir = compile("inherited_routes", r#"
+import "system";
+
+class Base {
+    func init() {}
+    func status(id: int) -> string { return "ok"; }
+    func page(id: int) -> string { return "base"; }
+    func size() -> int { return 1; }
+}
+
+class Site extends Base {
+    func page(id: int) -> string { return "site"; }
+}
+
+system.net.api_listen(8080, new Site());
+"#);
+    let main = function(&ir, "main");
+    assert!(main.contains("@fn_Base_status to i8*"), "{}", main);
+    assert!(main.contains("@fn_Site_page to i8*"), "{}", main);
+    for method in ["Base_page", "Base_init", "Site_init", "Base_size"] {
+        assert!(!main.contains(&format!("@fn_{} to i8*", method)), "{}", main);
+    }
+}
+
+#[test]
+fn signature_mismatches_have_their_own_codes() {
+    let output = errors("mismatch_codes", r#"
+interface Named {
+    func name() -> string;
+}
+
+class Base {
+    func size() -> int { return 1; }
+}
+
+class Child extends Base implements Named {
+    func size() -> string { return "1"; }
+    func name() -> int { return 2; }
+}
+
+class Loop extends Loop {}
+"#);
+    assert!(output.contains("error[E0321]: 'Child.size' overrides 'Base.size'"), "{}", output);
+    assert!(output.contains("error[E0322]: 'Child.name' implements 'Named.name'"), "{}", output);
+    assert!(output.contains("error[E0318]: class 'Loop' cannot extend 'Loop'"), "{}", output);
+}
+
+#[test]
+fn malformed_number_literals_are_rejected() {
+    for (literal, message) in [
+        ("1_", "'_' in a number literal must be between two digits"),
+        ("1__0", "'_' in a number literal must be between two digits"),
+        ("0x_FF", "'_' in a number literal must be between two digits"),
+        ("0b102", "invalid digit '2' in binary literal"),
+        ("0xFG", "invalid digit 'G' in hexadecimal literal"),
+    ] {
+        let output = errors("number_literal", &format!("var x = {};\n", literal));
+        assert!(output.contains(&format!("error[E0103]: {}", message)), "{}: {}", literal, output);
+    }
+    let ir = compile("separators", "var x = 1_000 + 0xFF_FF + 0b1_0;\nprint(x);\n");
+    assert!(ir.contains("@g_x = global i64 66537"), "{}", ir);
+}
+
+#[test]
+fn loop_variables_are_allocated_in_the_entry_block() {
+    let ir = compile("foreach_alloca", r#"
+func total(rows: [[int]], names: {string: int}) -> int {
+    var sum = 0;
+    var i = 0;
+    while (i < 1000) {
+        foreach (row in rows) {
+            foreach (x in row) {
+                var doubled = x * 2;
+                sum += doubled;
+            }
+        }
+        foreach (name in names) { sum += names[name]; }
+        i++;
+    }
+    return sum;
+}
+"#);
+    let total = function(&ir, "fn_total");
+    assert!(total.contains("%x_ptr = alloca i64"), "{}", total);
+    assert_allocas_in_entry(total);
+}
+
+#[test]
+fn objects_leaving_their_scope_are_not_freed() {
+    let ir = compile("escape_push", r#"
+class User {
+    var name: string;
+    func init(name: string) { this.name = name; }
+}
+
+class Holder {
+    var user: User;
+}
+
+func keep(users: [User], holder: Holder) -> string {
+    var i = 0;
+    while (i < 3) {
+        var pushed = new User("pushed");
+        users.push(pushed);
+        var held = new User("held");
+        holder.user = held;
+        var scoped = new User("scoped");
+        print_str(scoped.name);
+        i++;
+    }
+    return users[0].name + holder.user.name;
+}
+
+func make() -> User {
+    var made = new User("made");
+    return made;
+}
+"#);
+    // Only `scoped` is freed at the end of the loop body
+    let keep = function(&ir, "fn_keep");
+    assert_eq!(keep.matches("call void @free(").count(), 1, "{}", keep);
+    assert!(!function(&ir, "fn_make").contains("@free("), "{}", ir);
+}
+
+#[test]
+fn objects_stored_in_maps_are_not_freed() {
+    let ir = compile("escape_map", r#"
+class User {
+    var name: string;
+    func init(name: string) { this.name = name; }
+}
+
+func index(m: {string: User}) -> {string: User} {
+    var i = 0;
+    while (i < 3) {
+        var stored = new User("stored");
+        m["u${i}"] = stored;
+        var listed = new User("listed");
+        var other = {"first": listed};
+        if (other.has("first")) { m["first"] = other["first"]; }
+        i++;
+    }
+    return m;
+}
+"#);
+    let index = function(&ir, "fn_index");
+    assert!(!index.contains("@free("), "{}", index);
+}
+
+#[test]
+fn objects_stored_in_globals_are_not_freed() {
+    let ir = compile("escape_global", r#"
+class User {
+    var name: string;
+    func init(name: string) { this.name = name; }
+}
+
+var current = new User("nobody");
+
+func login(name: string) {
+    if (name != "") {
+        var user = new User(name);
+        current = user;
+    }
+    print_str(current.name);
+}
+"#);
+    let login = function(&ir, "fn_login");
+    assert!(login.contains(", %struct.User** @g_current\n"), "{}", login);
+    assert!(!login.contains("@free("), "{}", login);
+}
+
+#[test]
+fn closures_and_array_callbacks() {
+    let ir = compile("closures", r#"
+func scores(rows: [[int]], bonus: int) -> int {
+    var total = 0;
+    var i = 0;
+    while (i < 100) {
+        foreach (row in rows) {
+            var kept = row.map(x => x + bonus).filter(x => x > 10);
+            total += kept.reduce((acc, x) => acc + x, 0);
+        }
+        i++;
+    }
+    return total;
+}
+"#);
+    let scores = function(&ir, "fn_scores");
+    // `bonus` is captured, so it lives in a heap cell that the lambda's environment points to
+    assert!(scores.contains("%bonus_ptr = bitcast i8*"), "{}", scores);
+    assert_eq!(scores.matches("to %aura.closure*").count(), 1, "{}", scores);
+    assert!(ir.contains("constant %aura.closure"), "{}", ir);
+    assert_allocas_in_entry(scores);
+}